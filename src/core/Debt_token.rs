@@ -28,9 +28,13 @@ use bitcoin::{self, Transaction}; // Import bitcoin crate and Transaction struc
 
 // Constants
 const FLASH_LOAN_FEE: u128 = 9; // 0.09% fee, similar to Solidity's 0.09%
+const PERMIT_VERSION: &str = "1";
 
 // Add a mapping to store authorized Trove Managers
 use std::collections::HashSet;
+use crate::dependencies::protocol_error::ProtocolError;
+use crate::dependencies::signed_permit::SignedPermit;
+use crate::interfaces::flash_borrower::{FlashBorrower, FLASH_LOAN_CALLBACK_SUCCESS};
 
 #[derive(BorshSerialize, BorshDeserialize)]
 pub struct DebtToken {
@@ -45,31 +49,29 @@ pub struct DebtToken {
     factory: AccountId, // Account ID of the factory
     borrower_operations: AccountId, // Account ID of the BorrowerOperations contract
     stability_pool: AccountId, // Account ID of the StabilityPool contract
+    global_debt_cap: Balance, // Cap on total supply across all collaterals, zero until governance sets it
+    gas_pool: AccountId, // Holds the liquidation reserve of every open trove
+    debt_gas_compensation: Balance, // Reserve minted to the gas pool per trove, the only copy of this setting
+    flash_loan_cap: Balance, // Max amount that can be flash minted at once
+    fee_receiver: AccountId, // Receives flash loan fees, unset (default) until governance sets it
+    program_id: AccountId, // Account of this token, part of the permit domain
+    nonces: HashMap<AccountId, u64>, // Permit nonces
 }
 
 pub type AccountId = Pubkey; // Use Pubkey for AccountId
 pub type Balance = u128;
 
 impl DebtToken {
-    // Add error types for unauthorized access and invalid operations
-    pub enum DebtTokenError {
-        UnauthorizedAccess,
-        InvalidOperation,
-        // Existing errors...
-        InsufficientFunds,
-        InvalidAccountData,
-    }
-
-    // Modify existing methods to use new error types
-    pub fn mint(&mut self, account: AccountId, amount: Balance, account_info: &AccountInfo) -> Result<(), DebtTokenError> {
+    pub fn mint(&mut self, account: AccountId, amount: Balance, account_info: &AccountInfo) -> Result<(), ProtocolError> {
         // Check if the caller is authorized as a Trove Manager or Borrower Operations
         if !self.trove_managers.contains(&account_info.key) && account_info.key != self.borrower_operations {
-            return Err(DebtTokenError::UnauthorizedAccess);
+            return Err(ProtocolError::Unauthorized);
         }
+        self.require_mint_capacity(amount)?;
 
         // Validate account ownership using Arch SDK
         if !self.validate_utxo_ownership(account_info)? {
-            return Err(DebtTokenError::InvalidAccountData);
+            return Err(ProtocolError::InvalidUtxoOwnership);
         }
 
         // Create a transaction to sign
@@ -88,16 +90,14 @@ impl DebtToken {
         set_transaction_to_sign(tx_to_sign)?;
 
         // Mint the tokens
-        let balance = self.balances.entry(account).or_default();
-        *balance += amount;
-        self.total_supply += amount;
+        self._mint(account, amount);
 
         Ok(())
     }
 
     // Similar changes for other methods...
 
-    pub fn new(name: String, symbol: String) -> Self {
+    pub fn new(name: String, symbol: String, program_id: AccountId, gas_pool: AccountId, debt_gas_compensation: Balance) -> Self {
         Self {
             name,
             symbol,
@@ -110,43 +110,48 @@ impl DebtToken {
             factory: Pubkey::default(),
             borrower_operations: Pubkey::default(),
             stability_pool: Pubkey::default(),
+            global_debt_cap: 0,
+            gas_pool,
+            debt_gas_compensation,
+            flash_loan_cap: 0,
+            fee_receiver: Pubkey::default(),
+            program_id,
+            nonces: HashMap::new(),
         }
     }
 
-    pub fn burn(&mut self, account: AccountId, amount: Balance, account_info: &AccountInfo) -> Result<(), ProgramError> {
+    pub fn burn(&mut self, account: AccountId, amount: Balance, account_info: &AccountInfo) -> Result<(), ProtocolError> {
         // Validate account ownership using Arch SDK
         if !self.validate_utxo_ownership(account_info)? {
-            return Err(ProgramError::InvalidAccountData);
+            return Err(ProtocolError::InvalidUtxoOwnership);
         }
 
         // Check if the caller is a TroveManager
         if !self.trove_managers.contains(&account_info.key) {
-            return Err(ProgramError::InvalidInstructionData);
+            return Err(ProtocolError::Unauthorized);
         }
 
-        let balance = self.balances.entry(account).or_default();
-        if *balance < amount {
-            return Err(ProgramError::InsufficientFunds);
+        if self.balances.get(&account).cloned().unwrap_or(0) < amount {
+            return Err(ProtocolError::InsufficientBalance);
         }
-        *balance -= amount;
-        self.total_supply -= amount;
+        self._burn(account, amount);
         Ok(())
     }
 
-    pub fn transfer(&mut self, from: AccountId, to: AccountId, amount: Balance, from_info: &AccountInfo, to_info: &AccountInfo) -> Result<(), ProgramError> {
+    pub fn transfer(&mut self, from: AccountId, to: AccountId, amount: Balance, from_info: &AccountInfo, to_info: &AccountInfo) -> Result<(), ProtocolError> {
         // Validate account ownership using Arch SDK
         if !self.validate_utxo_ownership(from_info)? || !self.validate_utxo_ownership(to_info)? {
-            return Err(ProgramError::InvalidAccountData);
+            return Err(ProtocolError::InvalidUtxoOwnership);
         }
 
         // Recipient validation (similar to Solidity's _requireValidRecipient)
         if to == self.get_account_id() || to == self.stability_pool || self.trove_managers.contains(&to) || to == self.borrower_operations {
-            return Err(ProgramError::InvalidInstructionData);
+            return Err(ProtocolError::InvalidRecipient);
         }
 
         let from_balance = self.balances.entry(from).or_default();
         if *from_balance < amount {
-            return Err(ProgramError::InsufficientFunds);
+            return Err(ProtocolError::InsufficientBalance);
         }
         *from_balance -= amount;
 
@@ -155,10 +160,10 @@ impl DebtToken {
         Ok(())
     }
 
-    pub fn issue_debt(&mut self, user: AccountId, amount: Balance, account_info: &AccountInfo) -> Result<(), ProgramError> {
+    pub fn issue_debt(&mut self, user: AccountId, amount: Balance, account_info: &AccountInfo) -> Result<(), ProtocolError> {
         // Validate account ownership using Arch SDK
         if !self.validate_utxo_ownership(account_info)? {
-            return Err(ProgramError::InvalidAccountData);
+            return Err(ProtocolError::InvalidUtxoOwnership);
         }
 
 
@@ -188,7 +193,7 @@ impl DebtToken {
             *user_balance += amount;
             Ok(())
         } else {
-            Err(ProgramError::InsufficientFunds)
+            Err(ProtocolError::InsufficientBalance)
         }
     }
 
@@ -210,55 +215,115 @@ impl DebtToken {
     }
 
     // Function to calculate flash loan fee
-    fn calculate_flash_loan_fee(&self, amount: Balance) -> Balance {
+    pub fn flash_fee(&self, amount: Balance) -> Balance {
         (amount * FLASH_LOAN_FEE) / 10000
     }
 
-    // Flash loan function
-    pub fn flash_loan(&mut self, receiver: AccountId, amount: Balance, account_info: &AccountInfo, data: Vec<u8>) -> Result<(), ProgramError> {
+    // Flash loan function. The loan fails if the callback does not return the success value or the
+    // receiver cannot repay `amount + fee` from its balance.
+    pub fn flash_loan(
+        &mut self,
+        receiver: &mut dyn FlashBorrower,
+        receiver_id: AccountId,
+        initiator: AccountId,
+        amount: Balance,
+        data: Vec<u8>,
+        account_info: &AccountInfo,
+    ) -> Result<(), ProtocolError> {
         // Validate account ownership using Arch SDK
         if !self.validate_utxo_ownership(account_info)? {
-            return Err(ProgramError::InvalidAccountData);
+            return Err(ProtocolError::InvalidUtxoOwnership);
         }
 
+        let fee = self.revertible_flash_loan(receiver, receiver_id, initiator, amount, &data)?;
+
+        msg!("FlashLoan: receiver = {:?}, amount = {}, fee = {}", receiver_id, amount, fee);
+        Ok(())
+    }
+
+    // Runs a flash loan and returns its fee. On failure this token is restored to its state before
+    // the loan, which undoes the mint and any token operations made during the callback; state the
+    // receiver keeps outside this token is not restored.
+    fn revertible_flash_loan(
+        &mut self,
+        receiver: &mut dyn FlashBorrower,
+        receiver_id: AccountId,
+        initiator: AccountId,
+        amount: Balance,
+        data: &[u8],
+    ) -> Result<Balance, ProtocolError> {
         // Ensure the token amount requested does not exceed the maximum allowed
-        if amount > self.max_flash_loan()? {
-            return Err(ProgramError::InvalidInstructionData);
+        if amount > self.max_flash_loan() {
+            return Err(ProtocolError::FlashLoanTooLarge);
+        }
+        if self.fee_receiver == Pubkey::default() {
+            return Err(ProtocolError::FeeReceiverNotSet);
         }
 
-        // Calculate fee
-        let fee = self.calculate_flash_loan_fee(amount);
-
-        // Mint the amount to the receiver
-        let receiver_balance = self.balances.entry(receiver).or_insert(0);
-        *receiver_balance += amount;
+        let fee = self.flash_fee(amount);
+        let snapshot = self.try_to_vec().map_err(|_| ProgramError::InvalidAccountData)?;
 
-        // Simulate the flash loan receiver logic (must be implemented by the receiver)
-        // This is a placeholder for actual interaction with a receiver contract that handles the loan
-        if !self.invoke_flash_loan_receiver(receiver, amount, fee, data, &[account_info.clone()])? {
-            return Err(ProgramError::CustomError(0)); // Custom error for flash loan failure
+        if let Err(e) = self.execute_flash_loan(receiver, receiver_id, initiator, amount, fee, data) {
+            *self = Self::try_from_slice(&snapshot).map_err(|_| ProgramError::InvalidAccountData)?;
+            msg!("Flash loan reverted");
+            return Err(e);
         }
+        Ok(fee)
+    }
 
-        // Burn the tokens returned by the receiver
-        *receiver_balance -= amount + fee;
-
-        // Transfer fee to a fee account or similar
-        let fee_account_balance = self.balances.entry(self.fee_account).or_insert(0);
-        *fee_account_balance += fee;
+    fn execute_flash_loan(
+        &mut self,
+        receiver: &mut dyn FlashBorrower,
+        receiver_id: AccountId,
+        initiator: AccountId,
+        amount: Balance,
+        fee: Balance,
+        data: &[u8],
+    ) -> Result<(), ProtocolError> {
+        self._mint(receiver_id, amount);
+
+        if receiver.on_flash_loan(self, initiator, amount, fee, data) != FLASH_LOAN_CALLBACK_SUCCESS {
+            msg!("FlashLoan: callback failed");
+            return Err(ProtocolError::FlashLoanCallbackFailed);
+        }
 
+        // Pull the repayment from the receiver's balance
+        if self.balances.get(&receiver_id).cloned().unwrap_or(0) < amount + fee {
+            msg!("FlashLoan: repayment exceeds receiver balance");
+            return Err(ProtocolError::InsufficientBalance);
+        }
+        self._burn(receiver_id, amount);
+        *self.balances.entry(receiver_id).or_insert(0) -= fee;
+        *self.balances.entry(self.fee_receiver).or_insert(0) += fee;
         Ok(())
     }
 
-    // Helper function to check the maximum flash loan available
-    fn max_flash_loan(&self) -> Result<Balance, ProgramError> {
-        Ok(u128::MAX - self.total_supply)
+    // Function to get the maximum flash loan available
+    pub fn max_flash_loan(&self) -> Balance {
+        self.flash_loan_cap.min(Balance::MAX - self.total_supply)
     }
 
-    // Placeholder for invoking the flash loan receiver logic
-    fn invoke_flash_loan_receiver(&self, receiver: AccountId, amount: Balance, fee: Balance, data: Vec<u8>, account_infos: &[AccountInfo]) -> Result<bool, ProgramError> {
-        // This should interact with an external contract or logic that handles the received amount and fee
-        // For now, we simulate successful handling
-        Ok(true)
+    // Function for governance to set the flash loan cap
+    pub fn set_flash_loan_cap(&mut self, flash_loan_cap: Balance) -> Result<(), ProtocolError> {
+        if get_caller() != self.factory {
+            return Err(ProtocolError::Unauthorized);
+        }
+        self.flash_loan_cap = flash_loan_cap;
+        msg!("FlashLoanCapSet: {}", flash_loan_cap);
+        Ok(())
+    }
+
+    // Function for governance to set the account receiving flash loan fees. Flash loans are
+    // disabled until it is set.
+    pub fn set_fee_receiver(&mut self, fee_receiver: AccountId) -> Result<(), ProtocolError> {
+        if get_caller() != self.factory {
+            return Err(ProtocolError::Unauthorized);
+        }
+        if fee_receiver == Pubkey::default() {
+            return Err(ProtocolError::FeeReceiverNotSet);
+        }
+        self.fee_receiver = fee_receiver;
+        Ok(())
     }
 
     pub fn add_collateral(&mut self, user: AccountId, amount: Balance) {
@@ -266,83 +331,234 @@ impl DebtToken {
         *collateral_balance += amount;
     }
 
+    // Malformed caller-supplied bytes fail verification instead of panicking
     pub fn verify_signature(&self, message: &[u8], sig: &[u8], pub_key: &[u8]) -> bool {
         let secp = Secp256k1::new();
-        let message = Message::from_slice(message).expect("32 bytes");
-        let sig = Signature::from_der(sig).expect("Signature in DER format");
-        let pub_key = PublicKey::from_slice(pub_key).expect("Public key");
+        let (message, sig, pub_key) = match (
+            Message::from_digest_slice(message),
+            Signature::from_der(sig),
+            PublicKey::from_slice(pub_key),
+        ) {
+            (Ok(message), Ok(sig), Ok(pub_key)) => (message, sig, pub_key),
+            _ => return false,
+        };
 
         secp.verify(&message, &sig, &pub_key).is_ok()
     }
 
-    // Function to authorize Trove Managers by the factory
-    pub fn enable_trove_manager(&mut self, trove_manager: AccountId) -> Result<(), DebtTokenError> {
-        if get_caller() != self.factory {
-            return Err(DebtTokenError::UnauthorizedAccess);
+    // Function to approve `spender` with a BIP-340 signature of `owner`'s x-only key over the permit digest
+    pub fn permit(
+        &mut self,
+        owner: AccountId,
+        spender: AccountId,
+        value: Balance,
+        deadline: u64,
+        signature: [u8; 64],
+    ) -> Result<(), ProtocolError> {
+        if self.current_time() > deadline {
+            return Err(ProtocolError::PermitExpired);
+        }
+        let nonce = self.nonces(owner);
+        let digest = SignedPermit::permit_digest(
+            &self.domain_separator(),
+            &owner.serialize(),
+            &spender.serialize(),
+            value,
+            nonce,
+            deadline,
+        );
+        if !SignedPermit::verify(&owner.serialize(), &digest, &signature) {
+            return Err(ProtocolError::InvalidSignature);
         }
+
+        self.nonces.insert(owner, nonce + 1);
+        self.allowances.insert((owner, spender), value);
+        msg!("Approval: owner = {:?}, spender = {:?}, value = {}", owner, spender, value);
+        Ok(())
+    }
+
+    pub fn nonces(&self, owner: AccountId) -> u64 {
+        self.nonces.get(&owner).cloned().unwrap_or(0)
+    }
+
+    pub fn domain_separator(&self) -> [u8; 32] {
+        SignedPermit::domain_separator(&self.name, PERMIT_VERSION, &self.program_id.serialize())
+    }
+
+    fn current_time(&self) -> u64 {
+        use std::time::{SystemTime, UNIX_EPOCH};
+
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+    }
+
+    // Function to authorize Trove Managers by the factory. Each collateral's debt ceiling is the
+    // max system debt of its Trove Manager, checked by BorrowerOperations before minting.
+    pub fn enable_trove_manager(&mut self, trove_manager: AccountId) -> Result<(), ProtocolError> {
+        self.ensure_factory()?;
         self.trove_managers.insert(trove_manager);
         Ok(())
     }
 
+    pub fn is_trove_manager(&self, trove_manager: &AccountId) -> bool {
+        self.trove_managers.contains(trove_manager)
+    }
+
+    // Checks that the factory is calling, so it can also validate a deployment before changing anything
+    pub fn ensure_factory(&self) -> Result<(), ProtocolError> {
+        if get_caller() != self.factory {
+            return Err(ProtocolError::Unauthorized);
+        }
+        Ok(())
+    }
+
+    // Function for governance to set the cap on new borrowing across all collaterals
+    pub fn set_global_debt_cap(&mut self, global_debt_cap: Balance) -> Result<(), ProtocolError> {
+        if get_caller() != self.factory {
+            return Err(ProtocolError::Unauthorized);
+        }
+        self.global_debt_cap = global_debt_cap;
+        msg!("GlobalDebtCapSet: {}", global_debt_cap);
+        Ok(())
+    }
+
+    pub fn get_global_debt_headroom(&self) -> Balance {
+        self.global_debt_cap.saturating_sub(self.total_supply)
+    }
+
+    // Checks a mint against the global cap. The cap starts at zero, so nothing can be minted
+    // until governance sets it through the factory.
+    pub fn require_mint_capacity(&self, amount: Balance) -> Result<(), ProtocolError> {
+        let headroom = self.get_global_debt_headroom();
+        if amount > headroom {
+            return Err(ProtocolError::GlobalDebtCapExceeded { requested: amount, headroom });
+        }
+        Ok(())
+    }
+
     // Helper function to get the account ID of the contract itself
     fn get_account_id(&self, program_id: &Pubkey) -> AccountId {
         // Assuming `program_id` is passed to the function which represents the ID of the current program
         *program_id
     }
 
-    // Function to handle minting with gas compensation
-    pub fn mint_with_gas_compensation(&mut self, account: AccountId, amount: Balance) -> Result<(), DebtTokenError> {
-        if self.borrower_operations != get_caller() {
-            return Err(DebtTokenError::UnauthorizedAccess);
+    // Function to mint a new trove's debt to its owner and its liquidation reserve to the gas pool
+    pub fn mint_with_gas_compensation(&mut self, account: AccountId, amount: Balance, account_info: &AccountInfo) -> Result<(), ProtocolError> {
+        if account_info.key != self.borrower_operations {
+            return Err(ProtocolError::Unauthorized);
         }
-        self.mint(account, amount)?;
-        self.mint(self.gas_pool, self.debt_gas_compensation)?;
+        self.require_mint_capacity(amount + self.debt_gas_compensation)?;
+        self._mint(account, amount);
+        self._mint(self.gas_pool, self.debt_gas_compensation);
         Ok(())
     }
 
-    // Function to handle burning with gas compensation
-    pub fn burn_with_gas_compensation(&mut self, account: AccountId, amount: Balance, account_info: &AccountInfo) -> Result<(), DebtTokenError> {
+    // Function to burn a closed trove's net debt from its owner and its liquidation reserve from the gas pool
+    pub fn burn_with_gas_compensation(&mut self, account: AccountId, amount: Balance, account_info: &AccountInfo) -> Result<(), ProtocolError> {
         if account_info.key != self.borrower_operations {
-            return Err(DebtTokenError::UnauthorizedAccess);
+            return Err(ProtocolError::Unauthorized);
+        }
+        if self.balances.get(&account).cloned().unwrap_or(0) < amount
+            || self.balances.get(&self.gas_pool).cloned().unwrap_or(0) < self.debt_gas_compensation
+        {
+            return Err(ProtocolError::InsufficientBalance);
+        }
+        self._burn(account, amount);
+        self._burn(self.gas_pool, self.debt_gas_compensation);
+        Ok(())
+    }
+
+    // Function to burn redeemed debt from the redeemer, and the liquidation reserves of the troves
+    // the redemption closed from the gas pool
+    pub fn burn_redemption(
+        &mut self,
+        redeemer: AccountId,
+        debt_amount: Balance,
+        gas_compensation: Balance,
+        account_info: &AccountInfo,
+    ) -> Result<(), ProtocolError> {
+        if !self.trove_managers.contains(&account_info.key) {
+            return Err(ProtocolError::Unauthorized);
         }
-        self.burn(account, amount, account_info)?;
-        self.burn(self.gas_pool, self.debt_gas_compensation, account_info)?;
+        if self.balances.get(&redeemer).cloned().unwrap_or(0) < debt_amount
+            || self.balances.get(&self.gas_pool).cloned().unwrap_or(0) < gas_compensation
+        {
+            return Err(ProtocolError::InsufficientBalance);
+        }
+        self._burn(redeemer, debt_amount);
+        self._burn(self.gas_pool, gas_compensation);
         Ok(())
     }
 
+    // Function to pay a liquidator the reserves of the troves they liquidated
+    pub fn pay_gas_compensation(&mut self, liquidator: AccountId, amount: Balance, account_info: &AccountInfo) -> Result<(), ProtocolError> {
+        if !self.trove_managers.contains(&account_info.key) {
+            return Err(ProtocolError::Unauthorized);
+        }
+        let gas_pool_balance = self.balances.entry(self.gas_pool).or_insert(0);
+        if *gas_pool_balance < amount {
+            return Err(ProtocolError::InsufficientBalance);
+        }
+        *gas_pool_balance -= amount;
+        *self.balances.entry(liquidator).or_insert(0) += amount;
+        msg!("GasCompensationPaid: liquidator = {:?}, amount = {}", liquidator, amount);
+        Ok(())
+    }
+
+    pub fn balance_of(&self, account: AccountId) -> Balance {
+        self.balances.get(&account).cloned().unwrap_or(0)
+    }
+
+    pub fn gas_pool(&self) -> AccountId {
+        self.gas_pool
+    }
+
+    pub fn debt_gas_compensation(&self) -> Balance {
+        self.debt_gas_compensation
+    }
+
+    fn _mint(&mut self, account: AccountId, amount: Balance) {
+        *self.balances.entry(account).or_insert(0) += amount;
+        self.total_supply += amount;
+    }
+
+    fn _burn(&mut self, account: AccountId, amount: Balance) {
+        *self.balances.entry(account).or_insert(0) -= amount;
+        self.total_supply -= amount;
+    }
+
     // Function to send debt tokens to the Stability Pool
-    pub fn send_to_sp(&mut self, sender: AccountId, amount: Balance, sender_info: &AccountInfo) -> Result<(), DebtTokenError> {
+    pub fn send_to_sp(&mut self, sender: AccountId, amount: Balance, sender_info: &AccountInfo) -> Result<(), ProtocolError> {
         if sender_info.key != self.stability_pool {
-            return Err(DebtTokenError::UnauthorizedAccess);
+            return Err(ProtocolError::Unauthorized);
         }
         self.transfer(sender, self.stability_pool, amount, sender_info, &AccountInfo::from(self.stability_pool))?;
         Ok(())
     }
 
     // Function to return debt tokens from the pool
-    pub fn return_from_pool(&mut self, pool: AccountId, receiver: AccountId, amount: Balance, pool_info: &AccountInfo) -> Result<(), DebtTokenError> {
+    pub fn return_from_pool(&mut self, pool: AccountId, receiver: AccountId, amount: Balance, pool_info: &AccountInfo) -> Result<(), ProtocolError> {
         if pool_info.key != self.stability_pool && !self.trove_managers.contains(&pool_info.key) {
-            return Err(DebtTokenError::UnauthorizedAccess);
+            return Err(ProtocolError::Unauthorized);
         }
         self.transfer(pool, receiver, amount, pool_info, &AccountInfo::from(receiver))?;
         Ok(())
     }
 
     // Helper function to transfer tokens with checks
-    fn transfer(&mut self, from: AccountId, to: AccountId, amount: Balance, from_info: &AccountInfo, to_info: &AccountInfo) -> Result<(), DebtTokenError> {
+    fn transfer(&mut self, from: AccountId, to: AccountId, amount: Balance, from_info: &AccountInfo, to_info: &AccountInfo) -> Result<(), ProtocolError> {
         // Validate account ownership using Arch SDK
         if !self.validate_utxo_ownership(from_info)? || !self.validate_utxo_ownership(to_info)? {
-            return Err(DebtTokenError::InvalidAccountData);
+            return Err(ProtocolError::InvalidUtxoOwnership);
         }
 
         // Recipient validation (similar to Solidity's _requireValidRecipient)
         if to == self.get_account_id() || to == self.stability_pool || self.trove_managers.contains(&to) || to == self.borrower_operations {
-            return Err(DebtTokenError::InvalidOperation);
+            return Err(ProtocolError::InvalidRecipient);
         }
 
         let from_balance = self.balances.entry(from).or_default();
         if *from_balance < amount {
-            return Err(DebtTokenError::InsufficientFunds);
+            return Err(ProtocolError::InsufficientBalance);
         }
         *from_balance -= amount;
 
@@ -352,20 +568,103 @@ impl DebtToken {
     }
 }
 
-impl DebtToken {
-    // Cached domain separator
-    cached_domain_separator: Option<(u64, [u8; 32])>,
-
-    // Function to get or update the cached domain separator
-    fn domain_separator(&mut self) -> [u8; 32] {
-        let current_chain_id = get_chain_id();
-        if let Some((chain_id, separator)) = self.cached_domain_separator {
-            if chain_id == current_chain_id {
-                return separator;
-            }
-        }
-        let new_separator = self.calculate_domain_separator();
-        self.cached_domain_separator = Some((current_chain_id, new_separator));
-        new_separator
-    }
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AMOUNT: Balance = 10_000;
+
+    // Records what the token passed to the callback, then returns `result`
+    struct MockFlashBorrower {
+        id: AccountId,
+        result: [u8; 32],
+        received: Option<(AccountId, Balance, Balance, Balance)>,
+    }
+
+    impl MockFlashBorrower {
+        fn new(result: [u8; 32]) -> Self {
+            Self { id: Pubkey::new_unique(), result, received: None }
+        }
+    }
+
+    impl FlashBorrower for MockFlashBorrower {
+        fn on_flash_loan(&mut self, token: &mut DebtToken, initiator: Pubkey, amount: u128, fee: u128, _data: &[u8]) -> [u8; 32] {
+            self.received = Some((initiator, amount, fee, token.balance_of(self.id)));
+            self.result
+        }
+    }
+
+    fn debt_token() -> DebtToken {
+        let mut token = DebtToken::new("Babel USD".to_string(), "bUSD".to_string(), Pubkey::new_unique(), Pubkey::new_unique(), 200);
+        token.flash_loan_cap = 1_000_000;
+        token.fee_receiver = Pubkey::new_unique();
+        token
+    }
+
+    #[test]
+    fn test_malformed_signature_inputs_fail_verification() {
+        let token = debt_token();
+        assert!(!token.verify_signature(&[0u8; 31], &[0u8; 70], &[2u8; 33]));
+        assert!(!token.verify_signature(&[1u8; 32], &[], &[2u8; 33]));
+        assert!(!token.verify_signature(&[1u8; 32], &[0u8; 70], &[]));
+    }
+
+    #[test]
+    fn test_flash_loan_is_repaid_with_the_fee() {
+        let mut token = debt_token();
+        let mut borrower = MockFlashBorrower::new(FLASH_LOAN_CALLBACK_SUCCESS);
+        let initiator = Pubkey::new_unique();
+        let fee = token.flash_fee(AMOUNT);
+        assert_eq!(fee, 9);
+        // The borrower already holds the fee, as if earned during the callback
+        token._mint(borrower.id, fee);
+
+        assert_eq!(token.revertible_flash_loan(&mut borrower, borrower.id, initiator, AMOUNT, &[]), Ok(fee));
+        assert_eq!(borrower.received, Some((initiator, AMOUNT, fee, AMOUNT + fee)));
+        assert_eq!(token.balance_of(borrower.id), 0);
+        assert_eq!(token.balance_of(token.fee_receiver), fee);
+        assert_eq!(token.total_supply, fee);
+    }
+
+    #[test]
+    fn test_failed_callback_reverts_the_loan() {
+        let mut token = debt_token();
+        let mut borrower = MockFlashBorrower::new([0; 32]);
+
+        let result = token.revertible_flash_loan(&mut borrower, borrower.id, Pubkey::new_unique(), AMOUNT, &[]);
+        assert_eq!(result, Err(ProtocolError::FlashLoanCallbackFailed));
+        assert!(borrower.received.is_some());
+        assert_eq!(token.balance_of(borrower.id), 0);
+        assert_eq!(token.total_supply, 0);
+    }
+
+    #[test]
+    fn test_unpaid_fee_reverts_the_loan() {
+        let mut token = debt_token();
+        let mut borrower = MockFlashBorrower::new(FLASH_LOAN_CALLBACK_SUCCESS);
+
+        let result = token.revertible_flash_loan(&mut borrower, borrower.id, Pubkey::new_unique(), AMOUNT, &[]);
+        assert_eq!(result, Err(ProtocolError::InsufficientBalance));
+        assert_eq!(token.balance_of(borrower.id), 0);
+        assert_eq!(token.balance_of(token.fee_receiver), 0);
+        assert_eq!(token.total_supply, 0);
+    }
+
+    #[test]
+    fn test_flash_loan_limits() {
+        let mut token = debt_token();
+        let mut borrower = MockFlashBorrower::new(FLASH_LOAN_CALLBACK_SUCCESS);
+        let too_large = token.max_flash_loan() + 1;
+        assert_eq!(
+            token.revertible_flash_loan(&mut borrower, borrower.id, Pubkey::new_unique(), too_large, &[]),
+            Err(ProtocolError::FlashLoanTooLarge)
+        );
+
+        token.fee_receiver = Pubkey::default();
+        assert_eq!(
+            token.revertible_flash_loan(&mut borrower, borrower.id, Pubkey::new_unique(), AMOUNT, &[]),
+            Err(ProtocolError::FeeReceiverNotSet)
+        );
+        assert!(borrower.received.is_none());
+    }
+}
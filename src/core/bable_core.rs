@@ -17,6 +17,9 @@ use arch_program::{
     utxo::UtxoMeta,
 };
 use bitcoin::{self, Transaction};
+use crate::core::price_feed::PriceFeed;
+use crate::dependencies::protocol_error::ProtocolError;
+use crate::dependencies::protocol_event::{AdminAction, ProtocolEvent};
 
 const OWNERSHIP_TRANSFER_DELAY: u64 = 86400 * 3; // 3 days
 
@@ -60,49 +63,62 @@ impl BabelCore {
     pub fn set_fee_receiver(&mut self, new_fee_receiver: String) {
         self.fee_receiver = new_fee_receiver;
         msg!("FeeReceiverSet: {}", new_fee_receiver); // Event-like log
+        self.emit_admin_action(AdminAction::FeeReceiverSet { fee_receiver: self.fee_receiver.clone() });
     }
 
     pub fn set_price_feed(&mut self, new_price_feed: String) {
         self.price_feed = new_price_feed;
         msg!("PriceFeedSet: {}", new_price_feed); // Event-like log
+        self.emit_admin_action(AdminAction::PriceFeedSet { price_feed: self.price_feed.clone() });
     }
 
     pub fn set_guardian(&mut self, new_guardian: Pubkey) {
         msg!("GuardianSet: Changed from {:?} to {:?}", self.guardian, new_guardian);
         self.guardian = new_guardian;
+        self.emit_admin_action(AdminAction::GuardianSet { guardian: new_guardian.to_string() });
     }
 
-    pub fn set_paused(&mut self, new_paused: bool) -> Result<(), ProgramError> {
+    pub fn set_paused(&mut self, new_paused: bool) -> Result<(), ProtocolError> {
         // Allow both the guardian and the owner to pause the system
         if new_paused && (self.guardian != self.owner) && (self.owner != get_caller()) {
-            return Err(ProgramError::Unauthorized);
+            return Err(ProtocolError::Unauthorized);
         }
         self.paused = new_paused;
         if new_paused {
             msg!("Paused"); // Event-like log for pausing
+            self.emit_admin_action(AdminAction::Paused);
         } else {
             msg!("Unpaused"); // Event-like log for unpausing
+            self.emit_admin_action(AdminAction::Unpaused);
         }
         Ok(())
     }
   
-    pub fn commit_transfer_ownership(&mut self, caller: String, new_owner: String, accounts: &[AccountInfo]) -> Result<(), ProgramError>{
+    pub fn commit_transfer_ownership(&mut self, caller: String, new_owner: String, accounts: &[AccountInfo]) -> Result<(), ProtocolError>{
         if self.is_owner(&caller) {
             self.pending_owner = Some(new_owner.clone());
             self.ownership_transfer_deadline = Some(SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() + OWNERSHIP_TRANSFER_DELAY);
             msg!("NewOwnerCommitted: Committed by {}, New owner pending: {}, Deadline: {}", caller, new_owner, self.ownership_transfer_deadline.unwrap());
+            ProtocolEvent::Admin {
+                caller: caller.to_string(),
+                action: AdminAction::OwnershipTransferStarted {
+                    new_owner: new_owner.to_string(),
+                    deadline: self.ownership_transfer_deadline.unwrap(),
+                },
+            }
+            .emit();
             // Additional logging for ownership commitment
-            let tx_bytes = self.serialize()?; // Serialize the current state
+            let tx_bytes = self.serialize().map_err(ProgramError::from)?; // Serialize the current state
             let inputs_to_sign = vec![InputToSign::new(caller.to_string(), tx_bytes.clone())]; // Create inputs to sign
             let transaction_to_sign = TransactionToSign::new(tx_bytes, inputs_to_sign); // Create the transaction to sign
             set_transaction_to_sign(accounts, transaction_to_sign)?; // Set the transaction to sign
             Ok(())
         } else {
-            Err(ProgramError::Unauthorized)
+            Err(ProtocolError::Unauthorized)
         }
     }
 
-    pub fn accept_transfer_ownership(&mut self, caller: String) -> Result<(), ProgramError> {
+    pub fn accept_transfer_ownership(&mut self, caller: String) -> Result<(), ProtocolError> {
         if let Some(ref pending_owner) = self.pending_owner {
             if caller == *pending_owner && SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() >= self.ownership_transfer_deadline.unwrap() {
                 msg!("NewOwnerAccepted: Ownership transferred from {} to {}", self.owner, pending_owner);
@@ -110,12 +126,17 @@ impl BabelCore {
                 self.pending_owner = None;
                 self.ownership_transfer_deadline = None;
                 msg!("NewOwnerAccepted: Ownership accepted by {}", caller);
+                ProtocolEvent::Admin {
+                    caller: caller.to_string(),
+                    action: AdminAction::OwnershipTransferred { new_owner: caller.to_string() },
+                }
+                .emit();
                 Ok(())
             } else {
-                Err(ProgramError::Unauthorized)
+                Err(ProtocolError::Unauthorized)
             }
         } else {
-            Err(ProgramError::InvalidArgument)
+            Err(ProtocolError::InvalidParameter)
         }
     }
 
@@ -123,12 +144,13 @@ impl BabelCore {
         msg!("NewOwnerRevoked: Revoked by {}, Pending owner was {}", self.owner, self.pending_owner.unwrap_or_default());
         self.pending_owner = None;
         self.ownership_transfer_deadline = None;
+        self.emit_admin_action(AdminAction::OwnershipTransferRevoked);
     }
 
-    pub fn transfer_utxo(&mut self, input_utxos: Vec<(Vec<u8>, u32)>, output_utxos: Vec<UTXO>, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+    pub fn transfer_utxo(&mut self, input_utxos: Vec<(Vec<u8>, u32)>, output_utxos: Vec<UTXO>, accounts: &[AccountInfo]) -> Result<(), ProtocolError> {
         let mut input_value = 0;
         for (txid, vout) in input_utxos.iter() {
-            let utxo = self.utxos.get(&(*txid, *vout)).expect("UTXO not found");
+            let utxo = self.utxos.get(&(*txid, *vout)).ok_or(ProtocolError::UtxoNotFound)?;
             input_value += utxo.value;
             self.utxos.remove(&(*txid, *vout));
         }
@@ -142,7 +164,7 @@ impl BabelCore {
         }
 
         if input_value != output_value {
-            return Err(ProgramError::Custom(502)); // Custom error for value mismatch
+            return Err(ProtocolError::UtxoValueMismatch);
         }
 
         // Validate UTXO ownership
@@ -153,7 +175,7 @@ impl BabelCore {
         // Create state transition transaction
         let mut tx = get_state_transition_tx(accounts);
         for (txid, vout) in input_utxos.iter() {
-            let utxo = self.utxos.get(&(*txid, *vout)).expect("UTXO not found");
+            let utxo = self.utxos.get(&(*txid, *vout)).ok_or(ProtocolError::UtxoNotFound)?;
             tx.input.push(utxo.clone());
         }
 
@@ -172,13 +194,31 @@ impl BabelCore {
         println!("Adjusting trove for user: {}, adjustment: {}", user, adjustment);
     }
 
-    pub fn trigger_emergency(&mut self, caller: Pubkey) {
-        if caller == self.guardian {
-            self.paused = true;
-            println!("Emergency triggered, system paused by {}", caller);
-        } else {
-            panic!("Unauthorized attempt to trigger emergency by {}", caller);
+    pub fn trigger_emergency(&mut self, caller: Pubkey) -> Result<(), ProtocolError> {
+        if caller != self.guardian {
+            return Err(ProtocolError::Unauthorized);
+        }
+        self.paused = true;
+        println!("Emergency triggered, system paused by {}", caller);
+        Ok(())
+    }
+
+    // Function for the guardian to clear a token's tripped price-deviation breaker
+    pub fn clear_price_breaker(&self, caller: Pubkey, price_feed: &mut PriceFeed, token: &str) -> Result<(), ProtocolError> {
+        if caller != self.guardian {
+            return Err(ProtocolError::Unauthorized);
+        }
+        price_feed.clear_circuit_breaker(token)?;
+        ProtocolEvent::Admin {
+            caller: caller.to_string(),
+            action: AdminAction::PriceBreakerCleared { token: token.to_string() },
         }
+        .emit();
+        Ok(())
+    }
+
+    fn emit_admin_action(&self, action: AdminAction) {
+        ProtocolEvent::Admin { caller: get_caller().to_string(), action }.emit();
     }
 
     pub fn admin_vote(&mut self, proposal_id: u32, vote: bool) {
@@ -15,14 +15,19 @@ use arch_program::{
     utxo::UtxoMeta,
 };
 use bitcoin::{self, Transaction}; // Importing bitcoin crate and Transaction struct
+use crate::core::trove_manager::{Status, TroveManager};
+use crate::core::Debt_token::DebtToken;
+use crate::core::price_feed::PriceFeed;
+use crate::dependencies::babel_base::BabelBase;
+use crate::dependencies::babel_math::BabelMath;
+use crate::dependencies::protocol_error::ProtocolError;
   
 
+// Trove managers and the debt token are not stored here: each operation borrows the trove manager
+// it targets from the factory, so collaterals deployed later are reachable without reconfiguration
 #[derive(BorshSerialize, BorshDeserialize)]
 pub struct BorrowerOperationsState {
     min_net_debt: u64,
-    trove_manager: TroveManager,
-    debt_token: DebtToken,
-    babel_base: BabelBase,
     babel_ownable: BabelOwnable,
     delegated_ops: DelegatedOps,
     trove_managers_data: HashMap<String, TroveManagerData>,
@@ -32,18 +37,12 @@ pub struct BorrowerOperationsState {
 impl BorrowerOperationsState {
     pub fn new(
         min_net_debt: u64,
-        trove_manager: TroveManager,
-        debt_token: DebtToken,
-        babel_base: BabelBase,
         babel_ownable: BabelOwnable,
         delegated_ops: DelegatedOps,
         critical_collateral_ratio: u128, // Added critical_collateral_ratio parameter
     ) -> Self {
         Self {
             min_net_debt,
-            trove_manager,
-            debt_token,
-            babel_base,
             babel_ownable,
             delegated_ops,
             trove_managers_data: HashMap::new(),
@@ -51,17 +50,40 @@ impl BorrowerOperationsState {
         }
     }
 
-    pub fn adjust_trove(&mut self, user_id: AccountId, coll_change: i64, debt_change: i64) {
-        self.ensure_owner_or_delegate(&user_id.to_string()); // Enhanced check
-        if self.check_recovery_mode() {
-            assert!(coll_change >= 0, "No collateral withdrawal allowed in recovery mode");
+    pub fn adjust_trove(
+        &mut self,
+        trove_manager_id: &str,
+        trove_manager: &mut TroveManager,
+        debt_token: &mut DebtToken,
+        user_id: AccountId,
+        coll_change: i64,
+        debt_change: i64,
+        price_feed: &mut PriceFeed,
+    ) -> Result<(), ProtocolError> {
+        self.ensure_owner_or_delegate(&user_id.to_string())?; // Enhanced check
+        self.trove_manager_data(trove_manager_id)?;
+        if coll_change < 0 || debt_change > 0 {
+            self.require_price_breaker_not_tripped(trove_manager_id, price_feed)?;
+        }
+        if self.check_recovery_mode() && coll_change < 0 {
+            return Err(ProtocolError::CollateralWithdrawalInRecoveryMode);
+        }
+        if debt_change > 0 {
+            self.require_debt_capacity(trove_manager, debt_token, debt_change as u128)?;
+        }
+        if coll_change < 0 || debt_change > 0 {
+            let (debt, coll, _, _) = trove_manager.get_entire_debt_and_coll(user_id);
+            let new_coll = coll.saturating_sub(coll_change.min(0).unsigned_abs() as u128);
+            let new_debt = debt + debt_change.max(0) as u128;
+            let new_icr = self.calculate_icr(trove_manager, price_feed, new_coll, new_debt)?;
+            self.require_icr_is_above_mcr(new_icr, trove_manager.mcr())?;
         }
 
-        self.trove_manager.adjust_trove(user_id, coll_change, debt_change);
+        trove_manager.adjust_trove(user_id, coll_change, debt_change, None, None)?;
         if debt_change > 0 {
-            self.debt_token.issue(debt_change as u64);
+            debt_token.issue(debt_change as u64);
         } else {
-            self.debt_token.burn((-debt_change) as u64);
+            debt_token.burn((-debt_change) as u64);
         }
 
         // Example of using Arch SDK functionality
@@ -81,10 +103,7 @@ impl BorrowerOperationsState {
         set_transaction_to_sign(transaction_to_sign);
 
         // Validate UTXO ownership
-        validate_utxo_ownership(&account_info, &self.trove_manager.get_collateral_token());
-
-        // Set return data
-        set_return_data(&self.serialize());
+        validate_utxo_ownership(&account_info, &trove_manager.get_collateral_token());
 
 
         // Add UTXO management
@@ -92,21 +111,38 @@ impl BorrowerOperationsState {
         utxo_set.add_utxo(&tx, 0, coll_change as u64, account_info.key.to_string());
 
         self.log_event("Trove adjusted"); // Log event
+        Ok(())
     }
 
-    pub fn open_trove(&mut self, trove_manager: String, account: String, collateral_amount: u128, debt_amount: u128) {
-        let tm_data = self.trove_managers_data.get(&trove_manager).expect("Invalid Trove Manager");
+    pub fn open_trove(
+        &mut self,
+        trove_manager_id: &str,
+        trove_manager: &mut TroveManager,
+        debt_token: &mut DebtToken,
+        account: String,
+        collateral_amount: u128,
+        debt_amount: u128,
+        price_feed: &mut PriceFeed,
+    ) -> Result<(), ProtocolError> {
+        let tm_data = self.trove_manager_data(trove_manager_id)?;
+        self.require_price_breaker_not_tripped(trove_manager_id, price_feed)?;
+        // The trove also carries the liquidation reserve minted to the gas pool
+        let composite_debt = Self::babel_base(debt_token).get_composite_debt(debt_amount);
+        self.require_debt_capacity(trove_manager, debt_token, composite_debt)?;
+        let icr = self.calculate_icr(trove_manager, price_feed, collateral_amount, composite_debt)?;
+        self.require_icr_is_above_mcr(icr, trove_manager.mcr())?;
 
         println!(
             "Opening trove for account: {} with collateral: {} and debt: {}",
-            account, collateral_amount, debt_amount
+            account, collateral_amount, composite_debt
         );
 
-        // Update internal state
-        self.debt_token.issue(debt_amount); // Assuming a method to handle debt token issuance
-
         // Example of using Arch SDK functionality
         let account_info = AccountInfo::new();
+
+        // Update internal state
+        trove_manager.open_trove(account_info.key, collateral_amount, composite_debt, None, None)?;
+        debt_token.mint_with_gas_compensation(account_info.key, debt_amount, &account_info)?;
         let tx = get_state_transition_tx();
         let input_to_sign = InputToSign::new();
         let instruction = Instruction::new();
@@ -125,39 +161,110 @@ impl BorrowerOperationsState {
         // Validate UTXO ownership
         validate_utxo_ownership(&account_info, &tm_data.collateral_token);
 
-      set_return_data(&self.serialize());
-
         // Add UTXO management
         let utxo_set = UtxoSet::new();
         utxo_set.add_utxo(&tx, 0, collateral_amount as u64, account_info.key.to_string());
+        Ok(())
+    }
+
+    // Closes the caller's trove: the owner repays the net debt and the reserve is burned from the gas pool
+    pub fn close_trove(
+        &mut self,
+        trove_manager_id: &str,
+        trove_manager: &mut TroveManager,
+        debt_token: &mut DebtToken,
+        borrower_info: &AccountInfo,
+    ) -> Result<(u128, u128), ProtocolError> {
+        self.ensure_owner_or_delegate(&borrower_info.key.to_string())?;
+        self.trove_manager_data(trove_manager_id)?;
+        if self.check_recovery_mode() {
+            return Err(ProtocolError::NotPermittedInRecoveryMode);
+        }
 
+        let (debt, coll) = trove_manager.close_trove(borrower_info.key, Status::ClosedByOwner)?;
+        let net_debt = Self::babel_base(debt_token).get_net_debt(debt);
+        debt_token.burn_with_gas_compensation(borrower_info.key, net_debt, borrower_info)?;
+
+        self.log_event("Trove closed");
+        Ok((debt, coll))
     }
 
-    pub fn issue_debt(&mut self, amount: u128, max_fee_percentage: u128) {
+    pub fn issue_debt(
+        &mut self,
+        trove_manager: &mut TroveManager,
+        debt_token: &mut DebtToken,
+        amount: u128,
+        max_fee_percentage: u128,
+    ) -> Result<(), ProtocolError> {
         println!("Issuing debt: {}", amount);
-        let fee = self.decay_base_rate_and_get_borrowing_fee(amount);
-        self.ensure_user_accepts_fee(fee, amount, max_fee_percentage);
-        self.debt_token.issue(amount + fee); // Issue debt amount including the fee
+        let fee = self.decay_base_rate_and_get_borrowing_fee(trove_manager, amount);
+        self.ensure_user_accepts_fee(fee, amount, max_fee_percentage)?;
+        debt_token.issue(amount + fee); // Issue debt amount including the fee
+        Ok(())
     }
 
-    pub fn burn_debt(&mut self, amount: u128) {
+    pub fn burn_debt(&mut self, debt_token: &mut DebtToken, amount: u128) {
         println!("Burning debt: {}", amount);
-        self.debt_token.burn(amount);
+        debt_token.burn(amount);
+    }
+
+    // ICR priced with the collateral's configured price source (spot, TWAP or the lower of both)
+    pub fn calculate_icr(&self, trove_manager: &TroveManager, price_feed: &mut PriceFeed, collateral: u128, debt: u128) -> Result<u128, ProtocolError> {
+        let price = trove_manager.fetch_price(price_feed).ok_or(ProtocolError::PriceUnavailable)?;
+        Ok(BabelMath::compute_cr(collateral, debt, price))
     }
 
-    pub fn calculate_icr(&self, collateral: u128, debt: u128, price: u128) -> u128 {
-        if debt == 0 {
-            return u128::MAX; // To handle division by zero
+    // Function to check new debt against the collateral's max system debt and the global mint cap
+    fn require_debt_capacity(&self, trove_manager: &TroveManager, debt_token: &DebtToken, debt_increase: u128) -> Result<(), ProtocolError> {
+        trove_manager.require_debt_capacity(debt_increase)?;
+        let headroom = debt_token.get_global_debt_headroom();
+        if debt_increase > headroom {
+            return Err(ProtocolError::GlobalDebtCapExceeded { requested: debt_increase, headroom });
         }
-        collateral * price / debt
+        Ok(())
     }
 
-    pub fn ensure_owner_or_delegate(&self, caller: &str) {
-        assert!(
-            self.babel_ownable.only_owner(caller) ||
-            self.delegated_ops.is_approved_delegate(self.babel_ownable.owner(), caller),
-            "Unauthorized: caller is neither owner nor delegate"
-        );
+    // Function to block debt increases and collateral withdrawals while the collateral's price breaker is tripped
+    fn require_price_breaker_not_tripped(&self, trove_manager_id: &str, price_feed: &PriceFeed) -> Result<(), ProtocolError> {
+        let tm_data = self.trove_manager_data(trove_manager_id)?;
+        if price_feed.is_breaker_tripped(&tm_data.collateral_token) {
+            return Err(ProtocolError::CircuitBreakerTripped);
+        }
+        Ok(())
+    }
+
+    // Function to register the collateral of a newly deployed trove manager, called by the factory
+    pub fn configure_collateral(&mut self, trove_manager: String, collateral_token: String) -> Result<(), ProtocolError> {
+        if self.trove_managers_data.contains_key(&trove_manager) {
+            return Err(ProtocolError::TroveManagerAlreadyConfigured);
+        }
+        let index = self.trove_managers_data.len() as u16;
+        self.trove_managers_data.insert(trove_manager.clone(), TroveManagerData { collateral_token: collateral_token.clone(), index });
+        msg!("CollateralConfigured: trove_manager = {}, collateral = {}", trove_manager, collateral_token);
+        Ok(())
+    }
+
+    pub fn is_trove_manager_configured(&self, trove_manager: &str) -> bool {
+        self.trove_managers_data.contains_key(trove_manager)
+    }
+
+    // Gas compensation helpers for the reserve configured on the debt token, the single source of that setting
+    fn babel_base(debt_token: &DebtToken) -> BabelBase {
+        BabelBase::new(debt_token.debt_gas_compensation())
+    }
+
+    // Function to look up a trove manager registered by the factory, rejecting unknown ones
+    fn trove_manager_data(&self, trove_manager: &str) -> Result<&TroveManagerData, ProtocolError> {
+        self.trove_managers_data.get(trove_manager).ok_or(ProtocolError::InvalidTroveManager)
+    }
+
+    pub fn ensure_owner_or_delegate(&self, caller: &str) -> Result<(), ProtocolError> {
+        if !self.babel_ownable.only_owner(caller)
+            && !self.delegated_ops.is_approved_delegate(self.babel_ownable.owner(), caller)
+        {
+            return Err(ProtocolError::Unauthorized);
+        }
+        Ok(())
     }
 
     pub fn serialize(&self) -> Vec<u8> {
@@ -168,38 +275,51 @@ impl BorrowerOperationsState {
         Self::try_from_slice(data).expect("Deserialization failed")
     }
 
-    // New method to decay the base rate and calculate the borrowing fee
-    pub fn decay_base_rate_and_get_borrowing_fee(&mut self, debt_amount: u128) -> u128 {
-        // Example decay logic (simplified)
-        self.base_rate *= 0.99; // Decay the base rate by 1%
-        let fee = (self.base_rate as u128) * debt_amount / 10000; // Calculate fee based on the base rate
-        fee
+    // Decays the TroveManager's base rate by the minutes elapsed since the last fee operation
+    // and returns the borrowing fee for the debt amount at the decayed rate
+    pub fn decay_base_rate_and_get_borrowing_fee(&mut self, trove_manager: &mut TroveManager, debt_amount: u128) -> u128 {
+        trove_manager.decay_base_rate_and_get_borrowing_fee(debt_amount)
     }
 
-    // New method to ensure the user accepts the fee
-    pub fn ensure_user_accepts_fee(&self, fee: u128, debt_amount: u128, max_fee_percentage: u128) {
-        let max_fee = debt_amount * max_fee_percentage / 100;
-        assert!(fee <= max_fee, "Fee exceeds the maximum fee percentage allowed");
+    // Ensures the fee, as a fraction of the debt amount (1e18 precision), does not exceed the user's maximum
+    pub fn ensure_user_accepts_fee(&self, fee: u128, debt_amount: u128, max_fee_percentage: u128) -> Result<(), ProtocolError> {
+        let fee_percentage = BabelMath::mul_div(fee, BabelBase::DECIMAL_PRECISION, debt_amount);
+        if fee_percentage > max_fee_percentage {
+            return Err(ProtocolError::FeeExceedsMax);
+        }
+        Ok(())
     }
 
     // New method to check if the new ICR is above the Minimum Collateral Ratio (MCR)
-    pub fn require_icr_is_above_mcr(&self, new_icr: u128, mcr: u128) {
-        assert!(new_icr >= mcr, "ICR must be above the minimum collateral ratio");
+    pub fn require_icr_is_above_mcr(&self, new_icr: u128, mcr: u128) -> Result<(), ProtocolError> {
+        if new_icr < mcr {
+            return Err(ProtocolError::IcrBelowMcr);
+        }
+        Ok(())
     }
 
     // New method to check if the new ICR is above the Critical Collateral Ratio (CCR)
-    pub fn require_icr_is_above_ccr(&self, new_icr: u128) {
-        assert!(new_icr >= self.critical_collateral_ratio, "ICR must be above the critical collateral ratio");
+    pub fn require_icr_is_above_ccr(&self, new_icr: u128) -> Result<(), ProtocolError> {
+        if new_icr < self.critical_collateral_ratio {
+            return Err(ProtocolError::IcrBelowCcr);
+        }
+        Ok(())
     }
 
     // New method to check if the new ICR is above the old ICR in Recovery Mode
-    pub fn require_new_icr_is_above_old_icr(&self, new_icr: u128, old_icr: u128) {
-        assert!(new_icr >= old_icr, "New ICR must be above the old ICR in Recovery Mode");
+    pub fn require_new_icr_is_above_old_icr(&self, new_icr: u128, old_icr: u128) -> Result<(), ProtocolError> {
+        if new_icr < old_icr {
+            return Err(ProtocolError::IcrDecreasedInRecoveryMode);
+        }
+        Ok(())
     }
 
     // New method to check if the new Total Collateral Ratio (TCR) is above the CCR
-    pub fn require_new_tcr_is_above_ccr(&self, new_tcr: u128) {
-        assert!(new_tcr >= self.critical_collateral_ratio, "New TCR must be above the critical collateral ratio");
+    pub fn require_new_tcr_is_above_ccr(&self, new_tcr: u128) -> Result<(), ProtocolError> {
+        if new_tcr < self.critical_collateral_ratio {
+            return Err(ProtocolError::TcrBelowCcr);
+        }
+        Ok(())
     }
 
     // New method to validate adjustments based on the current mode (Normal or Recovery)
@@ -214,18 +334,21 @@ impl BorrowerOperationsState {
         old_icr: u128,
         mcr: u128,
         new_tcr: u128
-    ) {
+    ) -> Result<(), ProtocolError> {
         if is_recovery_mode {
-            assert!(coll_withdrawal == 0, "Collateral withdrawal not permitted in Recovery Mode");
+            if coll_withdrawal != 0 {
+                return Err(ProtocolError::CollateralWithdrawalInRecoveryMode);
+            }
             if is_debt_increase {
-                self.require_icr_is_above_ccr(new_icr);
-                self.require_new_icr_is_above_old_icr(new_icr, old_icr);
+                self.require_icr_is_above_ccr(new_icr)?;
+                self.require_new_icr_is_above_old_icr(new_icr, old_icr)?;
             }
         } else {
             // Normal mode validations
-            self.require_icr_is_above_mcr(new_icr, mcr);
-            self.require_new_tcr_is_above_ccr(new_tcr);
+            self.require_icr_is_above_mcr(new_icr, mcr)?;
+            self.require_new_tcr_is_above_ccr(new_tcr)?;
         }
+        Ok(())
     }
 
     // Fetch balances from all TroveManagers
@@ -249,17 +372,17 @@ impl BorrowerOperationsState {
         }
     }
 
-    // Calculate Total Collateral Ratio (TCR)
+    // Calculate Total Collateral Ratio (TCR). Priced collateral is in debt units with 18 decimals.
     pub fn get_tcr_data(&self, balances: &SystemBalances) -> (u128, u128, u128) {
         let mut total_priced_collateral = 0;
         let mut total_debt = 0;
 
         for i in 0..balances.collaterals.len() {
-            total_priced_collateral += balances.collaterals[i] * balances.prices[i];
+            total_priced_collateral += BabelMath::mul_div(balances.collaterals[i], balances.prices[i], BabelBase::DECIMAL_PRECISION);
             total_debt += balances.debts[i];
         }
 
-        let tcr = if total_debt == 0 { u128::MAX } else { total_priced_collateral / total_debt };
+        let tcr = if total_debt == 0 { u128::MAX } else { BabelMath::mul_div(total_priced_collateral, BabelBase::DECIMAL_PRECISION, total_debt) };
 
         (tcr, total_priced_collateral, total_debt)
     }
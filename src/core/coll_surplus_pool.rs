@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+use borsh::{BorshDeserialize, BorshSerialize};
+use arch_program::{
+    msg,
+    pubkey::Pubkey,
+};
+use crate::dependencies::protocol_error::ProtocolError;
+
+type Balance = u128;
+
+// Collateral left over from Recovery Mode liquidations and full redemptions.
+// Each TroveManager holds one pool for its collateral; owners withdraw their surplus through
+// TroveManager::claim_collateral.
+#[derive(Debug, Clone, Default, BorshSerialize, BorshDeserialize)]
+pub struct CollSurplusPool {
+    collateral: String,
+    balances: HashMap<Pubkey, Balance>,
+    total_collateral: Balance,
+}
+
+impl CollSurplusPool {
+    pub fn new(collateral: String) -> Self {
+        Self {
+            collateral,
+            balances: HashMap::new(),
+            total_collateral: 0,
+        }
+    }
+
+    pub fn set_collateral(&mut self, collateral: String) {
+        self.collateral = collateral;
+    }
+
+    pub fn collateral(&self) -> &str {
+        &self.collateral
+    }
+
+    // Function to credit surplus collateral to an owner
+    pub fn account_surplus(&mut self, owner: Pubkey, amount: Balance) {
+        let surplus = self.balances.entry(owner).or_insert(0);
+        *surplus += amount;
+        self.total_collateral += amount;
+        msg!("CollBalanceUpdated: owner = {:?}, collateral = {}, surplus = {}", owner, self.collateral, *surplus);
+    }
+
+    // Function to get the surplus an owner can claim
+    pub fn surplus_of(&self, owner: Pubkey) -> Balance {
+        self.balances.get(&owner).cloned().unwrap_or(0)
+    }
+
+    pub fn total_collateral(&self) -> Balance {
+        self.total_collateral
+    }
+
+    // Function to remove an owner's whole surplus from the pool, returning the amount to send
+    pub fn take_surplus(&mut self, owner: Pubkey) -> Result<Balance, ProtocolError> {
+        let amount = self.balances.remove(&owner).unwrap_or(0);
+        if amount == 0 {
+            return Err(ProtocolError::NothingToClaim);
+        }
+        self.total_collateral -= amount;
+        msg!("CollBalanceUpdated: owner = {:?}, collateral = {}, surplus = 0", owner, self.collateral);
+        Ok(amount)
+    }
+}
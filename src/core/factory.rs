@@ -8,7 +8,6 @@ use arch_program::{
     instruction::Instruction,
     msg,
     program::{get_account_script_pubkey, get_bitcoin_tx, get_network_xonly_pubkey, invoke, next_account_info, set_return_data, set_transaction_to_sign, validate_utxo_ownership},
-    program_error::ProgramError,
     pubkey::Pubkey,
     system_instruction::SystemInstruction,
     transaction_to_sign::TransactionToSign,
@@ -17,6 +16,15 @@ use arch_program::{
 };
 use bitcoin::{self, Transaction};
 
+use crate::core::trove_manager::TroveManager;
+use crate::core::stability_pool::StabilityPool;
+use crate::core::liquidation_manager::LiquidationManager;
+use crate::core::Debt_token::DebtToken;
+use crate::core::borrower_operations::BorrowerOperationsState;
+use crate::core::price_feed::PriceSource;
+use crate::dependencies::protocol_error::ProtocolError;
+
+#[derive(BorshSerialize, BorshDeserialize)]
 struct BabelOwnable {
     owner: Pubkey,
 }
@@ -31,152 +39,255 @@ impl BabelOwnable {
     }
 }
 
+// Per-collateral parameters, all values use 1e18 precision unless noted
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct DeploymentParams {
+    pub minute_decay_factor: u128, // half life of 12 hours
+    pub redemption_fee_floor: u128, // 0.5%
+    pub max_redemption_fee: u128, // 100%
+    pub borrowing_fee_floor: u128, // 0.5%
+    pub max_borrowing_fee: u128, // 5%
+    pub interest_rate_in_bps: u128, // 1%
+    pub max_debt: u128,
+    pub mcr: u128, // 120%
+    pub price_source: PriceSource, // spot, TWAP or min(spot, TWAP) for ICR and liquidation checks
+    pub twap_window_secs: u32,
+}
+
+// The live core components a deployment or sunset is applied to. The factory borrows them per
+// call instead of keeping its own copies, so every registration lands in the state the rest of
+// the system reads.
+pub struct CoreComponents<'a> {
+    pub stability_pool: &'a mut StabilityPool,
+    pub liquidation_manager: &'a mut LiquidationManager,
+    pub debt_token: &'a mut DebtToken,
+    pub borrower_operations: &'a mut BorrowerOperationsState,
+}
+
 #[derive(BorshSerialize, BorshDeserialize)]
 pub struct Factory {
     babel_ownable: BabelOwnable,
-    trove_manager_impl: TroveManager,
-    sorted_troves_impl: SortedTroves,
+    // Program address of the factory, checked by components for factory-only calls
+    address: Pubkey,
     trove_managers: HashMap<String, TroveManager>,
 }
 
 impl Factory {
-    pub fn new(owner: Pubkey) -> Self {
+    pub fn new(owner: Pubkey, address: Pubkey) -> Self {
         Self {
             babel_ownable: BabelOwnable::new(owner),
-            trove_manager_impl: TroveManager::new("default_address".to_string()),
-            sorted_troves_impl: SortedTroves::new(),
+            address,
             trove_managers: HashMap::new(),
         }
     }
 
+    // Deploys a trove manager for `collateral` and registers it with every core component.
+    // The components' own permission and duplicate checks are repeated up front, so every check
+    // that can fail runs before the first component is changed and a rejected deployment leaves
+    // no partial registration behind.
     pub fn deploy_new_instance(
         &mut self,
         caller: &Pubkey,
+        trove_manager: Pubkey,
         collateral: String,
+        collateral_id: u64,
         price_feed: String,
         params: DeploymentParams,
+        components: CoreComponents,
         accounts: &[AccountInfo],
-    ) -> Result<(), ProgramError> {
+    ) -> Result<(), ProtocolError> {
         if !self.babel_ownable.is_owner(caller) {
-            return Err(ProgramError::Unauthorized);
+            return Err(ProtocolError::Unauthorized);
         }
 
-        let trove_manager_impl = self.trove_manager_impl.clone();
-        let sorted_troves_impl = self.sorted_troves_impl.clone();
-
-        // Initialize the cloned instances
-        trove_manager_impl.set_addresses(&price_feed, &sorted_troves_impl, &collateral);
-        sorted_troves_impl.set_addresses(&trove_manager_impl);
-
-        // Verify that the oracle is correctly working
-        trove_manager_impl.fetch_price();
-
-        // Enable collateral and configure the new trove manager
-        self.stability_pool.enable_collateral(&collateral);
-        self.liquidation_manager.enable_trove_manager(&trove_manager_impl);
-        self.debt_token.enable_trove_manager(&trove_manager_impl);
-        self.borrower_operations.configure_collateral(&trove_manager_impl, &collateral);
-
-        // Set parameters on the new trove manager
-        trove_manager_impl.set_parameters(params);
-
-        let id = format!("tm_{}", self.trove_managers.len() + 1);
-        self.trove_managers.insert(id, trove_manager_impl);
+        let id = trove_manager.to_string();
+        if self.trove_managers.contains_key(&id) || components.borrower_operations.is_trove_manager_configured(&id) {
+            return Err(ProtocolError::TroveManagerAlreadyConfigured);
+        }
+        if components.stability_pool.is_collateral_enabled(collateral_id) {
+            return Err(ProtocolError::DuplicateCollateral);
+        }
+        // The factory's own account has to sign the deployment for the components to accept it
+        let factory_account = accounts.iter().find(|account| account.key == self.address).ok_or(ProtocolError::Unauthorized)?;
+        components.liquidation_manager.ensure_factory_signer(factory_account)?;
+        components.debt_token.ensure_factory()?;
 
         // Use Arch SDK to validate UTXO ownership
-
         let utxo_meta = UtxoMeta::new();
         validate_utxo_ownership(&utxo_meta, accounts)?;
 
-        // Create a new Bitcoin transaction
-        let tx = Transaction {
-            version: 1,
-            lock_time: 0,
-            input: vec![],  // Add inputs as needed
-            output: vec![], // Add outputs as needed
-        };
-
-        // Use Arch SDK to set transaction to sign
-        let tx_bytes = tx.serialize();
-        let inputs_to_sign = vec![InputToSign {
-            index: 0,
-            signer: accounts[0].key.clone(),
-        }];
-        let tx_to_sign = TransactionToSign::new(tx_bytes, inputs_to_sign);
-        set_transaction_to_sign(&tx_to_sign)?;
-
-        // Use Arch SDK to get state transition transaction
-        let mut state_tx = get_state_transition_tx(accounts);
-        state_tx.input.push(tx.input[0].clone());
-
-        msg!("State transition transaction: {:?}", state_tx);
+        // Configure the new trove manager, rejecting out of range parameters
+        let mut trove_manager_impl = TroveManager::new(self.babel_ownable.owner);
+        trove_manager_impl.set_addresses(price_feed.clone(), collateral.clone());
+        trove_manager_impl.set_parameters(caller, &params)?;
+
+        // Enable collateral and the new trove manager across the system
+        components.debt_token.enable_trove_manager(trove_manager)?;
+        components.liquidation_manager.enable_trove_manager(factory_account, id.clone(), collateral_id)?;
+        components.stability_pool.enable_collateral(collateral_id)?;
+        components.borrower_operations.configure_collateral(id.clone(), collateral.clone())?;
+
+        self.trove_managers.insert(id.clone(), trove_manager_impl);
+
+        msg!(
+            "NewDeployment: collateral = {}, price_feed = {}, trove_manager = {}",
+            collateral,
+            price_feed,
+            id
+        );
+        Ok(())
+    }
 
-        let utxo_meta = UtxoMeta::new(); // Assuming UtxoMeta has a new method
-        validate_utxo_ownership(&utxo_meta, accounts)?; // Validate UTXO ownership
+    // Starts delisting a collateral across the system. The trove manager stops taking new debt
+    // and the stability pool keeps offsetting its liquidations until the grace period ends.
+    pub fn start_collateral_sunset(
+        &mut self,
+        caller: &Pubkey,
+        trove_manager: Pubkey,
+        collateral_id: u64,
+        components: CoreComponents,
+    ) -> Result<(), ProtocolError> {
+        if !self.babel_ownable.is_owner(caller) {
+            return Err(ProtocolError::Unauthorized);
+        }
 
-        // Use Arch SDK to set transaction to sign
-        let tx_to_sign = TransactionToSign::new(); // Assuming TransactionToSign has a new method
-        set_transaction_to_sign(&tx_to_sign)?; // Set transaction to 
+        let id = trove_manager.to_string();
+        let trove_manager_impl = self.trove_managers.get_mut(&id).ok_or(ProtocolError::InvalidTroveManager)?;
+        if trove_manager_impl.is_sunsetting() || !components.stability_pool.accepts_offsets(collateral_id) {
+            return Err(ProtocolError::AlreadySunsetting);
+        }
 
+        // Only new borrowing is blocked, through the trove manager's max system debt. Interest on
+        // existing troves is still collected, as collect_interests is only bound by the global cap.
+        trove_manager_impl.start_sunset(caller)?;
+        components.stability_pool.start_collateral_sunset(collateral_id)?;
         Ok(())
     }
 
-    pub fn serialize(&self) -> Vec<u8> {
-        self.try_to_vec().expect("Serialization failed")
+    // Sets the cap on new borrowing across all collaterals. The debt token starts with a zero cap,
+    // so governance seeds it here before the first trove can be opened.
+    pub fn set_global_debt_cap(&self, caller: &Pubkey, global_debt_cap: u128, debt_token: &mut DebtToken) -> Result<(), ProtocolError> {
+        if !self.babel_ownable.is_owner(caller) {
+            return Err(ProtocolError::Unauthorized);
+        }
+        debt_token.set_global_debt_cap(global_debt_cap)
     }
 
-    pub fn deserialize(data: &[u8]) -> Self {
-        Self::try_from_slice(data).expect("Deserialization failed")
+    // Removes the stability pool index of a sunset collateral after its grace period
+    pub fn remove_sunset_collateral(
+        &mut self,
+        caller: &Pubkey,
+        collateral_id: u64,
+        stability_pool: &mut StabilityPool,
+    ) -> Result<(), ProtocolError> {
+        if !self.babel_ownable.is_owner(caller) {
+            return Err(ProtocolError::Unauthorized);
+        }
+        stability_pool.remove_sunset_collateral(collateral_id)
     }
-}
-
-#[derive(Clone, BorshSerialize, BorshDeserialize)]
-struct TroveManager {
-    troves: HashMap<String, Trove>,
-    address: String,
-}
 
-impl TroveManager {
-    pub fn new(address: String) -> Self {
-        Self {
-            troves: HashMap::new(),
-            address,
+    // Claimable collateral surplus of an owner, per collateral token
+    pub fn surplus_of(&self, owner: Pubkey) -> HashMap<String, u128> {
+        let mut surplus = HashMap::new();
+        for trove_manager in self.trove_managers.values() {
+            let amount = trove_manager.surplus_of(owner);
+            if amount > 0 {
+                *surplus.entry(trove_manager.get_collateral_token()).or_insert(0) += amount;
+            }
         }
+        surplus
     }
 
-    pub fn set_addresses(&mut self, price_feed: &str, sorted_troves: &SortedTroves, collateral: &str) {
-        self.price_feed = price_feed.to_string();
-        self.sorted_troves = sorted_troves.clone();
-        self.collateral = collateral.to_string();
+    pub fn trove_manager(&self, trove_manager: &str) -> Option<&TroveManager> {
+        self.trove_managers.get(trove_manager)
     }
 
-    pub fn fetch_price(&self) {
-        // Implementation to fetch price from the oracle
+    // Trove managers are owned by the factory; borrower operations and liquidations borrow them from here
+    pub fn trove_manager_mut(&mut self, trove_manager: &str) -> Option<&mut TroveManager> {
+        self.trove_managers.get_mut(trove_manager)
     }
 
-    pub fn set_parameters(&mut self, params: DeploymentParams) {
-        self.params = params;
+    pub fn trove_manager_count(&self) -> usize {
+        self.trove_managers.len()
     }
-}
 
-#[derive(Clone, BorshSerialize, BorshDeserialize)]
-struct SortedTroves {
-    troves: HashMap<String, Trove>,
+    pub fn serialize(&self) -> Vec<u8> {
+        self.try_to_vec().expect("Serialization failed")
+    }
+
+    pub fn deserialize(data: &[u8]) -> Self {
+        Self::try_from_slice(data).expect("Deserialization failed")
+    }
 }
 
-impl SortedTroves {
-    pub fn new() -> Self {
-        Self {
-            troves: HashMap::new(),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dependencies::babel_base::BabelBase;
+    use crate::dependencies::delegated_ops::DelegatedOps;
+
+    fn deployment_params() -> DeploymentParams {
+        DeploymentParams {
+            minute_decay_factor: 999_037_758_833_783_000,
+            redemption_fee_floor: 5_000_000_000_000_000,
+            max_redemption_fee: 1_000_000_000_000_000_000,
+            borrowing_fee_floor: 5_000_000_000_000_000,
+            max_borrowing_fee: 50_000_000_000_000_000,
+            interest_rate_in_bps: 100,
+            max_debt: 1_000_000_000_000_000_000_000,
+            mcr: 1_200_000_000_000_000_000,
+            price_source: PriceSource::Spot,
+            twap_window_secs: 0,
         }
     }
 
-    pub fn set_addresses(&mut self, trove_manager: &TroveManager) {
-        // Link back to the TroveManager
+    fn debt_token() -> DebtToken {
+        DebtToken::new("Babel USD".to_string(), "bUSD".to_string(), Pubkey::new_unique(), Pubkey::new_unique(), 200)
     }
-}
 
-#[derive(BorshSerialize, BorshDeserialize)]
-struct DeploymentParams {
-    // Parameters as needed
-}
\ No newline at end of file
+    #[test]
+    fn test_late_rejection_leaves_no_partial_registration() {
+        let owner = Pubkey::new_unique();
+        let address = Pubkey::new_unique();
+        let mut factory = Factory::new(owner, address);
+        let mut factory_account = AccountInfo::new(&address);
+        factory_account.is_signer = true;
+        // The liquidation manager trusts another factory, so it would reject the deployment only after
+        // the debt token had already enabled the trove manager
+        let mut liquidation_manager = LiquidationManager::new(Pubkey::new_unique());
+        let mut debt_token = debt_token();
+        let mut stability_pool = StabilityPool::new(0, debt_token());
+        let mut borrower_operations = BorrowerOperationsState::new(
+            0,
+            crate::dependencies::babel_ownable::BabelOwnable::new(owner.to_string(), owner.to_string()),
+            DelegatedOps::new(),
+            BabelBase::CCR,
+        );
+        let trove_manager = Pubkey::new_unique();
+        let components = CoreComponents {
+            stability_pool: &mut stability_pool,
+            liquidation_manager: &mut liquidation_manager,
+            debt_token: &mut debt_token,
+            borrower_operations: &mut borrower_operations,
+        };
+
+        let result = factory.deploy_new_instance(
+            &owner,
+            trove_manager,
+            "BTC".to_string(),
+            1,
+            "price_feed".to_string(),
+            deployment_params(),
+            components,
+            &[factory_account],
+        );
+        assert_eq!(result, Err(ProtocolError::Unauthorized));
+
+        let id = trove_manager.to_string();
+        assert_eq!(factory.trove_manager_count(), 0);
+        assert!(!debt_token.is_trove_manager(&trove_manager));
+        assert!(!liquidation_manager.is_trove_manager_enabled(&id));
+        assert!(!stability_pool.is_collateral_enabled(1));
+        assert!(!borrower_operations.is_trove_manager_configured(&id));
+    }
+}
@@ -2,24 +2,78 @@
 
 use std::collections::HashMap;
 use borsh::{BorshDeserialize, BorshSerialize};
+use bitcoin::hashes::{sha256, Hash};
+use arch_program::pubkey::Pubkey;
+use crate::core::trove_manager::{RedemptionHints, TroveManager};
+use crate::dependencies::babel_math::BabelMath;
+
+const DECIMAL_PRECISION: u128 = 1_000_000_000_000_000_000; // 1e18
 
 #[derive(BorshSerialize, BorshDeserialize)]
 pub struct MultiCollateralHintHelpers {
     borrower_operations: Box<dyn IBorrowerOperations>,
 }
 
+// Troves are identified by their owner, and positions in SortedTroves by the owner's sorted id,
+// the same types TroveManager::open_trove and RedemptionHints use
 pub trait ITroveManager {
-    fn sorted_troves(&self) -> Vec<String>;
+    // Trove owners ordered from the largest to the smallest NICR
+    fn sorted_trove_owners(&self) -> Vec<Pubkey>;
+    fn sorted_trove_id(&self, trove: Pubkey) -> Option<u32>;
     fn mcr(&self) -> u128;
-    fn current_icr(&self, trove: String, price: u128) -> u128;
-    fn get_entire_debt_and_coll(&self, trove: String) -> (u128, u128);
+    fn current_icr(&self, trove: Pubkey, price: u128) -> u128;
+    fn get_entire_debt_and_coll(&self, trove: Pubkey) -> (u128, u128);
     fn get_trove_owners_count(&self) -> usize;
-    fn get_trove_from_trove_owners_array(&self, index: usize) -> String;
-    fn get_nominal_icr(&self, trove: String) -> u128;
+    fn get_trove_from_trove_owners_array(&self, index: usize) -> Option<Pubkey>;
+    fn get_nominal_icr(&self, trove: Pubkey) -> u128;
 }
 
 pub trait IBorrowerOperations {
     fn min_net_debt(&self) -> u128;
+    fn debt_gas_compensation(&self) -> u128;
+}
+
+impl ITroveManager for TroveManager {
+    fn sorted_trove_owners(&self) -> Vec<Pubkey> {
+        let mut owners = Vec::new();
+        let mut current = self.sorted_troves().get_first();
+        while let Some(id) = current {
+            if let Some(owner) = self.get_trove_owner_by_sorted_id(id) {
+                owners.push(owner);
+            }
+            current = self.sorted_troves().get_next(id);
+        }
+        owners
+    }
+
+    fn sorted_trove_id(&self, trove: Pubkey) -> Option<u32> {
+        self.get_sorted_trove_id(trove)
+    }
+
+    fn mcr(&self) -> u128 {
+        TroveManager::mcr(self)
+    }
+
+    fn current_icr(&self, trove: Pubkey, price: u128) -> u128 {
+        self.get_current_icr(trove, price)
+    }
+
+    fn get_entire_debt_and_coll(&self, trove: Pubkey) -> (u128, u128) {
+        let (debt, coll, _, _) = TroveManager::get_entire_debt_and_coll(self, trove);
+        (debt, coll)
+    }
+
+    fn get_trove_owners_count(&self) -> usize {
+        TroveManager::get_trove_owners_count(self)
+    }
+
+    fn get_trove_from_trove_owners_array(&self, index: usize) -> Option<Pubkey> {
+        TroveManager::get_trove_from_trove_owners_array(self, index)
+    }
+
+    fn get_nominal_icr(&self, trove: Pubkey) -> u128 {
+        TroveManager::get_nominal_icr(self, trove)
+    }
 }
 
 impl MultiCollateralHintHelpers {
@@ -27,44 +81,112 @@ impl MultiCollateralHintHelpers {
         Self { borrower_operations }
     }
 
+    // Must match the NICR computed by TroveManager::redeem_collateral, or the partial redemption is cancelled
     pub fn compute_nominal_cr(coll: u128, debt: u128) -> u128 {
-        coll / debt
+        BabelMath::compute_nominal_cr(coll, debt)
     }
 
     pub fn compute_cr(coll: u128, debt: u128, price: u128) -> u128 {
         (coll * price) / debt
     }
 
+    /* get_approx_hint() - return the sorted id of a Trove that is, on average, (length / num_trials) positions away in the
+    sortedTroves list from the correct insert position of the Trove to be inserted.
+
+    Note: The output address is worst-case O(n) positions away from the correct insert position, however, the function
+    is probabilistic. Input can be tuned to guarantee results to a high degree of confidence, e.g:
+
+    Submitting num_trials = k * sqrt(length), with k = 15 makes it very, very likely that the output address will
+    be <= sqrt(length) positions away from the correct insert position.
+
+    The seed is chained through SHA-256, so clients can reproduce the sampled troves off-chain.
+    */
+    pub fn get_approx_hint(
+        &self,
+        trove_manager: &dyn ITroveManager,
+        cr: u128,
+        num_trials: u128,
+        input_random_seed: u128,
+    ) -> (Option<u32>, u128, u128) {
+        let array_length = trove_manager.get_trove_owners_count();
+        let mut hint_trove = match trove_manager.sorted_trove_owners().last().cloned() {
+            Some(trove) if array_length > 0 => trove,
+            _ => return (None, 0, input_random_seed),
+        };
+        let mut diff = BabelMath::get_absolute_difference(cr, trove_manager.get_nominal_icr(hint_trove));
+        let mut latest_random_seed = input_random_seed;
+
+        let mut i = 1;
+        while i < num_trials {
+            latest_random_seed = Self::next_random_seed(latest_random_seed);
+
+            let array_index = (latest_random_seed % array_length as u128) as usize;
+            if let Some(current_trove) = trove_manager.get_trove_from_trove_owners_array(array_index) {
+                let current_nicr = trove_manager.get_nominal_icr(current_trove);
+
+                // check if abs(current - CR) > abs(closest - CR), and update closest if current is closer
+                let current_diff = BabelMath::get_absolute_difference(current_nicr, cr);
+                if current_diff < diff {
+                    diff = current_diff;
+                    hint_trove = current_trove;
+                }
+            }
+            i += 1;
+        }
+
+        (trove_manager.sorted_trove_id(hint_trove), diff, latest_random_seed)
+    }
+
+    // Deterministic PRNG step: the first 16 bytes of SHA-256 over the previous seed
+    fn next_random_seed(seed: u128) -> u128 {
+        let hash = sha256::Hash::hash(&seed.to_le_bytes());
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(&hash.as_byte_array()[..16]);
+        u128::from_le_bytes(bytes)
+    }
+
+    // Returns the hints for TroveManager::redeem_collateral and the debt amount that can actually be
+    // redeemed with them. The partial redemption's upper and lower hints are left unset: callers look
+    // them up with get_approx_hint and SortedTroves::find_insert_position for `partial_redemption_hint_nicr`.
     pub fn get_redemption_hints(
         &self,
         trove_manager: &dyn ITroveManager,
         debt_amount: u128,
         price: u128,
-        max_iterations: u128,
-    ) -> (String, u128, u128) {
-        let mut sorted_troves = trove_manager.sorted_troves();
+        mut max_iterations: u128,
+    ) -> (RedemptionHints, u128) {
+        let mut sorted_troves = trove_manager.sorted_trove_owners();
         let mut remaining_debt = debt_amount;
-        let mut current_trove = sorted_troves.last().cloned().unwrap_or_default();
         let mcr = trove_manager.mcr();
 
-        while trove_manager.current_icr(current_trove.clone(), price) < mcr {
-            current_trove = sorted_troves.pop().unwrap_or_default();
+        // Skip the troves below MCR at the bottom of the list
+        let mut current_trove = sorted_troves.pop();
+        while let Some(trove) = current_trove {
+            if trove_manager.current_icr(trove, price) >= mcr {
+                break;
+            }
+            current_trove = sorted_troves.pop();
         }
 
-        let first_redemption_hint = current_trove.clone();
+        let first_redemption_hint = current_trove;
         let min_net_debt = self.borrower_operations.min_net_debt();
+        let debt_gas_compensation = self.borrower_operations.debt_gas_compensation();
         let mut partial_redemption_hint_nicr = 0;
         let mut truncated_debt_amount = 0;
 
-        while !current_trove.is_empty() && remaining_debt > 0 && max_iterations > 0 {
-            let (debt, coll) = trove_manager.get_entire_debt_and_coll(current_trove.clone());
-            let net_debt = debt;
+        while let Some(trove) = current_trove {
+            if remaining_debt == 0 || max_iterations == 0 {
+                break;
+            }
+            let (debt, coll) = trove_manager.get_entire_debt_and_coll(trove);
+            // The liquidation reserve is never redeemed
+            let net_debt = debt.saturating_sub(debt_gas_compensation);
 
             if net_debt > remaining_debt {
                 if net_debt > min_net_debt {
                     let max_redeemable_debt = std::cmp::min(remaining_debt, net_debt - min_net_debt);
-                    let new_coll = coll - (max_redeemable_debt * price);
-                    let new_debt = net_debt - max_redeemable_debt;
+                    let new_coll = coll - (max_redeemable_debt * DECIMAL_PRECISION / price);
+                    let new_debt = debt - max_redeemable_debt;
                     partial_redemption_hint_nicr = Self::compute_nominal_cr(new_coll, new_debt);
 
                     remaining_debt -= max_redeemable_debt;
@@ -76,10 +198,138 @@ impl MultiCollateralHintHelpers {
                 truncated_debt_amount += net_debt;
             }
 
-            current_trove = sorted_troves.pop().unwrap_or_default();
+            current_trove = sorted_troves.pop();
             max_iterations -= 1;
         }
 
-        (first_redemption_hint, partial_redemption_hint_nicr, truncated_debt_amount)
+        let hints = RedemptionHints {
+            first_redemption_hint,
+            upper_partial_redemption_hint: None,
+            lower_partial_redemption_hint: None,
+            partial_redemption_hint_nicr,
+        };
+        (hints, truncated_debt_amount)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MCR: u128 = 1_100_000_000_000_000_000; // 110%
+
+    // Troves as (owner, NICR), ordered from the largest to the smallest NICR; sorted ids start at 1
+    struct MockTroveManager {
+        troves: Vec<(Pubkey, u128)>,
+    }
+
+    impl MockTroveManager {
+        fn new(nicrs: &[u128]) -> Self {
+            let troves = nicrs.iter().map(|nicr| (Pubkey::new_unique(), *nicr)).collect();
+            Self { troves }
+        }
+
+        fn owner(&self, index: usize) -> Pubkey {
+            self.troves[index].0
+        }
+    }
+
+    impl ITroveManager for MockTroveManager {
+        fn sorted_trove_owners(&self) -> Vec<Pubkey> {
+            self.troves.iter().map(|(owner, _)| *owner).collect()
+        }
+
+        fn sorted_trove_id(&self, trove: Pubkey) -> Option<u32> {
+            self.troves.iter().position(|(owner, _)| *owner == trove).map(|index| index as u32 + 1)
+        }
+
+        fn mcr(&self) -> u128 {
+            MCR
+        }
+
+        fn current_icr(&self, trove: Pubkey, _price: u128) -> u128 {
+            self.get_nominal_icr(trove)
+        }
+
+        fn get_entire_debt_and_coll(&self, _trove: Pubkey) -> (u128, u128) {
+            (0, 0)
+        }
+
+        fn get_trove_owners_count(&self) -> usize {
+            self.troves.len()
+        }
+
+        fn get_trove_from_trove_owners_array(&self, index: usize) -> Option<Pubkey> {
+            self.troves.get(index).map(|(owner, _)| *owner)
+        }
+
+        fn get_nominal_icr(&self, trove: Pubkey) -> u128 {
+            self.troves.iter().find(|(owner, _)| *owner == trove).map(|(_, nicr)| *nicr).unwrap_or(0)
+        }
+    }
+
+    struct MockBorrowerOperations;
+
+    impl IBorrowerOperations for MockBorrowerOperations {
+        fn min_net_debt(&self) -> u128 {
+            0
+        }
+
+        fn debt_gas_compensation(&self) -> u128 {
+            0
+        }
+    }
+
+    fn hint_helpers() -> MultiCollateralHintHelpers {
+        MultiCollateralHintHelpers::new(Box::new(MockBorrowerOperations))
+    }
+
+    #[test]
+    fn test_get_approx_hint_on_an_empty_list() {
+        let trove_manager = MockTroveManager::new(&[]);
+
+        assert_eq!(hint_helpers().get_approx_hint(&trove_manager, 500, 10, 42), (None, 0, 42));
+    }
+
+    #[test]
+    fn test_single_trial_returns_the_last_trove() {
+        let trove_manager = MockTroveManager::new(&[500, 400, 300, 200, 100]);
+
+        // No trove is sampled, so the seed is returned unchanged
+        let (hint, diff, seed) = hint_helpers().get_approx_hint(&trove_manager, 450, 1, 42);
+        assert_eq!(hint, Some(5));
+        assert_eq!(diff, 350);
+        assert_eq!(seed, 42);
+    }
+
+    #[test]
+    fn test_enough_trials_find_the_closest_trove() {
+        let trove_manager = MockTroveManager::new(&[500, 400, 300, 200, 100]);
+
+        let (hint, diff, _) = hint_helpers().get_approx_hint(&trove_manager, 390, 200, 7);
+        assert_eq!(hint, trove_manager.sorted_trove_id(trove_manager.owner(1)));
+        assert_eq!(diff, 10);
+
+        let (hint, diff, _) = hint_helpers().get_approx_hint(&trove_manager, 300, 200, 7);
+        assert_eq!(hint, Some(3));
+        assert_eq!(diff, 0);
+    }
+
+    #[test]
+    fn test_get_approx_hint_is_deterministic_for_a_seed() {
+        let trove_manager = MockTroveManager::new(&[900, 800, 700, 600, 500, 400, 300, 200, 100]);
+        let hint_helpers = hint_helpers();
+
+        let first = hint_helpers.get_approx_hint(&trove_manager, 450, 4, 1234);
+        assert_eq!(hint_helpers.get_approx_hint(&trove_manager, 450, 4, 1234), first);
+
+        // The returned seed is the input chained once per sampled trove, so clients can resume from it
+        let mut expected_seed = 1234;
+        for _ in 1..4 {
+            expected_seed = MultiCollateralHintHelpers::next_random_seed(expected_seed);
+        }
+        assert_eq!(first.2, expected_seed);
+
+        // The hint is never further from the target than the last trove
+        assert!(first.1 <= 350);
+    }
+}
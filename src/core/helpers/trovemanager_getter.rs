@@ -1,5 +1,7 @@
 use std::collections::HashMap;
 use borsh::{BorshDeserialize, BorshSerialize};
+use crate::core::trove_manager::TroveManager;
+use crate::core::Debt_token::DebtToken;
 
 #[derive(BorshSerialize, BorshDeserialize)]
 pub struct TroveManagerGetters {
@@ -36,6 +38,18 @@ impl TroveManagerGetters {
         *self.trove_status.get(&(trove_manager.to_string(), account.to_string())).unwrap_or(&0)
     }
 
+    // Debt that can still be borrowed against a collateral: the lower of its own
+    // remaining max system debt and the remaining global mint cap
+    pub fn get_debt_headroom(&self, trove_manager: &TroveManager, debt_token: &DebtToken) -> u128 {
+        trove_manager
+            .get_remaining_debt_capacity()
+            .min(debt_token.get_global_debt_headroom())
+    }
+
+    pub fn get_global_debt_headroom(&self, debt_token: &DebtToken) -> u128 {
+        debt_token.get_global_debt_headroom()
+    }
+
     pub fn get_active_trove_managers_for_account(&self, account: &str) -> Vec<String> {
         let mut active_managers = Vec::new();
         for (key, value) in self.trove_status.iter() {
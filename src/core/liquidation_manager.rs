@@ -21,6 +21,23 @@ use arch_program::{
 };
 
 use bitcoin::{self, Transaction}; // Ensure this import is present
+use crate::core::trove_manager::{TroveManager, Status};
+use crate::core::stability_pool::StabilityPool;
+use crate::core::Debt_token::DebtToken;
+use crate::core::borrower_operations::BorrowerOperationsState;
+use crate::core::price_feed::PriceFeed;
+use crate::dependencies::babel_base::BabelBase;
+use crate::dependencies::babel_math::BabelMath;
+use crate::dependencies::protocol_error::ProtocolError;
+use crate::dependencies::protocol_event::ProtocolEvent;
+use crate::interfaces::liquidation_manager::{TroveLiquidated, TroveUpdated};
+
+const DECIMAL_PRECISION: u128 = 1_000_000_000_000_000_000; // 1e18
+const _100PCT: u128 = DECIMAL_PRECISION;
+
+// Operation codes carried by TroveLiquidated / TroveUpdated
+const LIQUIDATE_IN_NORMAL_MODE: u8 = 1;
+const LIQUIDATE_IN_RECOVERY_MODE: u8 = 2;
 
 // Define UTXO structure
 pub struct Utxo {
@@ -61,14 +78,103 @@ pub struct LiquidationManager {
     borrower_operations: String,
     factory: Pubkey, // Change factory type to Pubkey
     enabled_trove_managers: HashMap<String, bool>,
+    // StabilityPool collateral index of each enabled trove manager
+    trove_manager_collateral: HashMap<String, u64>,
     utxo_set: UtxoSet,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
-pub struct Liquidation {
-    borrower: Pubkey, // Change borrower type to Pubkey
-    liquidated_debt: u64,
-    liquidated_coll: u64,
+// Amounts moved by the liquidation of a single trove
+#[derive(Debug, Clone, Default, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct LiquidationValues {
+    pub entire_trove_debt: u128,
+    pub entire_trove_coll: u128,
+    pub coll_gas_compensation: u128,
+    pub debt_gas_compensation: u128,
+    pub debt_to_offset: u128,
+    pub coll_to_send_to_sp: u128,
+    pub debt_to_redistribute: u128,
+    pub coll_to_redistribute: u128,
+    pub coll_surplus: u128,
+}
+
+// Aggregated amounts moved by a sequence or batch of liquidations
+#[derive(Debug, Clone, Default, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct LiquidationTotals {
+    pub troves_liquidated: u32,
+    pub total_debt_in_sequence: u128,
+    pub total_coll_in_sequence: u128,
+    pub total_coll_gas_compensation: u128,
+    pub total_debt_gas_compensation: u128,
+    pub total_debt_to_offset: u128,
+    pub total_coll_to_send_to_sp: u128,
+    pub total_debt_to_redistribute: u128,
+    pub total_coll_to_redistribute: u128,
+    pub total_coll_surplus: u128,
+}
+
+impl LiquidationTotals {
+    fn add(&mut self, values: &LiquidationValues) {
+        self.troves_liquidated += 1;
+        self.total_debt_in_sequence += values.entire_trove_debt;
+        self.total_coll_in_sequence += values.entire_trove_coll;
+        self.total_coll_gas_compensation += values.coll_gas_compensation;
+        self.total_debt_gas_compensation += values.debt_gas_compensation;
+        self.total_debt_to_offset += values.debt_to_offset;
+        self.total_coll_to_send_to_sp += values.coll_to_send_to_sp;
+        self.total_debt_to_redistribute += values.debt_to_redistribute;
+        self.total_coll_to_redistribute += values.coll_to_redistribute;
+        self.total_coll_surplus += values.coll_surplus;
+    }
+}
+
+// System totals tracked while liquidating, so TCR can be recomputed after each trove.
+// Priced collateral is in debt units with 18 decimals.
+struct SystemState {
+    total_priced_collateral: u128,
+    total_debt: u128,
+    debt_in_stab_pool: u128,
+    // Gas compensation helpers, built from the reserve configured on the debt token
+    babel_base: BabelBase,
+}
+
+impl SystemState {
+    fn new(
+        borrower_operations: &BorrowerOperationsState,
+        stability_pool: &StabilityPool,
+        debt_token: &DebtToken,
+        collateral_id: u64,
+    ) -> Self {
+        let (_, total_priced_collateral, total_debt) = borrower_operations.get_tcr_data(&borrower_operations.fetch_balances());
+        // Once a sunset collateral's grace period ends, the pool stops absorbing its debt
+        // and every liquidation is redistributed
+        let debt_in_stab_pool = if stability_pool.accepts_offsets(collateral_id) {
+            stability_pool.get_total_debt_token_deposits()
+        } else {
+            0
+        };
+        Self {
+            total_priced_collateral,
+            total_debt,
+            debt_in_stab_pool,
+            babel_base: BabelBase::new(debt_token.debt_gas_compensation()),
+        }
+    }
+
+    fn tcr(&self) -> u128 {
+        if self.total_debt == 0 {
+            u128::MAX
+        } else {
+            BabelMath::mul_div(self.total_priced_collateral, DECIMAL_PRECISION, self.total_debt)
+        }
+    }
+
+    // Offset debt and collateral leaving the system change TCR. Redistributed amounts stay in the system.
+    fn apply(&mut self, values: &LiquidationValues, price: u128) {
+        let coll_removed = values.coll_to_send_to_sp + values.coll_gas_compensation + values.coll_surplus;
+        self.total_priced_collateral = self.total_priced_collateral.saturating_sub(BabelMath::mul_div(coll_removed, price, DECIMAL_PRECISION));
+        self.total_debt = self.total_debt.saturating_sub(values.debt_to_offset);
+        self.debt_in_stab_pool = self.debt_in_stab_pool.saturating_sub(values.debt_to_offset);
+    }
 }
 
 impl LiquidationManager {
@@ -79,44 +185,413 @@ impl LiquidationManager {
             borrower_operations: String::new(),
             factory,
             enabled_trove_managers: HashMap::new(),
+            trove_manager_collateral: HashMap::new(),
             utxo_set: UtxoSet::new(),
         }
     }
 
-    pub fn enable_trove_manager(&mut self, trove_manager: String) {
-        assert_eq!(self.factory, self.factory, "Only factory can enable a trove manager");
-        self.enabled_trove_managers.insert(trove_manager, true);
+    // Function to enable a newly deployed trove manager, called by the factory with its own
+    // account from the transaction
+    pub fn enable_trove_manager(&mut self, signer: &AccountInfo, trove_manager: String, collateral_id: u64) -> Result<(), ProtocolError> {
+        self.ensure_factory_signer(signer)?;
+        self.enabled_trove_managers.insert(trove_manager.clone(), true);
+        self.trove_manager_collateral.insert(trove_manager, collateral_id);
+        Ok(())
+    }
+
+    pub fn is_trove_manager_enabled(&self, trove_manager: &str) -> bool {
+        *self.enabled_trove_managers.get(trove_manager).unwrap_or(&false)
     }
 
-    pub fn liquidate(&mut self, trove_manager: String, borrower: Pubkey) -> Result<Option<Liquidation>, ProgramError> {
+    // Liquidates a single trove. The system TCR is taken from BorrowerOperations to decide between
+    // Normal Mode and Recovery Mode rules. Returns None if the trove cannot be liquidated.
+    // Liquidations are priced with the collateral's configured price source, never a caller-supplied price.
+    pub fn liquidate(
+        &mut self,
+        trove_manager: String,
+        tm: &mut TroveManager,
+        stability_pool: &mut StabilityPool,
+        borrower_operations: &BorrowerOperationsState,
+        debt_token: &mut DebtToken,
+        liquidator: Pubkey,
+        tm_account: &AccountInfo,
+        borrower: Pubkey,
+        price_feed: &mut PriceFeed,
+    ) -> Result<Option<(TroveLiquidated, TroveUpdated)>, ProtocolError> {
         let is_enabled = *self.enabled_trove_managers.get(&trove_manager).unwrap_or(&false);
         if (!is_enabled) {
 
             return Ok(None);
         }
+        let collateral_id = *self.trove_manager_collateral.get(&trove_manager).ok_or(ProtocolError::InvalidTroveManager)?;
+        let price = Self::fetch_price(tm, price_feed)?;
 
-        // Use Arch SDK for financial calculations
-        let debt_reduction = FinancialMath::calculate_debt_reduction();
-        let collateral_reduction = FinancialMath::calculate_collateral_reduction();
+        let mut system = SystemState::new(borrower_operations, stability_pool, debt_token, collateral_id);
+        let (values, operation) = match self.liquidate_single(tm, borrower, &system, price)? {
+            Some(result) => result,
+            None => return Ok(None),
+        };
+        system.apply(&values, price);
+        self.log_trove_liquidated(&tm.get_collateral_token(), borrower, &values, operation);
 
-        let liquidation = Liquidation {
-            borrower: borrower.clone(),
+        let mut totals = LiquidationTotals::default();
+        totals.add(&values);
+        self.apply_liquidation_totals(tm, stability_pool, collateral_id, &totals)?;
+        self.send_gas_compensation(tm, debt_token, liquidator, &totals, tm_account)?;
 
-            liquidated_debt: debt_reduction,
+        Ok(Some(self.trove_liquidation_events(borrower, &values, operation)))
+    }
 
-            liquidated_coll: collateral_reduction,
-        };
+    // Liquidates up to `max_troves_to_liquidate` troves, starting from the one with the lowest ICR and
+    // stopping at the first trove with ICR above `max_icr` or that cannot be liquidated
+    pub fn liquidate_troves(
+        &mut self,
+        trove_manager: String,
+        tm: &mut TroveManager,
+        stability_pool: &mut StabilityPool,
+        borrower_operations: &BorrowerOperationsState,
+        debt_token: &mut DebtToken,
+        liquidator: Pubkey,
+        tm_account: &AccountInfo,
+        max_troves_to_liquidate: u32,
+        max_icr: u128,
+        price_feed: &mut PriceFeed,
+    ) -> Result<LiquidationTotals, ProtocolError> {
+        let collateral_id = self.require_enabled(&trove_manager)?;
+        let price = Self::fetch_price(tm, price_feed)?;
+
+        let mut system = SystemState::new(borrower_operations, stability_pool, debt_token, collateral_id);
+        let mut totals = LiquidationTotals::default();
+        let mut troves_remaining = max_troves_to_liquidate;
+        let mut current = tm.sorted_troves().get_last();
+
+        // The last trove in the system can never be liquidated
+        while let Some(id) = current {
+            if troves_remaining == 0 || tm.get_trove_owners_count() <= 1 {
+                break;
+            }
+            // Save the neighbour now, as the current trove is removed from the list when liquidated
+            let next = tm.sorted_troves().get_prev(id);
+            let borrower = match tm.get_trove_owner_by_sorted_id(id) {
+                Some(borrower) => borrower,
+                None => break,
+            };
+            if tm.get_current_icr(borrower, price) > max_icr {
+                break;
+            }
+
+            match self.liquidate_single(tm, borrower, &system, price)? {
+                Some((values, operation)) => {
+                    // Recovery Mode is re-evaluated on the next iteration from the updated totals
+                    system.apply(&values, price);
+                    totals.add(&values);
+                    self.log_trove_liquidated(&tm.get_collateral_token(), borrower, &values, operation);
+                }
+                None => break,
+            }
+
+            troves_remaining -= 1;
+            current = next;
+        }
 
-        // Log the liquidation event
-        msg!("Liquidation occurred for borrower: {:?}", borrower);
+        if totals.troves_liquidated == 0 {
+            return Err(ProtocolError::NothingToLiquidate);
+        }
+        self.apply_liquidation_totals(tm, stability_pool, collateral_id, &totals)?;
+        self.send_gas_compensation(tm, debt_token, liquidator, &totals, tm_account)?;
+        Ok(totals)
+    }
 
-        // Create a transaction to record the liquidation
-        let tx = self.create_liquidation_transaction(&borrower, debt_reduction, collateral_reduction)?;
+    // Liquidates every trove in `trove_array` that is eligible, skipping the ones that are not
+    pub fn batch_liquidate_troves(
+        &mut self,
+        trove_manager: String,
+        tm: &mut TroveManager,
+        stability_pool: &mut StabilityPool,
+        borrower_operations: &BorrowerOperationsState,
+        debt_token: &mut DebtToken,
+        liquidator: Pubkey,
+        tm_account: &AccountInfo,
+        trove_array: Vec<Pubkey>,
+        price_feed: &mut PriceFeed,
+    ) -> Result<LiquidationTotals, ProtocolError> {
+        let collateral_id = self.require_enabled(&trove_manager)?;
+        if trove_array.is_empty() {
+            return Err(ProtocolError::EmptyTroveList);
+        }
+        let price = Self::fetch_price(tm, price_feed)?;
 
-        // Set the transaction to be signed
-        set_transaction_to_sign(tx)?;
+        let mut system = SystemState::new(borrower_operations, stability_pool, debt_token, collateral_id);
+        let totals = self.liquidate_trove_array(tm, trove_array, &mut system, price)?;
 
-        Ok(Some(liquidation))
+        if totals.troves_liquidated == 0 {
+            return Err(ProtocolError::NothingToLiquidate);
+        }
+        self.apply_liquidation_totals(tm, stability_pool, collateral_id, &totals)?;
+        self.send_gas_compensation(tm, debt_token, liquidator, &totals, tm_account)?;
+        Ok(totals)
+    }
+
+    // Liquidates the eligible troves of `trove_array` in order, skipping the others
+    fn liquidate_trove_array(
+        &self,
+        tm: &mut TroveManager,
+        trove_array: Vec<Pubkey>,
+        system: &mut SystemState,
+        price: u128,
+    ) -> Result<LiquidationTotals, ProtocolError> {
+        let mut totals = LiquidationTotals::default();
+        for borrower in trove_array {
+            if tm.get_trove_owners_count() <= 1 {
+                break;
+            }
+            if !tm.is_active_trove(borrower) {
+                continue;
+            }
+
+            if let Some((values, operation)) = self.liquidate_single(tm, borrower, system, price)? {
+                // Recovery Mode is re-evaluated for the next trove from the updated totals
+                system.apply(&values, price);
+                totals.add(&values);
+                self.log_trove_liquidated(&tm.get_collateral_token(), borrower, &values, operation);
+            }
+        }
+        Ok(totals)
+    }
+
+    // Price of the trove manager's collateral from its configured source (spot, TWAP or the lower of both)
+    fn fetch_price(tm: &TroveManager, price_feed: &mut PriceFeed) -> Result<u128, ProtocolError> {
+        tm.fetch_price(price_feed).ok_or(ProtocolError::PriceUnavailable)
+    }
+
+    fn require_enabled(&self, trove_manager: &String) -> Result<u64, ProtocolError> {
+        if !*self.enabled_trove_managers.get(trove_manager).unwrap_or(&false) {
+            return Err(ProtocolError::InvalidTroveManager);
+        }
+        self.trove_manager_collateral.get(trove_manager).cloned().ok_or(ProtocolError::InvalidTroveManager)
+    }
+
+    // Applies Normal Mode or Recovery Mode rules depending on the current TCR
+    fn liquidate_single(
+        &self,
+        tm: &mut TroveManager,
+        borrower: Pubkey,
+        system: &SystemState,
+        price: u128,
+    ) -> Result<Option<(LiquidationValues, u8)>, ProtocolError> {
+        let icr = tm.get_current_icr(borrower, price);
+        let tcr = system.tcr();
+
+        if tcr < BabelBase::CCR {
+            // Stop if the pool is empty and only capped liquidations would remain
+            if icr >= tm.mcr() && system.debt_in_stab_pool == 0 {
+                return Ok(None);
+            }
+            Ok(self
+                .liquidate_recovery_mode(tm, borrower, icr, system, tcr, price)?
+                .map(|values| (values, LIQUIDATE_IN_RECOVERY_MODE)))
+        } else if icr < tm.mcr() {
+            Ok(Some((self.liquidate_normal_mode(tm, borrower, system)?, LIQUIDATE_IN_NORMAL_MODE)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    // Moves liquidated debt and collateral to the Stability Pool and active troves
+    fn apply_liquidation_totals(
+        &self,
+        tm: &mut TroveManager,
+        stability_pool: &mut StabilityPool,
+        collateral_id: u64,
+        totals: &LiquidationTotals,
+    ) -> Result<(), ProtocolError> {
+        if totals.total_debt_to_offset > 0 {
+            stability_pool.offset(collateral_id, totals.total_debt_to_offset, totals.total_coll_to_send_to_sp)?;
+        }
+        tm.redistribute_debt_and_coll(totals.total_debt_to_redistribute, totals.total_coll_to_redistribute);
+        tm.update_system_snapshots();
+
+        msg!(
+            "Liquidation: troves = {}, debt_offset = {}, coll_to_sp = {}, debt_redistributed = {}, coll_redistributed = {}, coll_gas_compensation = {}, debt_gas_compensation = {}, coll_surplus = {}",
+            totals.troves_liquidated,
+            totals.total_debt_to_offset,
+            totals.total_coll_to_send_to_sp,
+            totals.total_debt_to_redistribute,
+            totals.total_coll_to_redistribute,
+            totals.total_coll_gas_compensation,
+            totals.total_debt_gas_compensation,
+            totals.total_coll_surplus
+        );
+        Ok(())
+    }
+
+    // Pays the liquidator the debt reserves and collateral compensation of the liquidated troves
+    fn send_gas_compensation(
+        &self,
+        tm: &mut TroveManager,
+        debt_token: &mut DebtToken,
+        liquidator: Pubkey,
+        totals: &LiquidationTotals,
+        tm_account: &AccountInfo,
+    ) -> Result<(), ProtocolError> {
+        tm.send_gas_compensation(
+            debt_token,
+            liquidator,
+            totals.total_debt_gas_compensation,
+            totals.total_coll_gas_compensation,
+            tm_account,
+        )
+    }
+
+    fn log_trove_liquidated(&self, collateral: &str, borrower: Pubkey, values: &LiquidationValues, operation: u8) {
+        msg!(
+            "TroveLiquidated: borrower = {:?}, debt = {}, coll = {}, operation = {}",
+            borrower,
+            values.entire_trove_debt,
+            values.entire_trove_coll - values.coll_surplus,
+            operation
+        );
+        ProtocolEvent::TroveLiquidated {
+            collateral: collateral.to_string(),
+            borrower: borrower.to_string(),
+            coll: values.entire_trove_coll - values.coll_surplus,
+            debt: values.entire_trove_debt,
+            operation,
+        }
+        .emit();
+    }
+
+    // Liquidates a trove with ICR < MCR: offset against the Stability Pool, redistribute the remainder
+    fn liquidate_normal_mode(
+        &self,
+        tm: &mut TroveManager,
+        borrower: Pubkey,
+        system: &SystemState,
+    ) -> Result<LiquidationValues, ProtocolError> {
+        let (entire_trove_debt, entire_trove_coll) = tm
+            .close_trove(borrower, Status::ClosedByLiquidation)?;
+
+        let coll_gas_compensation = system.babel_base.get_coll_gas_compensation(entire_trove_coll);
+        let coll_to_liquidate = entire_trove_coll - coll_gas_compensation;
+        let (debt_to_offset, coll_to_send_to_sp, debt_to_redistribute, coll_to_redistribute) =
+            Self::get_offset_and_redistribution_vals(entire_trove_debt, coll_to_liquidate, system.debt_in_stab_pool);
+
+        Ok(LiquidationValues {
+            entire_trove_debt,
+            entire_trove_coll,
+            coll_gas_compensation,
+            debt_gas_compensation: system.babel_base.debt_gas_compensation(),
+            debt_to_offset,
+            coll_to_send_to_sp,
+            debt_to_redistribute,
+            coll_to_redistribute,
+            coll_surplus: 0,
+        })
+    }
+
+    // Liquidates a trove while TCR < CCR:
+    // - ICR <= 100%: everything is redistributed, the Stability Pool is not used
+    // - ICR < MCR: same as Normal Mode
+    // - MCR <= ICR < TCR: the full debt is offset, collateral is capped at MCR x debt and the excess is claimable by the owner
+    fn liquidate_recovery_mode(
+        &self,
+        tm: &mut TroveManager,
+        borrower: Pubkey,
+        icr: u128,
+        system: &SystemState,
+        tcr: u128,
+        price: u128,
+    ) -> Result<Option<LiquidationValues>, ProtocolError> {
+        if icr <= _100PCT {
+            let (entire_trove_debt, entire_trove_coll) = tm
+                .close_trove(borrower, Status::ClosedByLiquidation)?;
+            let coll_gas_compensation = system.babel_base.get_coll_gas_compensation(entire_trove_coll);
+
+            return Ok(Some(LiquidationValues {
+                entire_trove_debt,
+                entire_trove_coll,
+                coll_gas_compensation,
+                debt_gas_compensation: system.babel_base.debt_gas_compensation(),
+                debt_to_redistribute: entire_trove_debt,
+                coll_to_redistribute: entire_trove_coll - coll_gas_compensation,
+                ..Default::default()
+            }));
+        }
+
+        if icr < tm.mcr() {
+            return self.liquidate_normal_mode(tm, borrower, system).map(Some);
+        }
+
+        let (entire_trove_debt, _, _, _) = tm.get_entire_debt_and_coll(borrower);
+        // If ICR >= TCR, or the Stability Pool cannot absorb the whole debt, the trove is left untouched
+        if icr >= tcr || entire_trove_debt > system.debt_in_stab_pool {
+            return Ok(None);
+        }
+
+        let (entire_trove_debt, entire_trove_coll) = tm
+            .close_trove(borrower, Status::ClosedByLiquidation)?;
+        let values = self.get_capped_offset_vals(&system.babel_base, tm.mcr(), entire_trove_debt, entire_trove_coll, price);
+        if values.coll_surplus > 0 {
+            tm.account_collateral_surplus(borrower, values.coll_surplus);
+        }
+        Ok(Some(values))
+    }
+
+    // Splits a liquidated trove between the Stability Pool offset and redistribution to active troves
+    fn get_offset_and_redistribution_vals(
+        debt: u128,
+        coll: u128,
+        debt_in_stab_pool: u128,
+    ) -> (u128, u128, u128, u128) {
+        if debt_in_stab_pool == 0 {
+            return (0, 0, debt, coll);
+        }
+        // Offset as much debt & collateral as possible against the Stability Pool, and redistribute the remainder
+        let debt_to_offset = BabelMath::min(debt, debt_in_stab_pool);
+        let coll_to_send_to_sp = BabelMath::mul_div(coll, debt_to_offset, debt);
+        (debt_to_offset, coll_to_send_to_sp, debt - debt_to_offset, coll - coll_to_send_to_sp)
+    }
+
+    // Liquidation values for a Recovery Mode trove with MCR <= ICR < TCR: the collateral that is
+    // offset is capped at MCR x debt, anything above stays with the owner as a claimable surplus
+    fn get_capped_offset_vals(
+        &self,
+        babel_base: &BabelBase,
+        mcr: u128,
+        entire_trove_debt: u128,
+        entire_trove_coll: u128,
+        price: u128,
+    ) -> LiquidationValues {
+        let capped_coll_portion = BabelMath::mul_div(entire_trove_debt, mcr, price);
+        let coll_gas_compensation = babel_base.get_coll_gas_compensation(capped_coll_portion);
+
+        LiquidationValues {
+            entire_trove_debt,
+            entire_trove_coll,
+            coll_gas_compensation,
+            debt_gas_compensation: babel_base.debt_gas_compensation(),
+            debt_to_offset: entire_trove_debt,
+            coll_to_send_to_sp: capped_coll_portion - coll_gas_compensation,
+            coll_surplus: entire_trove_coll - capped_coll_portion,
+            ..Default::default()
+        }
+    }
+
+    fn trove_liquidation_events(&self, borrower: Pubkey, values: &LiquidationValues, operation: u8) -> (TroveLiquidated, TroveUpdated) {
+        let liquidated = TroveLiquidated {
+            borrower: borrower.to_string(),
+            debt: values.entire_trove_debt.into(),
+            coll: (values.entire_trove_coll - values.coll_surplus).into(),
+            operation,
+        };
+        let updated = TroveUpdated {
+            borrower: borrower.to_string(),
+            debt: 0u128.into(),
+            coll: 0u128.into(),
+            stake: 0u128.into(),
+            operation,
+        };
+        (liquidated, updated)
     }
 
     fn create_liquidation_transaction(&self, borrower: &Pubkey, debt_reduction: u64, collateral_reduction: u64) -> Result<TransactionToSign, ProgramError> {
@@ -183,11 +658,19 @@ impl LiquidationManager {
     }
 
     // Access control method
-    pub fn ensure_factory_or_approved(&self, caller: Pubkey) {
-        assert!(
-            caller == self.factory || self.is_approved_delegate(self.factory.clone(), caller),
-            "Unauthorized access"
-        );
+    // The key of an account is only trusted once the runtime has checked its signature
+    pub fn ensure_factory_signer(&self, signer: &AccountInfo) -> Result<(), ProtocolError> {
+        if !signer.is_signer {
+            return Err(ProtocolError::Unauthorized);
+        }
+        self.ensure_factory_or_approved(signer.key)
+    }
+
+    pub fn ensure_factory_or_approved(&self, caller: Pubkey) -> Result<(), ProtocolError> {
+        if caller != self.factory && !self.is_approved_delegate(self.factory.clone(), caller) {
+            return Err(ProtocolError::Unauthorized);
+        }
+        Ok(())
     }
 
     fn is_approved_delegate(&self, factory: Pubkey, caller: Pubkey) -> bool {
@@ -195,30 +678,182 @@ impl LiquidationManager {
     }
 }
 
-mod FinancialMath {
-    pub fn calculate_debt_reduction() -> u64 {
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRICE: u128 = 2 * DECIMAL_PRECISION;
+    const GAS_COMPENSATION: u128 = DECIMAL_PRECISION / 10;
+
+    // Opens troves given as (coll, debt) in whole units, returning their owners
+    fn trove_manager(troves: &[(u128, u128)]) -> (TroveManager, Vec<Pubkey>) {
+        let owner = Pubkey::new_unique();
+        let mut tm = TroveManager::new(owner);
+        tm.set_max_system_debt(&owner, 1_000 * DECIMAL_PRECISION).unwrap();
+        let owners: Vec<Pubkey> = troves.iter().map(|_| Pubkey::new_unique()).collect();
+        for (owner, (coll, debt)) in owners.iter().zip(troves) {
+            tm.open_trove(*owner, coll * DECIMAL_PRECISION, debt * DECIMAL_PRECISION, None, None).unwrap();
+        }
+        (tm, owners)
+    }
 
-        // This function should calculate the debt reduction based on the logic in LiquidationManager.sol
-        100
+    fn signer(key: &Pubkey, is_signer: bool) -> AccountInfo {
+        let mut account = AccountInfo::new(key);
+        account.is_signer = is_signer;
+        account
     }
 
-    pub fn calculate_collateral_reduction() -> u64 {
+    fn system(tm: &TroveManager, debt_in_stab_pool: u128) -> SystemState {
+        SystemState {
+            total_priced_collateral: BabelMath::mul_div(tm.get_entire_system_coll(), PRICE, DECIMAL_PRECISION),
+            total_debt: tm.get_entire_system_debt(),
+            debt_in_stab_pool,
+            babel_base: BabelBase::new(GAS_COMPENSATION),
+        }
+    }
 
-        // This function should calculate the collateral reduction based on the logic in LiquidationManager.sol
-        50
+    #[test]
+    fn test_normal_mode_offsets_then_redistributes() {
+        let (mut tm, owners) = trove_manager(&[(10, 5), (1, 2)]);
+        let manager = LiquidationManager::new(Pubkey::new_unique());
+        let system = system(&tm, DECIMAL_PRECISION);
+        assert!(system.tcr() >= BabelBase::CCR);
+
+        // ICR above MCR cannot be liquidated in Normal Mode
+        assert_eq!(manager.liquidate_single(&mut tm, owners[0], &system, PRICE), Ok(None));
+
+        let (values, operation) = manager.liquidate_single(&mut tm, owners[1], &system, PRICE).unwrap().unwrap();
+        assert_eq!(operation, LIQUIDATE_IN_NORMAL_MODE);
+        assert_eq!(values.coll_gas_compensation, DECIMAL_PRECISION / 200);
+        assert_eq!(values.debt_to_offset, DECIMAL_PRECISION);
+        assert_eq!(values.coll_to_send_to_sp, 995 * DECIMAL_PRECISION / 2000);
+        assert_eq!(values.debt_to_redistribute, DECIMAL_PRECISION);
+        assert_eq!(values.coll_to_redistribute, 995 * DECIMAL_PRECISION / 2000);
+        assert_eq!(values.coll_surplus, 0);
+        assert!(!tm.is_active_trove(owners[1]));
     }
 
-    pub fn calculate_coll_gas_compensation(coll: u64) -> u64 {
+    #[test]
+    fn test_recovery_mode_rules() {
+        // ICRs at PRICE: 200%, 120%, 100%. TCR is 28 / 17, about 165%.
+        let (mut tm, owners) = trove_manager(&[(10, 10), (3, 5), (1, 2)]);
+        let manager = LiquidationManager::new(Pubkey::new_unique());
+        let system = system(&tm, 10 * DECIMAL_PRECISION);
+        assert!(system.tcr() < BabelBase::CCR);
+
+        // ICR >= TCR is left untouched
+        assert_eq!(manager.liquidate_single(&mut tm, owners[0], &system, PRICE), Ok(None));
+
+        // ICR <= 100% is fully redistributed, the pool is not used
+        let (values, operation) = manager.liquidate_single(&mut tm, owners[2], &system, PRICE).unwrap().unwrap();
+        assert_eq!(operation, LIQUIDATE_IN_RECOVERY_MODE);
+        assert_eq!(values.debt_to_offset, 0);
+        assert_eq!(values.debt_to_redistribute, 2 * DECIMAL_PRECISION);
+        assert_eq!(values.coll_to_redistribute, DECIMAL_PRECISION - DECIMAL_PRECISION / 200);
+
+        // MCR <= ICR < TCR: full offset, collateral capped at MCR x debt, the rest is the owner's surplus
+        let system = SystemState { debt_in_stab_pool: 10 * DECIMAL_PRECISION, ..system };
+        let (debt, coll, _, _) = tm.get_entire_debt_and_coll(owners[1]);
+        let (values, operation) = manager.liquidate_single(&mut tm, owners[1], &system, PRICE).unwrap().unwrap();
+        let capped_coll = debt * tm.mcr() / PRICE;
+        assert_eq!(operation, LIQUIDATE_IN_RECOVERY_MODE);
+        assert_eq!(values.debt_to_offset, debt);
+        assert_eq!(values.coll_gas_compensation, capped_coll / 200);
+        assert_eq!(values.coll_to_send_to_sp, capped_coll - capped_coll / 200);
+        assert_eq!(values.coll_surplus, coll - capped_coll);
+        assert_eq!(tm.surplus_of(owners[1]), coll - capped_coll);
+    }
 
-        // This function should calculate the collateral gas compensation based on the logic in LiquidationManager.sol
-        coll / 100 // of the collateral
+    #[test]
+    fn test_batch_re_evaluates_recovery_mode_after_each_trove() {
+        // ICRs at PRICE: 250%, 130%, 130%. TCR is 252 / 120 = 210%, below CCR.
+        let (mut tm, owners) = trove_manager(&[(100, 80), (13, 20), (13, 20)]);
+        let manager = LiquidationManager::new(Pubkey::new_unique());
+        let mut system = system(&tm, 100 * DECIMAL_PRECISION);
+        assert!(system.tcr() < BabelBase::CCR);
+
+        // The capped liquidation of the first trove removes 20 debt and 13 collateral,
+        // lifting TCR to 226%: the second trove is then judged by Normal Mode rules and kept
+        let totals = manager.liquidate_trove_array(&mut tm, vec![owners[1], owners[2], owners[0]], &mut system, PRICE).unwrap();
+        assert_eq!(totals.troves_liquidated, 1);
+        assert_eq!(totals.total_debt_to_offset, 20 * DECIMAL_PRECISION);
+        assert!(system.tcr() >= BabelBase::CCR);
+        assert!(!tm.is_active_trove(owners[1]));
+        assert!(tm.is_active_trove(owners[2]));
+        assert!(tm.is_active_trove(owners[0]));
     }
 
-    pub fn calculate_debt_gas_compensation() -> u64 {
+    #[test]
+    fn test_batch_skips_inactive_troves_and_keeps_the_last_one() {
+        let (mut tm, owners) = trove_manager(&[(1, 2), (1, 2)]);
+        let manager = LiquidationManager::new(Pubkey::new_unique());
+        let mut system = system(&tm, 0);
+        let unknown = Pubkey::new_unique();
+
+        let totals = manager.liquidate_trove_array(&mut tm, vec![unknown, owners[0], owners[1]], &mut system, PRICE).unwrap();
+        assert_eq!(totals.troves_liquidated, 1);
+        assert_eq!(totals.total_debt_to_redistribute, 2 * DECIMAL_PRECISION);
+        assert!(tm.is_active_trove(owners[1]));
+    }
 
-        
-        // This function should calculate the debt gas compensation based on the logic in LiquidationManager.sol
-        10 // Example gas
+    #[test]
+    fn test_recovery_mode_skips_capped_liquidation_the_pool_cannot_absorb() {
+        let (mut tm, owners) = trove_manager(&[(10, 10), (3, 5)]);
+        let manager = LiquidationManager::new(Pubkey::new_unique());
+        let system = system(&tm, 4 * DECIMAL_PRECISION);
+        assert!(system.tcr() < BabelBase::CCR);
+        assert_eq!(manager.liquidate_single(&mut tm, owners[1], &system, PRICE), Ok(None));
+        assert!(tm.is_active_trove(owners[1]));
+    }
 
+    #[test]
+    fn test_liquidation_math_at_a_btc_price() {
+        let price = 60_000 * DECIMAL_PRECISION;
+
+        // 100 BTC against 4M of debt, half of which the pool absorbs
+        let (debt_to_offset, coll_to_send_to_sp, debt_to_redistribute, coll_to_redistribute) =
+            LiquidationManager::get_offset_and_redistribution_vals(4_000_000 * DECIMAL_PRECISION, 100 * DECIMAL_PRECISION, 2_000_000 * DECIMAL_PRECISION);
+        assert_eq!((debt_to_offset, coll_to_send_to_sp), (2_000_000 * DECIMAL_PRECISION, 50 * DECIMAL_PRECISION));
+        assert_eq!((debt_to_redistribute, coll_to_redistribute), (2_000_000 * DECIMAL_PRECISION, 50 * DECIMAL_PRECISION));
+
+        // Capped at 110% of 5.4M of debt, 99 of the 100 BTC are offset
+        let manager = LiquidationManager::new(Pubkey::new_unique());
+        let babel_base = BabelBase::new(GAS_COMPENSATION);
+        let values = manager.get_capped_offset_vals(&babel_base, 11 * DECIMAL_PRECISION / 10, 5_400_000 * DECIMAL_PRECISION, 100 * DECIMAL_PRECISION, price);
+        assert_eq!(values.coll_surplus, DECIMAL_PRECISION);
+        assert_eq!(values.coll_to_send_to_sp + values.coll_gas_compensation, 99 * DECIMAL_PRECISION);
+
+        // 100 BTC worth 6M against 4M of debt; offsetting 400k of debt with 10 BTC keeps TCR at 150%
+        let mut system = SystemState {
+            total_priced_collateral: 6_000_000 * DECIMAL_PRECISION,
+            total_debt: 4_000_000 * DECIMAL_PRECISION,
+            debt_in_stab_pool: 4_000_000 * DECIMAL_PRECISION,
+            babel_base,
+        };
+        assert_eq!(system.tcr(), 15 * DECIMAL_PRECISION / 10);
+        let offset = LiquidationValues {
+            debt_to_offset: 400_000 * DECIMAL_PRECISION,
+            coll_to_send_to_sp: 10 * DECIMAL_PRECISION,
+            ..Default::default()
+        };
+        system.apply(&offset, price);
+        assert_eq!(system.total_priced_collateral, 5_400_000 * DECIMAL_PRECISION);
+        assert_eq!(system.tcr(), 15 * DECIMAL_PRECISION / 10);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_only_the_factory_enables_trove_managers() {
+        let factory = Pubkey::new_unique();
+        let stranger = Pubkey::new_unique();
+        let mut manager = LiquidationManager::new(factory);
+        let trove_manager = Pubkey::new_unique().to_string();
+
+        assert_eq!(manager.enable_trove_manager(&signer(&stranger, true), trove_manager.clone(), 1), Err(ProtocolError::Unauthorized));
+        // Naming the factory's account is not enough without its signature
+        assert_eq!(manager.enable_trove_manager(&signer(&factory, false), trove_manager.clone(), 1), Err(ProtocolError::Unauthorized));
+        assert_eq!(manager.require_enabled(&trove_manager), Err(ProtocolError::InvalidTroveManager));
+
+        manager.enable_trove_manager(&signer(&factory, true), trove_manager.clone(), 1).unwrap();
+        assert_eq!(manager.require_enabled(&trove_manager), Ok(1));
+    }
+}
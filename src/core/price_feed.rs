@@ -37,20 +37,98 @@ use arch_program::{
     bitcoin::{self, Transaction},
 };
 
-// Assuming a simplified interface for AggregatorV3Interface
-trait AggregatorV3Interface {
-    fn latest_round_data(&self) -> (u64, i128, u32, u32, u32);
+use crate::dependencies::babel_math::BabelMath;
+use crate::dependencies::protocol_error::ProtocolError;
+use crate::interfaces::aggregator_v3::{AggregatorV3Interface, RoundData};
+use crate::interfaces::rate_provider::RateProvider;
+
+const TARGET_DIGITS: u32 = 18;
+const BPS_DIVISOR: u128 = 10_000;
+// Extra time allowed beyond an oracle's heartbeat before its answer is considered stale
+const RESPONSE_TIMEOUT_BUFFER: u32 = 3600;
+// Number of observations kept per token for TWAP queries
+const OBSERVATION_CARDINALITY: usize = 64;
+// Number of accepted rounds kept per token for round and point-in-time lookups
+const ROUND_HISTORY_CARDINALITY: usize = 256;
+// Oldest last good price served while the feed is down; past this age no price is served
+const MAX_FROZEN_PRICE_AGE: u32 = 24 * 3600;
+// Period over which a rate may move by `max_change_bps`; the band widens with each period elapsed
+const RATE_CHANGE_PERIOD: u32 = 3600;
+
+// Which price a collateral uses for ICR and liquidation checks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum PriceSource {
+    Spot,
+    Twap,
+    MinSpotTwap,
 }
 
-#[derive(Default, Clone, BorshSerialize, BorshDeserialize)]
+impl Default for PriceSource {
+    fn default() -> Self {
+        PriceSource::Spot
+    }
+}
+
+// Value of the price accumulator at a point in time
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct Observation {
+    timestamp: u32,
+    cumulative_price: u128,
+}
+
+// Price accepted by the feed. `round_id` is numbered by the feed per token, starting at 1;
+// `answered_in_round` is the source round reported with the median.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct PriceRound {
+    pub round_id: u64,
+    pub answer: u128,
+    pub started_at: u32,
+    pub updated_at: u32,
+    pub answered_in_round: u64,
+}
+
+impl From<PriceRound> for RoundData {
+    fn from(round: PriceRound) -> Self {
+        RoundData {
+            round_id: round.round_id as u128,
+            answer: round.answer as i128,
+            started_at: round.started_at as u128,
+            updated_at: round.updated_at as u128,
+            answered_in_round: round.answered_in_round as u128,
+        }
+    }
+}
+
+#[derive(Default, BorshSerialize, BorshDeserialize)]
 pub struct OracleRecord {
-    chainlink_oracle: Box<dyn AggregatorV3Interface>,
-    decimals: u8,
+    // Price sources are program handles and are re-attached on load with attach_oracles, not persisted
+    #[borsh_skip]
+    oracles: Vec<Box<dyn AggregatorV3Interface>>,
     heartbeat: u32,
-    share_price_signature: [u8; 4],
-    share_price_decimals: u8,
+    // Answers further than this from the median of fresh answers are discarded
+    max_deviation_bps: u32,
+    // Minimum number of fresh, non-deviating answers for the feed to be working
+    quorum: u8,
     is_feed_working: bool,
     is_eth_indexed: bool,
+    // A move larger than this from the last stored price within one heartbeat trips the breaker; 0 disables it
+    max_price_change_bps: u32,
+    // Seconds after which a tripped breaker clears itself; 0 leaves clearing to the guardian
+    breaker_cooldown: u32,
+}
+
+// Share price source of a yield-bearing collateral, composed with its underlying USD price
+#[derive(Default, BorshSerialize, BorshDeserialize)]
+pub struct RateProviderRecord {
+    #[borsh_skip]
+    provider: Option<Box<dyn RateProvider>>,
+    // Bounds on the rate, with 18 digits
+    min_rate: u128,
+    max_rate: u128,
+    // Largest accepted move per RATE_CHANGE_PERIOD since the previously accepted rate
+    max_change_bps: u32,
+    cached_rate: u128,
+    last_updated: u32,
 }
 
 #[derive(Default, Clone, BorshSerialize, BorshDeserialize)]
@@ -59,12 +137,89 @@ pub struct PriceRecord {
     timestamp: u32,
     last_updated: u32,
     round_id: u64,
+    // Set while the feed is down and the last good price is served
+    is_frozen: bool,
+    // Sum of price x seconds since the first stored price
+    cumulative_price: u128,
+    // Ring buffer of accumulator values; `observation_index` is the latest entry
+    observations: Vec<Observation>,
+    observation_index: u16,
+    // Time the circuit breaker tripped, 0 when it is not tripped
+    breaker_tripped_at: u32,
+    // Set when the guardian clears the breaker so the next price is accepted as the new reference
+    breaker_reset: bool,
+    // Ring buffer of accepted rounds; `round_index` is the latest entry
+    rounds: Vec<PriceRound>,
+    round_index: u16,
+    latest_round_id: u64,
+}
+
+impl PriceRecord {
+    // Accumulator extrapolated to `timestamp` with the current price
+    fn cumulative_price_at(&self, timestamp: u32) -> u128 {
+        self.cumulative_price + self.scaled_price * timestamp.saturating_sub(self.timestamp) as u128
+    }
+
+    // Carries the accumulator forward to `timestamp` and records an observation
+    fn write_observation(&mut self, timestamp: u32) {
+        self.cumulative_price = self.cumulative_price_at(timestamp);
+        let observation = Observation { timestamp, cumulative_price: self.cumulative_price };
+        if self.observations.len() < OBSERVATION_CARDINALITY {
+            self.observations.push(observation);
+            self.observation_index = (self.observations.len() - 1) as u16;
+        } else {
+            self.observation_index = ((self.observation_index as usize + 1) % OBSERVATION_CARDINALITY) as u16;
+            self.observations[self.observation_index as usize] = observation;
+        }
+    }
+
+    fn write_round(&mut self, answer: u128, started_at: u32, updated_at: u32, answered_in_round: u64) {
+        self.latest_round_id += 1;
+        let round = PriceRound { round_id: self.latest_round_id, answer, started_at, updated_at, answered_in_round };
+        if self.rounds.len() < ROUND_HISTORY_CARDINALITY {
+            self.rounds.push(round);
+            self.round_index = (self.rounds.len() - 1) as u16;
+        } else {
+            self.round_index = ((self.round_index as usize + 1) % ROUND_HISTORY_CARDINALITY) as u16;
+            self.rounds[self.round_index as usize] = round;
+        }
+    }
+
+    // Rounds are numbered consecutively, so a round's slot follows from its distance to the latest one
+    fn round(&self, round_id: u64) -> Option<PriceRound> {
+        let len = self.rounds.len();
+        let age = self.latest_round_id.checked_sub(round_id)? as usize;
+        if round_id == 0 || age >= len {
+            return None;
+        }
+        Some(self.rounds[(self.round_index as usize + len - age) % len])
+    }
+
+    // Latest round updated at or before `target`
+    fn round_at_or_before(&self, target: u32) -> Option<PriceRound> {
+        let len = self.rounds.len();
+        let newest = self.round_index as usize;
+        (0..len)
+            .map(|i| self.rounds[(newest + len - i) % len])
+            .find(|round| round.updated_at <= target)
+    }
+
+    // Newest observation at or before `target`, or the oldest one if none is that old
+    fn observation_before(&self, target: u32) -> Option<Observation> {
+        let len = self.observations.len();
+        let newest = self.observation_index as usize;
+        (0..len)
+            .map(|i| self.observations[(newest + len - i) % len])
+            .find(|observation| observation.timestamp <= target)
+            .or_else(|| self.observations.get((newest + 1) % len).cloned())
+    }
 }
 
 #[derive(BorshSerialize, BorshDeserialize)]
 pub struct PriceFeed {
     oracle_records: HashMap<String, OracleRecord>,
     price_records: HashMap<String, PriceRecord>,
+    rate_providers: HashMap<String, RateProviderRecord>,
     owner: Pubkey, // Changed to Pubkey for compatibility with Arch SDK
 }
 
@@ -73,121 +228,409 @@ impl PriceFeed {
         Self {
             oracle_records: HashMap::new(),
             price_records: HashMap::new(),
+            rate_providers: HashMap::new(),
             owner,
         }
     }
 
+    // Function to configure the price sources of a token. Reconfiguring a token keeps its
+    // circuit breaker settings.
     pub fn set_oracle(
         &mut self,
+        caller: Pubkey,
         token: String,
-        chainlink_oracle: Box<dyn AggregatorV3Interface>,
+        oracles: Vec<Box<dyn AggregatorV3Interface>>,
         heartbeat: u32,
-        share_price_signature: [u8; 4],
-        share_price_decimals: u8,
+        max_deviation_bps: u32,
+        quorum: u8,
         is_eth_indexed: bool,
-    ) {
+    ) -> Result<(), ProtocolError> {
+        self.require_owner(caller)?;
+        Self::validate_oracles(&oracles, quorum)?;
+        if max_deviation_bps as u128 > BPS_DIVISOR {
+            msg!("PriceFeed: deviation band too wide");
+            return Err(ProtocolError::InvalidOracleConfig);
+        }
+
+        let (max_price_change_bps, breaker_cooldown) = self
+            .oracle_records
+            .get(&token)
+            .map_or((0, 0), |record| (record.max_price_change_bps, record.breaker_cooldown));
         let record = OracleRecord {
-            chainlink_oracle,
-            decimals: 18,
+            oracles,
             heartbeat,
-            share_price_signature,
-            share_price_decimals,
+            max_deviation_bps,
+            quorum,
             is_feed_working: true,
             is_eth_indexed,
+            max_price_change_bps,
+            breaker_cooldown,
         };
+        msg!("NewOracleRegistered: token = {}, sources = {}, quorum = {}", token, record.oracles.len(), quorum);
         self.oracle_records.insert(token, record);
+        Ok(())
+    }
+
+    // Function to re-attach the price sources of a configured token after the feed is loaded.
+    // Every other setting of the token is kept.
+    pub fn attach_oracles(&mut self, caller: Pubkey, token: &str, oracles: Vec<Box<dyn AggregatorV3Interface>>) -> Result<(), ProtocolError> {
+        self.require_owner(caller)?;
+        let oracle_record = self.oracle_records.get_mut(token).ok_or(ProtocolError::UnknownToken)?;
+        Self::validate_oracles(&oracles, oracle_record.quorum)?;
+        oracle_record.oracles = oracles;
+        msg!("OraclesAttached: token = {}, sources = {}", token, oracle_record.oracles.len());
+        Ok(())
+    }
+
+    fn validate_oracles(oracles: &[Box<dyn AggregatorV3Interface>], quorum: u8) -> Result<(), ProtocolError> {
+        if oracles.is_empty() || quorum == 0 || quorum as usize > oracles.len() {
+            msg!("PriceFeed: quorum must be between 1 and the number of oracles");
+            return Err(ProtocolError::InvalidOracleConfig);
+        }
+        for oracle in oracles.iter() {
+            if oracle.decimals() as u32 > TARGET_DIGITS {
+                return Err(ProtocolError::InvalidOracleConfig);
+            }
+        }
+        Ok(())
+    }
+
+    fn require_owner(&self, caller: Pubkey) -> Result<(), ProtocolError> {
+        if caller != self.owner {
+            return Err(ProtocolError::Unauthorized);
+        }
+        Ok(())
     }
 
     pub fn fetch_price(&mut self, token: &str) -> Option<u128> {
         let current_timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as u32;
-        let price_record = self.price_records.get(token)?;
+        if let Some(price_record) = self.price_records.get(token) {
+            if price_record.last_updated == current_timestamp {
+                // Return cached price if it was updated in the current block
+                return Some(price_record.scaled_price);
+            }
+        }
 
-        if price_record.last_updated == current_timestamp {
-            // Return cached price if it was updated in the current block
-            return Some(price_record.scaled_price);
+        if self.is_breaker_tripped(token) {
+            // Only the last good price is served until the breaker is cleared
+            return self.price_records.get(token).map(|price_record| price_record.scaled_price);
         }
 
         let oracle_record = self.oracle_records.get(token)?;
-        let (round_id, answer, timestamp, _, _) = oracle_record.chainlink_oracle.latest_round_data();
+        let answers = self.fresh_answers(oracle_record);
+        let quorum = oracle_record.quorum as usize;
+        let is_eth_indexed = oracle_record.is_eth_indexed;
 
-        if self.is_price_stale(timestamp, oracle_record.heartbeat) {
-            return None;
+        if answers.len() < quorum {
+            return self.freeze_price(token, answers.len());
         }
 
-        let mut scaled_price = (answer as u128) * 10u128.pow(18 - oracle_record.decimals as u32);
-
-        if oracle_record.share_price_signature != [0; 4] {
-            // Simulate fetching share price (this would be a call to another contract in Solidity)
-            let share_price = 1u128; // Placeholder for actual share price fetching logic
-            scaled_price = (scaled_price * share_price) / 10u128.pow(oracle_record.share_price_decimals as u32);
+        let answers_len = answers.len();
+        let started_at = answers.iter().map(|(_, timestamp, _)| *timestamp).min().unwrap_or(0);
+        let (mut scaled_price, timestamp, round_id) = Self::median_answer(answers);
+
+        if self.rate_providers.contains_key(token) {
+            // A rate outside its bounds is treated like a failed feed
+            let share_price = match self.fetch_rate(token) {
+                Some(rate) => rate,
+                None => return self.freeze_price(token, answers_len),
+            };
+            scaled_price = BabelMath::mul_div(scaled_price, share_price, 10u128.pow(TARGET_DIGITS));
         }
 
-        if oracle_record.is_eth_indexed {
+        if is_eth_indexed {
             // Convert ETH price to USD
             let eth_price = self.fetch_price("ETH")?;
-            scaled_price = (scaled_price * eth_price) / 1_000_000_000_000_000_000u128;
+            scaled_price = BabelMath::mul_div(scaled_price, eth_price, 1_000_000_000_000_000_000u128);
         }
 
-        // Validate UTXO ownership before proceeding
-        let utxo_meta = UtxoMeta {
-            txid: fees_tx.txid(),
-            vout: 0,
-            amount: 0, // Placeholder, replace with actual amount
-            script_pubkey: get_account_script_pubkey(account.key),
-        };
-        validate_utxo_ownership(&utxo_meta)?;
+        if self.exceeds_price_change_limit(token, scaled_price, timestamp) {
+            return self.trip_breaker(token, scaled_price, current_timestamp);
+        }
 
-        self.store_price(token, scaled_price, timestamp, round_id);
+        if let Some(oracle_record) = self.oracle_records.get_mut(token) {
+            if !oracle_record.is_feed_working {
+                msg!("PriceFeedRecovered: token = {}", token);
+            }
+            oracle_record.is_feed_working = true;
+        }
+        self.store_price(token, scaled_price, started_at, timestamp, round_id);
         Some(scaled_price)
     }
 
-    fn is_price_stale(&self, price_timestamp: u32, heartbeat: u32) -> bool {
+    // Function to configure the price-deviation circuit breaker of a token
+    pub fn set_circuit_breaker(&mut self, caller: Pubkey, token: &str, max_price_change_bps: u32, breaker_cooldown: u32) -> Result<(), ProtocolError> {
+        self.require_owner(caller)?;
+        let oracle_record = self.oracle_records.get_mut(token).ok_or(ProtocolError::UnknownToken)?;
+        if max_price_change_bps as u128 > BPS_DIVISOR {
+            return Err(ProtocolError::InvalidOracleConfig);
+        }
+        oracle_record.max_price_change_bps = max_price_change_bps;
+        oracle_record.breaker_cooldown = breaker_cooldown;
+        msg!("CircuitBreakerSet: token = {}, max_change_bps = {}, cooldown = {}", token, max_price_change_bps, breaker_cooldown);
+        Ok(())
+    }
+
+    // True while the breaker of `token` is tripped and its cooling-off period has not elapsed
+    pub fn is_breaker_tripped(&self, token: &str) -> bool {
+        let tripped_at = match self.price_records.get(token) {
+            Some(price_record) if price_record.breaker_tripped_at != 0 => price_record.breaker_tripped_at,
+            _ => return false,
+        };
+        let cooldown = self.oracle_records.get(token).map_or(0, |record| record.breaker_cooldown);
+        let current_timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as u32;
+        cooldown == 0 || current_timestamp < tripped_at.saturating_add(cooldown)
+    }
+
+    // Function to clear a tripped breaker, called through BabelCore by the guardian.
+    // The next fetched price is accepted without the deviation check.
+    pub fn clear_circuit_breaker(&mut self, token: &str) -> Result<(), ProtocolError> {
+        let price_record = self.price_records.get_mut(token).ok_or(ProtocolError::UnknownToken)?;
+        if price_record.breaker_tripped_at == 0 {
+            return Err(ProtocolError::CircuitBreakerNotTripped);
+        }
+        price_record.breaker_tripped_at = 0;
+        price_record.breaker_reset = true;
+        msg!("CircuitBreakerCleared: token = {}", token);
+        Ok(())
+    }
+
+    // Function to register the share price source of a yield-bearing token
+    pub fn set_rate_provider(
+        &mut self,
+        caller: Pubkey,
+        token: String,
+        provider: Box<dyn RateProvider>,
+        min_rate: u128,
+        max_rate: u128,
+        max_change_bps: u32,
+    ) -> Result<(), ProtocolError> {
+        self.require_owner(caller)?;
+        if min_rate == 0 || min_rate > max_rate || provider.rate_decimals() as u32 > TARGET_DIGITS {
+            return Err(ProtocolError::InvalidOracleConfig);
+        }
+        if max_change_bps == 0 {
+            msg!("PriceFeed: a zero rate change band would reject every rate after the first");
+            return Err(ProtocolError::InvalidOracleConfig);
+        }
+        let record = RateProviderRecord {
+            provider: Some(provider),
+            min_rate,
+            max_rate,
+            max_change_bps,
+            cached_rate: 0,
+            last_updated: 0,
+        };
+        msg!("RateProviderSet: token = {}, min_rate = {}, max_rate = {}", token, min_rate, max_rate);
+        self.rate_providers.insert(token, record);
+        Ok(())
+    }
+
+    // Share price of `token` with 18 digits, cached per block. None if the rate is outside its
+    // bounds or moved more than `max_change_bps` per RATE_CHANGE_PERIOD elapsed since the last
+    // accepted rate. A rejected rate does not move the reference, but the band keeps widening
+    // so a sustained move is accepted once enough time has passed.
+    pub fn fetch_rate(&mut self, token: &str) -> Option<u128> {
         let current_timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as u32;
-        current_timestamp - price_timestamp > heartbeat + 3600 // RESPONSE_TIMEOUT_BUFFER equivalent
+        let record = self.rate_providers.get_mut(token)?;
+        if record.last_updated == current_timestamp && record.cached_rate > 0 {
+            return Some(record.cached_rate);
+        }
+
+        let provider = record.provider.as_ref()?;
+        let rate = provider.get_rate() * 10u128.pow(TARGET_DIGITS - provider.rate_decimals() as u32);
+        if rate < record.min_rate || rate > record.max_rate {
+            msg!("RateOutOfBounds: token = {}, rate = {}", token, rate);
+            return None;
+        }
+        if record.cached_rate > 0 {
+            let change = rate.max(record.cached_rate) - rate.min(record.cached_rate);
+            let periods = (current_timestamp.saturating_sub(record.last_updated) / RATE_CHANGE_PERIOD).max(1);
+            if change * BPS_DIVISOR > record.cached_rate * record.max_change_bps as u128 * periods as u128 {
+                msg!("RateChangeTooLarge: token = {}, rate = {}, previous = {}", token, rate, record.cached_rate);
+                return None;
+            }
+        }
+
+        record.cached_rate = rate;
+        record.last_updated = current_timestamp;
+        Some(rate)
+    }
+
+    // Time-weighted average price over the last `window_secs`. If the stored observations do not
+    // reach that far back, the average covers the oldest observation available instead.
+    pub fn fetch_twap(&mut self, token: &str, window_secs: u32) -> Option<u128> {
+        let spot = self.fetch_price(token)?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as u32;
+        let price_record = self.price_records.get(token)?;
+
+        let start = price_record.observation_before(now.saturating_sub(window_secs))?;
+        let elapsed = now.saturating_sub(start.timestamp);
+        if elapsed == 0 {
+            return Some(spot);
+        }
+        Some((price_record.cumulative_price_at(now) - start.cumulative_price) / elapsed as u128)
+    }
+
+    // Price of `token` according to the collateral's configured price source. While the breaker
+    // is tripped every source is held to the last good price.
+    pub fn fetch_price_with_source(&mut self, token: &str, source: PriceSource, window_secs: u32) -> Option<u128> {
+        let spot = self.fetch_price(token)?;
+        if self.is_breaker_tripped(token) {
+            return Some(spot);
+        }
+        match source {
+            PriceSource::Spot => Some(spot),
+            PriceSource::Twap => self.fetch_twap(token, window_secs),
+            PriceSource::MinSpotTwap => {
+                let twap = self.fetch_twap(token, window_secs)?;
+                Some(spot.min(twap))
+            }
+        }
+    }
+
+    // Accepted round of `token` by feed round id, while it is still in the history
+    pub fn get_round_data(&self, token: &str, round_id: u64) -> Option<RoundData> {
+        self.price_records.get(token)?.round(round_id).map(RoundData::from)
+    }
+
+    pub fn latest_round_data(&self, token: &str) -> Option<RoundData> {
+        let price_record = self.price_records.get(token)?;
+        price_record.round(price_record.latest_round_id).map(RoundData::from)
+    }
+
+    // Price in effect at `timestamp`: the latest accepted round updated at or before it.
+    // None if the history does not reach back that far.
+    pub fn get_price_at(&self, token: &str, timestamp: u32) -> Option<RoundData> {
+        self.price_records.get(token)?.round_at_or_before(timestamp).map(RoundData::from)
     }
 
-    fn store_price(&mut self, token: &str, price: u128, timestamp: u32, round_id: u64) {
-        // Create a state transition transaction using Arch SDK
-        let mut tx = get_state_transition_tx(&[]);
+    pub fn is_feed_working(&self, token: &str) -> bool {
+        self.oracle_records.get(token).map_or(false, |record| record.is_feed_working)
+    }
+
+    pub fn is_price_frozen(&self, token: &str) -> bool {
+        self.price_records.get(token).map_or(false, |record| record.is_frozen)
+    }
 
-        // Add necessary inputs to the transaction
-        let inputs_to_sign = vec![InputToSign {
-            index: 0,
-            signer: self.owner,
-        }];
+    // Answers that are positive, within the heartbeat and within the deviation band around the
+    // median of those, scaled to 18 digits
+    fn fresh_answers(&self, oracle_record: &OracleRecord) -> Vec<(u128, u32, u64)> {
+        let mut answers: Vec<(u128, u32, u64)> = oracle_record
+            .oracles
+            .iter()
+            .filter_map(|oracle| {
+                // Sources with no data report a zeroed round
+                let round: RoundData = oracle.latest_round_data();
+                if round.answer <= 0 || round.updated_at == 0 {
+                    return None;
+                }
+                let round_id = u64::try_from(round.round_id).ok()?;
+                let updated_at = u32::try_from(round.updated_at).ok()?;
+                if self.is_price_stale(updated_at, oracle_record.heartbeat) {
+                    return None;
+                }
+                let scaled = (round.answer as u128) * 10u128.pow(TARGET_DIGITS - oracle.decimals() as u32);
+                Some((scaled, updated_at, round_id))
+            })
+            .collect();
+        if answers.is_empty() {
+            return answers;
+        }
+
+        let (median, _, _) = Self::median_answer(answers.clone());
+        let band = median * oracle_record.max_deviation_bps as u128 / BPS_DIVISOR;
+        answers.retain(|(price, _, _)| price.max(&median) - price.min(&median) <= band);
+        answers
+    }
 
-        let tx_to_sign = TransactionToSign {
-            tx_bytes: &bitcoin::consensus::serialize(&tx),
-            inputs_to_sign: &inputs_to_sign,
+    // Median price; the most recent timestamp and round id among the answers are reported with it
+    fn median_answer(mut answers: Vec<(u128, u32, u64)>) -> (u128, u32, u64) {
+        answers.sort_by_key(|(price, _, _)| *price);
+        let mid = answers.len() / 2;
+        let median = if answers.len() % 2 == 0 {
+            (answers[mid - 1].0 + answers[mid].0) / 2
+        } else {
+            answers[mid].0
         };
+        let (_, timestamp, round_id) = answers.iter().max_by_key(|(_, timestamp, _)| *timestamp).cloned().unwrap();
+        (median, timestamp, round_id)
+    }
 
-        // Add UTXO metadata to the transaction
-        let utxo_meta = UtxoMeta {
-            txid: tx.input[0].previous_output.txid,
-            vout: tx.input[0].previous_output.vout,
-            amount: 0, // Placeholder, replace with actual amount
-            script_pubkey: get_account_script_pubkey(&self.owner),
+    // Whether `price` moved further from the last stored price than the breaker allows.
+    // Prices more than one heartbeat apart are not compared, nor is the first price after a clear.
+    fn exceeds_price_change_limit(&self, token: &str, price: u128, timestamp: u32) -> bool {
+        let (oracle_record, price_record) = match (self.oracle_records.get(token), self.price_records.get(token)) {
+            (Some(oracle_record), Some(price_record)) => (oracle_record, price_record),
+            _ => return false,
         };
+        if oracle_record.max_price_change_bps == 0 || price_record.breaker_reset || price_record.breaker_tripped_at != 0 {
+            return false;
+        }
+        if timestamp.saturating_sub(price_record.timestamp) > oracle_record.heartbeat {
+            return false;
+        }
+        let last_price = price_record.scaled_price;
+        let change = price.max(last_price) - price.min(last_price);
+        change * BPS_DIVISOR > last_price * oracle_record.max_price_change_bps as u128
+    }
 
-        // Ensure the transaction is signed by the owner
-        self.only_owner();
+    // Rejects the fetched price and keeps serving the last good one until the breaker is cleared
+    fn trip_breaker(&mut self, token: &str, rejected_price: u128, current_timestamp: u32) -> Option<u128> {
+        let price_record = self.price_records.get_mut(token)?;
+        price_record.breaker_tripped_at = current_timestamp;
+        msg!(
+            "CircuitBreakerTripped: token = {}, last_price = {}, rejected_price = {}",
+            token, price_record.scaled_price, rejected_price
+        );
+        Some(price_record.scaled_price)
+    }
 
-        // Set the transaction to sign using Arch SDK
-        set_transaction_to_sign(&[], tx_to_sign);
+    // Too few sources responded: mark the feed as not working and serve the last good price,
+    // unless it is older than MAX_FROZEN_PRICE_AGE
+    fn freeze_price(&mut self, token: &str, responses: usize) -> Option<u128> {
+        if let Some(oracle_record) = self.oracle_records.get_mut(token) {
+            if oracle_record.is_feed_working {
+                msg!("PriceFeedFrozen: token = {}, responses = {}", token, responses);
+            }
+            oracle_record.is_feed_working = false;
+        }
+        let price_record = self.price_records.get_mut(token)?;
+        price_record.is_frozen = true;
+        let current_timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as u32;
+        if current_timestamp.saturating_sub(price_record.timestamp) > MAX_FROZEN_PRICE_AGE {
+            msg!("FrozenPriceExpired: token = {}, price_timestamp = {}", token, price_record.timestamp);
+            return None;
+        }
+        Some(price_record.scaled_price)
+    }
 
-        // Log the transaction details
-        msg!("Transaction to sign: {:?}", tx_to_sign);
+    fn is_price_stale(&self, price_timestamp: u32, heartbeat: u32) -> bool {
+        let current_timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as u32;
+        current_timestamp.saturating_sub(price_timestamp) > heartbeat + RESPONSE_TIMEOUT_BUFFER
+    }
 
-        // Store the price record
-        self.price_records.insert(
-            token.to_string(),
-            PriceRecord {
-                scaled_price: price,
-                timestamp,
-                last_updated: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as u32,
-                round_id,
-            },
-        );
+    // Called from the permissionless fetch path, so it only writes feed state and needs no signer
+    fn store_price(&mut self, token: &str, price: u128, started_at: u32, timestamp: u32, round_id: u64) {
+        // Store the price record, accumulating the previous price up to the new one's timestamp
+        let price_record = self.price_records.entry(token.to_string()).or_default();
+        if !price_record.observations.is_empty() {
+            price_record.write_observation(timestamp);
+        } else {
+            price_record.observations.push(Observation { timestamp, cumulative_price: 0 });
+            price_record.observation_index = 0;
+        }
+        price_record.scaled_price = price;
+        price_record.timestamp = timestamp;
+        price_record.last_updated = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as u32;
+        price_record.round_id = round_id;
+        price_record.is_frozen = false;
+        if price_record.breaker_tripped_at != 0 {
+            msg!("CircuitBreakerExpired: token = {}", token);
+        }
+        price_record.breaker_tripped_at = 0;
+        price_record.breaker_reset = false;
+        price_record.write_round(price, started_at, timestamp, round_id);
     }
 
     fn create_transaction_bytes(&self, token: &str, price: u128, timestamp: u32, round_id: u64) -> Vec<u8> {
@@ -223,8 +666,272 @@ impl PriceFeed {
         tx_bytes
     }
 
-    fn only_owner(&self) {
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DP: u128 = 1_000_000_000_000_000_000;
+    const HEARTBEAT: u32 = 3600;
 
-        assert_eq!(self.owner, Pubkey::new_unique(), "Only owner can call this function");
+    struct MockOracle {
+        answer: i128,
+        updated_at: u32,
+        decimals: u8,
     }
-}
\ No newline at end of file
+
+    impl AggregatorV3Interface for MockOracle {
+        fn decimals(&self) -> u8 {
+            self.decimals
+        }
+
+        fn description(&self) -> String {
+            "mock".to_string()
+        }
+
+        fn version(&self) -> u128 {
+            1
+        }
+
+        fn get_round_data(&self, _round_id: u128) -> RoundData {
+            self.latest_round_data()
+        }
+
+        fn latest_round_data(&self) -> RoundData {
+            RoundData {
+                round_id: self.updated_at as u128,
+                answer: self.answer,
+                started_at: self.updated_at as u128,
+                updated_at: self.updated_at as u128,
+                answered_in_round: self.updated_at as u128,
+            }
+        }
+    }
+
+    fn now() -> u32 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as u32
+    }
+
+    // Oracles with 8 decimals answering (whole units, age in seconds)
+    fn oracles(answers: &[(i128, u32)]) -> Vec<Box<dyn AggregatorV3Interface>> {
+        answers
+            .iter()
+            .map(|(answer, age)| {
+                Box::new(MockOracle { answer: answer * 100_000_000, updated_at: now() - age, decimals: 8 }) as Box<dyn AggregatorV3Interface>
+            })
+            .collect()
+    }
+
+    fn price_feed(answers: &[(i128, u32)], quorum: u8) -> (PriceFeed, Pubkey) {
+        let owner = Pubkey::new_unique();
+        let mut feed = PriceFeed::new(owner);
+        feed.set_oracle(owner, "BTC".to_string(), oracles(answers), HEARTBEAT, 500, quorum, false).unwrap();
+        (feed, owner)
+    }
+
+    // Last good price as if stored `age` seconds ago
+    fn seed_price(feed: &mut PriceFeed, price: u128, age: u32) {
+        let price_record = PriceRecord { scaled_price: price, timestamp: now() - age, ..Default::default() };
+        feed.price_records.insert("BTC".to_string(), price_record);
+    }
+
+    #[test]
+    fn test_median_answer() {
+        let answers = vec![(300, 10, 3), (100, 30, 1), (200, 20, 2)];
+        assert_eq!(PriceFeed::median_answer(answers), (200, 30, 1));
+
+        // Even counts average the two middle answers
+        let answers = vec![(100, 10, 1), (400, 20, 2), (200, 30, 3), (300, 5, 4)];
+        assert_eq!(PriceFeed::median_answer(answers), (250, 30, 3));
+    }
+
+    #[test]
+    fn test_fresh_answers_drop_stale_invalid_and_deviating_answers() {
+        let stale = HEARTBEAT + RESPONSE_TIMEOUT_BUFFER + 60;
+        let (feed, _) = price_feed(&[(100, 0), (101, 0), (130, 0), (0, 0), (100, stale)], 2);
+
+        // The median of the three valid answers is 101; 130 is outside the 5% band around it
+        let answers = feed.fresh_answers(&feed.oracle_records["BTC"]);
+        let prices: Vec<u128> = answers.iter().map(|(price, _, _)| *price).collect();
+        assert_eq!(prices, vec![100 * DP, 101 * DP]);
+    }
+
+    #[test]
+    fn test_missing_quorum_freezes_the_last_good_price() {
+        let (mut feed, _) = price_feed(&[(100, 0), (130, 0), (0, 0)], 2);
+        // Without a stored price there is nothing to fall back to
+        assert_eq!(feed.fetch_price("BTC"), None);
+        assert!(!feed.is_feed_working("BTC"));
+
+        seed_price(&mut feed, 95 * DP, 600);
+        assert_eq!(feed.fetch_price("BTC"), Some(95 * DP));
+        assert!(feed.is_price_frozen("BTC"));
+        assert!(!feed.is_feed_working("BTC"));
+    }
+
+    #[test]
+    fn test_frozen_price_expires() {
+        let (mut feed, _) = price_feed(&[(100, 0), (0, 0)], 2);
+        seed_price(&mut feed, 95 * DP, MAX_FROZEN_PRICE_AGE + 1);
+        assert_eq!(feed.fetch_price("BTC"), None);
+        assert!(feed.is_price_frozen("BTC"));
+    }
+
+    struct MockRateProvider {
+        rate: u128,
+    }
+
+    impl RateProvider for MockRateProvider {
+        fn get_rate(&self) -> u128 {
+            self.rate
+        }
+
+        fn rate_decimals(&self) -> u8 {
+            18
+        }
+    }
+
+    fn rate_provider(rate: u128) -> Box<dyn RateProvider> {
+        Box::new(MockRateProvider { rate })
+    }
+
+    #[test]
+    fn test_set_rate_provider_validation() {
+        let (mut feed, owner) = price_feed(&[(100, 0)], 1);
+        assert_eq!(
+            feed.set_rate_provider(Pubkey::new_unique(), "BTC".to_string(), rate_provider(DP), DP, 2 * DP, 100),
+            Err(ProtocolError::Unauthorized)
+        );
+        assert_eq!(
+            feed.set_rate_provider(owner, "BTC".to_string(), rate_provider(DP), DP, 2 * DP, 0),
+            Err(ProtocolError::InvalidOracleConfig)
+        );
+        assert_eq!(
+            feed.set_rate_provider(owner, "BTC".to_string(), rate_provider(DP), 2 * DP, DP, 100),
+            Err(ProtocolError::InvalidOracleConfig)
+        );
+        feed.set_rate_provider(owner, "BTC".to_string(), rate_provider(DP), DP, 2 * DP, 100).unwrap();
+        assert_eq!(feed.fetch_rate("BTC"), Some(DP));
+    }
+
+    #[test]
+    fn test_rate_change_band_widens_with_time() {
+        let (mut feed, owner) = price_feed(&[(100, 0)], 1);
+        feed.set_rate_provider(owner, "BTC".to_string(), rate_provider(DP), DP / 2, 2 * DP, 100).unwrap();
+        assert_eq!(feed.fetch_rate("BTC"), Some(DP));
+
+        // A 3% move is rejected within the first period and the reference is kept
+        let record = feed.rate_providers.get_mut("BTC").unwrap();
+        record.provider = Some(rate_provider(DP * 103 / 100));
+        record.last_updated -= RATE_CHANGE_PERIOD - 1;
+        assert_eq!(feed.fetch_rate("BTC"), None);
+        assert_eq!(feed.rate_providers["BTC"].cached_rate, DP);
+
+        // Three periods after the last accepted rate the band is 3% and the move is accepted
+        feed.rate_providers.get_mut("BTC").unwrap().last_updated -= 2 * RATE_CHANGE_PERIOD + 1;
+        assert_eq!(feed.fetch_rate("BTC"), Some(DP * 103 / 100));
+        assert_eq!(feed.rate_providers["BTC"].cached_rate, DP * 103 / 100);
+
+        // Rates outside the bounds are never accepted
+        let record = feed.rate_providers.get_mut("BTC").unwrap();
+        record.provider = Some(rate_provider(3 * DP));
+        record.last_updated = 0;
+        assert_eq!(feed.fetch_rate("BTC"), None);
+    }
+
+    #[test]
+    fn test_share_price_at_a_btc_price() {
+        // 60,000 * 1.05 in 1e18 units: the product of the two overflows u128
+        let (mut feed, owner) = price_feed(&[(60_000, 0)], 1);
+        feed.set_rate_provider(owner, "BTC".to_string(), rate_provider(DP * 105 / 100), DP, 2 * DP, 100).unwrap();
+        assert_eq!(feed.fetch_price("BTC"), Some(63_000 * DP));
+    }
+
+    #[test]
+    fn test_fetch_price_stores_the_median() {
+        let (mut feed, _) = price_feed(&[(100, 0), (102, 0), (101, 0)], 2);
+        assert_eq!(feed.fetch_price("BTC"), Some(101 * DP));
+
+        let price_record = &feed.price_records["BTC"];
+        assert_eq!(price_record.scaled_price, 101 * DP);
+        assert_eq!(price_record.observations.len(), 1);
+        assert_eq!(price_record.latest_round_id, 1);
+        assert!(!price_record.is_frozen);
+        assert_eq!(feed.latest_round_data("BTC").map(|round| round.answer), Some(101 * DP as i128));
+    }
+
+    #[test]
+    fn test_observation_ring_buffer_wraps() {
+        // Constant price of 2, one observation every 10 seconds
+        let mut price_record = PriceRecord { scaled_price: 2, observations: vec![Observation::default()], ..Default::default() };
+        let writes = OBSERVATION_CARDINALITY as u32 + 9;
+        for i in 1..=writes {
+            price_record.write_observation(i * 10);
+            price_record.timestamp = i * 10;
+        }
+
+        // 74 observations written into 64 slots: the newest sits in slot 9 and the oldest kept is t = 100
+        assert_eq!(price_record.observations.len(), OBSERVATION_CARDINALITY);
+        assert_eq!(price_record.observation_index, 9);
+        assert_eq!(price_record.observations[9], Observation { timestamp: 730, cumulative_price: 1460 });
+        assert_eq!(price_record.cumulative_price_at(735), 1470);
+
+        // Lookups on both sides of the wrap point
+        assert_eq!(price_record.observation_before(725), Some(Observation { timestamp: 720, cumulative_price: 1440 }));
+        assert_eq!(price_record.observation_before(455), Some(Observation { timestamp: 450, cumulative_price: 900 }));
+        // Older than anything kept: the oldest observation is used
+        assert_eq!(price_record.observation_before(50), Some(Observation { timestamp: 100, cumulative_price: 200 }));
+    }
+
+    #[test]
+    fn test_tripped_breaker_holds_every_source_to_the_last_good_price() {
+        let (mut feed, owner) = price_feed(&[(100, 0)], 1);
+        assert_eq!(feed.set_circuit_breaker(Pubkey::new_unique(), "BTC", 1_000, 0), Err(ProtocolError::Unauthorized));
+        feed.set_circuit_breaker(owner, "BTC", 1_000, 0).unwrap();
+        seed_price(&mut feed, 90 * DP, 60);
+
+        // 100 is more than 10% above the last good price of 90
+        assert_eq!(feed.fetch_price("BTC"), Some(90 * DP));
+        assert!(feed.is_breaker_tripped("BTC"));
+        assert_eq!(feed.fetch_price_with_source("BTC", PriceSource::Twap, 600), Some(90 * DP));
+        assert_eq!(feed.fetch_price_with_source("BTC", PriceSource::MinSpotTwap, 600), Some(90 * DP));
+        assert_eq!(feed.price_records["BTC"].scaled_price, 90 * DP);
+    }
+
+    #[test]
+    fn test_set_oracle_validates_and_keeps_breaker_settings() {
+        let (mut feed, owner) = price_feed(&[(100, 0), (100, 0)], 2);
+        assert_eq!(
+            feed.set_oracle(owner, "BTC".to_string(), oracles(&[(100, 0)]), HEARTBEAT, 500, 2, false),
+            Err(ProtocolError::InvalidOracleConfig)
+        );
+        assert_eq!(
+            feed.set_oracle(Pubkey::new_unique(), "BTC".to_string(), oracles(&[(100, 0)]), HEARTBEAT, 500, 1, false),
+            Err(ProtocolError::Unauthorized)
+        );
+
+        feed.set_circuit_breaker(owner, "BTC", 1_000, 600).unwrap();
+        feed.set_oracle(owner, "BTC".to_string(), oracles(&[(100, 0)]), HEARTBEAT, 500, 1, false).unwrap();
+        assert_eq!(feed.oracle_records["BTC"].max_price_change_bps, 1_000);
+        assert_eq!(feed.oracle_records["BTC"].breaker_cooldown, 600);
+    }
+
+    #[test]
+    fn test_oracles_are_re_attached_after_reload() {
+        let (mut feed, owner) = price_feed(&[(100, 0), (100, 0)], 2);
+        feed.set_circuit_breaker(owner, "BTC", 1_000, 600).unwrap();
+
+        let mut feed = PriceFeed::try_from_slice(&feed.try_to_vec().unwrap()).unwrap();
+        assert!(feed.oracle_records["BTC"].oracles.is_empty());
+
+        assert_eq!(feed.attach_oracles(owner, "ETH", oracles(&[(100, 0)])), Err(ProtocolError::UnknownToken));
+        assert_eq!(feed.attach_oracles(owner, "BTC", oracles(&[(100, 0)])), Err(ProtocolError::InvalidOracleConfig));
+        assert_eq!(feed.attach_oracles(Pubkey::new_unique(), "BTC", oracles(&[(100, 0), (100, 0)])), Err(ProtocolError::Unauthorized));
+
+        feed.attach_oracles(owner, "BTC", oracles(&[(100, 0), (101, 0)])).unwrap();
+        let record = &feed.oracle_records["BTC"];
+        assert_eq!(record.oracles.len(), 2);
+        assert_eq!((record.quorum, record.max_price_change_bps, record.breaker_cooldown), (2, 1_000, 600));
+        assert_eq!(feed.fresh_answers(record).len(), 2);
+    }
+}
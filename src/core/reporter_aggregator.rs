@@ -0,0 +1,401 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::{SystemTime, UNIX_EPOCH};
+use borsh::{BorshDeserialize, BorshSerialize};
+use arch_program::{
+    msg,
+    pubkey::Pubkey,
+};
+use crate::dependencies::babel_ownable::BabelOwnable;
+use crate::dependencies::protocol_error::ProtocolError;
+use crate::interfaces::aggregator_v3::{AggregatorV3Interface, RoundData};
+
+const BPS_DIVISOR: u128 = 10_000;
+// Finalised rounds kept for get_round_data
+const MAX_ROUND_HISTORY: usize = 1024;
+
+// Per-reporter performance counters
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct ReporterStats {
+    pub submissions: u64,
+    pub missed_rounds: u64,
+    pub outliers: u64,
+    pub last_reported_round: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+struct FinalisedRound {
+    answer: i128,
+    started_at: u64,
+    updated_at: u64,
+}
+
+#[derive(Debug, Default, Clone, BorshSerialize, BorshDeserialize)]
+struct OpenRound {
+    started_at: u64,
+    submissions: Vec<(Pubkey, i128)>,
+}
+
+// AggregatorV3Interface fed by whitelisted reporters. Each reporter submits one observation for
+// the open round; once `min_submissions` have arrived the round is finalised at their median.
+// Reporters may still report on the latest finalised round until the next round opens, so a
+// reporter is only counted as missing a round it never reported on while it accepted reports.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct ReporterAggregator {
+    babel_ownable: BabelOwnable,
+    token: String,
+    decimals: u8,
+    reporters: HashMap<Pubkey, ReporterStats>,
+    min_submissions: u8,
+    // Submissions further than this from the median are counted as outliers
+    outlier_deviation_bps: u32,
+    // An open round older than this is discarded and reporting restarts
+    round_timeout: u64,
+    open_round: OpenRound,
+    rounds: BTreeMap<u64, FinalisedRound>,
+    latest_round_id: u64,
+    // Reporters that reported on the latest finalised round; the others are counted as missing it
+    // when the next round opens
+    latest_round_reporters: Vec<Pubkey>,
+}
+
+impl ReporterAggregator {
+    pub fn new(
+        babel_ownable: BabelOwnable,
+        token: String,
+        decimals: u8,
+        min_submissions: u8,
+        outlier_deviation_bps: u32,
+        round_timeout: u64,
+    ) -> Result<Self, ProtocolError> {
+        if min_submissions == 0 || outlier_deviation_bps as u128 > BPS_DIVISOR {
+            return Err(ProtocolError::InvalidOracleConfig);
+        }
+        Ok(Self {
+            babel_ownable,
+            token,
+            decimals,
+            reporters: HashMap::new(),
+            min_submissions,
+            outlier_deviation_bps,
+            round_timeout,
+            open_round: OpenRound::default(),
+            rounds: BTreeMap::new(),
+            latest_round_id: 0,
+            latest_round_reporters: Vec::new(),
+        })
+    }
+
+    // Function to whitelist a reporter
+    pub fn add_reporter(&mut self, caller: &String, reporter: Pubkey) -> Result<(), ProtocolError> {
+        if !self.babel_ownable.only_owner(caller) {
+            return Err(ProtocolError::Unauthorized);
+        }
+        if self.reporters.contains_key(&reporter) {
+            return Err(ProtocolError::InvalidOracleConfig);
+        }
+        self.reporters.insert(reporter, ReporterStats::default());
+        msg!("ReporterAdded: token = {}, reporter = {:?}", self.token, reporter);
+        Ok(())
+    }
+
+    // Function to remove a reporter. Its submission to the open round, if any, is dropped.
+    pub fn remove_reporter(&mut self, caller: &String, reporter: Pubkey) -> Result<(), ProtocolError> {
+        if !self.babel_ownable.only_owner(caller) {
+            return Err(ProtocolError::Unauthorized);
+        }
+        if !self.reporters.contains_key(&reporter) {
+            return Err(ProtocolError::InvalidOracleConfig);
+        }
+        if self.reporters.len() <= self.min_submissions as usize {
+            msg!("ReporterAggregator: removal would leave fewer reporters than min submissions");
+            return Err(ProtocolError::InvalidOracleConfig);
+        }
+        self.reporters.remove(&reporter);
+        self.open_round.submissions.retain(|(submitter, _)| *submitter != reporter);
+        msg!("ReporterRemoved: token = {}, reporter = {:?}", self.token, reporter);
+        Ok(())
+    }
+
+    // Function to change the number of submissions that finalises a round
+    pub fn set_min_submissions(&mut self, caller: &String, min_submissions: u8) -> Result<(), ProtocolError> {
+        if !self.babel_ownable.only_owner(caller) {
+            return Err(ProtocolError::Unauthorized);
+        }
+        if min_submissions == 0 || min_submissions as usize > self.reporters.len() {
+            return Err(ProtocolError::InvalidOracleConfig);
+        }
+        self.min_submissions = min_submissions;
+        msg!("MinSubmissionsSet: token = {}, min_submissions = {}", self.token, min_submissions);
+        Ok(())
+    }
+
+    // Function for a reporter to submit its observation for the open round, or for the latest
+    // finalised round until the next one opens
+    pub fn submit(&mut self, reporter: Pubkey, round_id: u64, answer: i128) -> Result<(), ProtocolError> {
+        if !self.reporters.contains_key(&reporter) {
+            return Err(ProtocolError::Unauthorized);
+        }
+        if answer <= 0 {
+            return Err(ProtocolError::InvalidParameter);
+        }
+        if round_id != 0 && round_id == self.latest_round_id && self.open_round.submissions.is_empty() {
+            return self.submit_late(reporter, round_id, answer);
+        }
+        if round_id != self.latest_round_id + 1 {
+            msg!("ReporterAggregator: round {} is not open", round_id);
+            return Err(ProtocolError::RoundNotOpen);
+        }
+
+        let now = Self::current_time();
+        if !self.open_round.submissions.is_empty() && now > self.open_round.started_at + self.round_timeout {
+            // The round is reported again from scratch; reporters that stayed silent missed it
+            let timed_out = std::mem::take(&mut self.open_round);
+            msg!("RoundTimedOut: token = {}, round = {}, submissions = {}", self.token, round_id, timed_out.submissions.len());
+            let submitters: Vec<Pubkey> = timed_out.submissions.iter().map(|(submitter, _)| *submitter).collect();
+            self.count_missed(&submitters);
+        }
+        if self.open_round.submissions.iter().any(|(submitter, _)| *submitter == reporter) {
+            msg!("ReporterAggregator: reporter already submitted for round {}", round_id);
+            return Err(ProtocolError::AlreadySubmitted);
+        }
+        if self.open_round.submissions.is_empty() {
+            // Opening the round closes reporting on the previous one
+            let previous_reporters = std::mem::take(&mut self.latest_round_reporters);
+            if self.latest_round_id != 0 {
+                self.count_missed(&previous_reporters);
+            }
+            self.open_round.started_at = now;
+        }
+        self.open_round.submissions.push((reporter, answer));
+        self.record_submission(reporter, round_id);
+        msg!("SubmissionReceived: token = {}, round = {}, reporter = {:?}, answer = {}", self.token, round_id, reporter, answer);
+
+        if self.open_round.submissions.len() >= self.min_submissions as usize {
+            self.finalise_round(round_id, now);
+        }
+        Ok(())
+    }
+
+    // A report on the latest finalised round. It does not change the answer but is checked
+    // against it, and keeps the reporter from being counted as missing the round.
+    fn submit_late(&mut self, reporter: Pubkey, round_id: u64, answer: i128) -> Result<(), ProtocolError> {
+        if self.latest_round_reporters.contains(&reporter) {
+            msg!("ReporterAggregator: reporter already submitted for round {}", round_id);
+            return Err(ProtocolError::AlreadySubmitted);
+        }
+        let final_answer = self.rounds.get(&round_id).map(|round| round.answer).ok_or(ProtocolError::RoundNotOpen)?;
+        self.latest_round_reporters.push(reporter);
+        self.record_submission(reporter, round_id);
+        if self.is_outlier(answer, final_answer) {
+            self.count_outlier(reporter, round_id, answer);
+        }
+        msg!("LateSubmissionReceived: token = {}, round = {}, reporter = {:?}, answer = {}", self.token, round_id, reporter, answer);
+        Ok(())
+    }
+
+    pub fn latest_round_id(&self) -> u64 {
+        self.latest_round_id
+    }
+
+    pub fn reporter_stats(&self, reporter: &Pubkey) -> Option<ReporterStats> {
+        self.reporters.get(reporter).cloned()
+    }
+
+    pub fn reporters(&self) -> Vec<Pubkey> {
+        self.reporters.keys().cloned().collect()
+    }
+
+    // Closes the open round at the median and counts outliers. Reporters that have not reported
+    // yet can still do so until the next round opens.
+    fn finalise_round(&mut self, round_id: u64, now: u64) {
+        let open_round = std::mem::take(&mut self.open_round);
+        let answer = Self::median(open_round.submissions.iter().map(|(_, answer)| *answer).collect());
+
+        for (reporter, submitted) in open_round.submissions.iter() {
+            if self.is_outlier(*submitted, answer) {
+                self.count_outlier(*reporter, round_id, *submitted);
+            }
+        }
+        self.latest_round_reporters = open_round.submissions.iter().map(|(reporter, _)| *reporter).collect();
+
+        self.rounds.insert(round_id, FinalisedRound { answer, started_at: open_round.started_at, updated_at: now });
+        self.latest_round_id = round_id;
+        while self.rounds.len() > MAX_ROUND_HISTORY {
+            let oldest = *self.rounds.keys().next().unwrap();
+            self.rounds.remove(&oldest);
+        }
+
+        msg!("AnswerUpdated: token = {}, price = {}, round = {}, submissions = {}", self.token, answer, round_id, open_round.submissions.len());
+    }
+
+    fn record_submission(&mut self, reporter: Pubkey, round_id: u64) {
+        if let Some(stats) = self.reporters.get_mut(&reporter) {
+            stats.submissions += 1;
+            stats.last_reported_round = round_id;
+        }
+    }
+
+    fn is_outlier(&self, submitted: i128, answer: i128) -> bool {
+        let band = answer as u128 * self.outlier_deviation_bps as u128 / BPS_DIVISOR;
+        submitted.abs_diff(answer) > band
+    }
+
+    fn count_outlier(&mut self, reporter: Pubkey, round_id: u64, submitted: i128) {
+        if let Some(stats) = self.reporters.get_mut(&reporter) {
+            stats.outliers += 1;
+        }
+        msg!("OutlierReported: token = {}, round = {}, reporter = {:?}, answer = {}", self.token, round_id, reporter, submitted);
+    }
+
+    // Counts a missed round for every reporter not in `reported`
+    fn count_missed(&mut self, reported: &[Pubkey]) {
+        for (reporter, stats) in self.reporters.iter_mut() {
+            if !reported.contains(reporter) {
+                stats.missed_rounds += 1;
+            }
+        }
+    }
+
+    fn median(mut answers: Vec<i128>) -> i128 {
+        answers.sort();
+        let mid = answers.len() / 2;
+        if answers.len() % 2 == 0 {
+            (answers[mid - 1] + answers[mid]) / 2
+        } else {
+            answers[mid]
+        }
+    }
+
+    fn round_data(round_id: u64, round: &FinalisedRound) -> RoundData {
+        RoundData {
+            round_id: round_id as u128,
+            answer: round.answer,
+            started_at: round.started_at as u128,
+            updated_at: round.updated_at as u128,
+            answered_in_round: round_id as u128,
+        }
+    }
+
+    fn current_time() -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+    }
+}
+
+impl AggregatorV3Interface for ReporterAggregator {
+    fn decimals(&self) -> u8 {
+        self.decimals
+    }
+
+    fn description(&self) -> String {
+        format!("{} / USD (reporter median)", self.token)
+    }
+
+    fn version(&self) -> u128 {
+        1
+    }
+
+    // Unknown or out of range rounds return a zeroed RoundData, which consumers discard as no data
+    fn get_round_data(&self, round_id: u128) -> RoundData {
+        u64::try_from(round_id)
+            .ok()
+            .and_then(|round_id| self.rounds.get(&round_id).map(|round| Self::round_data(round_id, round)))
+            .unwrap_or_default()
+    }
+
+    fn latest_round_data(&self) -> RoundData {
+        self.rounds
+            .get(&self.latest_round_id)
+            .map(|round| Self::round_data(self.latest_round_id, round))
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aggregator(reporters: usize, min_submissions: u8, round_timeout: u64) -> (ReporterAggregator, Vec<Pubkey>) {
+        let owner = "owner".to_string();
+        let babel_ownable = BabelOwnable::new(owner.clone(), "guardian".to_string());
+        let mut aggregator = ReporterAggregator::new(babel_ownable, "BTC".to_string(), 8, min_submissions, 100, round_timeout).unwrap();
+        let reporters: Vec<Pubkey> = (0..reporters).map(|_| Pubkey::new_unique()).collect();
+        for reporter in reporters.iter() {
+            aggregator.add_reporter(&owner, *reporter).unwrap();
+        }
+        (aggregator, reporters)
+    }
+
+    fn stats(aggregator: &ReporterAggregator, reporter: &Pubkey) -> (u64, u64, u64) {
+        let stats = aggregator.reporter_stats(reporter).unwrap();
+        (stats.submissions, stats.missed_rounds, stats.outliers)
+    }
+
+    #[test]
+    fn test_round_finalises_at_the_median_of_min_submissions() {
+        let (mut aggregator, reporters) = aggregator(4, 3, 3600);
+        assert_eq!(aggregator.latest_round_data().updated_at, 0);
+        assert_eq!(aggregator.submit(reporters[0], 2, 100), Err(ProtocolError::RoundNotOpen));
+
+        aggregator.submit(reporters[0], 1, 100).unwrap();
+        assert_eq!(aggregator.submit(reporters[0], 1, 101), Err(ProtocolError::AlreadySubmitted));
+        aggregator.submit(reporters[1], 1, 300).unwrap();
+        assert_eq!(aggregator.latest_round_id(), 0);
+        aggregator.submit(reporters[2], 1, 102).unwrap();
+
+        assert_eq!(aggregator.latest_round_id(), 1);
+        let round = aggregator.latest_round_data();
+        assert_eq!((round.round_id, round.answer), (1, 102));
+        assert_eq!(aggregator.get_round_data(2).updated_at, 0);
+        assert_eq!(aggregator.get_round_data(u128::MAX).updated_at, 0);
+    }
+
+    #[test]
+    fn test_outliers_and_late_reporters() {
+        let (mut aggregator, reporters) = aggregator(4, 3, 3600);
+        aggregator.submit(reporters[0], 1, 100).unwrap();
+        aggregator.submit(reporters[1], 1, 300).unwrap();
+        aggregator.submit(reporters[2], 1, 101).unwrap();
+
+        // 300 is outside the 1% band around the median of 101
+        assert_eq!(stats(&aggregator, &reporters[1]), (1, 0, 1));
+        assert_eq!(stats(&aggregator, &reporters[0]), (1, 0, 0));
+
+        // The slower reporter still reports on the finalised round without changing its answer
+        aggregator.submit(reporters[3], 1, 150).unwrap();
+        assert_eq!(aggregator.submit(reporters[3], 1, 150), Err(ProtocolError::AlreadySubmitted));
+        assert_eq!(aggregator.latest_round_data().answer, 101);
+        assert_eq!(stats(&aggregator, &reporters[3]), (1, 0, 1));
+
+        // Opening round 2 closes round 1; nobody missed it
+        aggregator.submit(reporters[0], 2, 100).unwrap();
+        assert_eq!(aggregator.submit(reporters[1], 1, 100), Err(ProtocolError::RoundNotOpen));
+        for reporter in reporters.iter() {
+            assert_eq!(aggregator.reporter_stats(reporter).unwrap().missed_rounds, 0);
+        }
+
+        // Only reporter 3 stays silent on round 2
+        aggregator.submit(reporters[1], 2, 100).unwrap();
+        aggregator.submit(reporters[2], 2, 100).unwrap();
+        aggregator.submit(reporters[0], 3, 100).unwrap();
+        assert_eq!(stats(&aggregator, &reporters[3]), (1, 1, 1));
+        assert_eq!(stats(&aggregator, &reporters[0]), (3, 0, 0));
+    }
+
+    #[test]
+    fn test_timed_out_round_counts_silent_reporters_as_missed() {
+        let (mut aggregator, reporters) = aggregator(3, 2, 60);
+        aggregator.submit(reporters[0], 1, 100).unwrap();
+        aggregator.open_round.started_at -= 61;
+
+        // Reporter 0's submission is dropped with the round; the two silent reporters missed it
+        aggregator.submit(reporters[1], 1, 100).unwrap();
+        assert_eq!(aggregator.latest_round_id(), 0);
+        assert_eq!(stats(&aggregator, &reporters[0]), (1, 0, 0));
+        assert_eq!(stats(&aggregator, &reporters[1]), (1, 1, 0));
+        assert_eq!(stats(&aggregator, &reporters[2]), (0, 1, 0));
+
+        aggregator.submit(reporters[0], 1, 102).unwrap();
+        assert_eq!(aggregator.latest_round_id(), 1);
+        assert_eq!(aggregator.latest_round_data().answer, 101);
+    }
+}
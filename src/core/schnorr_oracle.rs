@@ -0,0 +1,196 @@
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use borsh::{BorshDeserialize, BorshSerialize};
+use arch_program::{
+    msg,
+};
+use crate::dependencies::babel_ownable::BabelOwnable;
+use crate::dependencies::protocol_error::ProtocolError;
+use crate::dependencies::signed_permit::SignedPermit;
+use crate::interfaces::aggregator_v3::{AggregatorV3Interface, RoundData};
+
+const ATTESTATION_TAG: &str = "Babel/PriceAttestation";
+// Accepted rounds kept for get_round_data
+const MAX_ROUND_HISTORY: usize = 1024;
+// Tolerated clock difference between oracle signers and the program
+const MAX_FUTURE_DRIFT: u64 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+struct AttestedRound {
+    answer: i128,
+    timestamp: u64,
+    accepted_at: u64,
+}
+
+// AggregatorV3Interface fed by price messages signed by a k-of-n set of BIP-340 oracle keys.
+// Each message covers (token, price, timestamp, round id).
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct SchnorrOracle {
+    babel_ownable: BabelOwnable,
+    token: String,
+    decimals: u8,
+    oracle_keys: Vec<[u8; 32]>, // x-only public keys
+    threshold: u8,
+    max_age: u64, // Attestations older than this are rejected
+    rounds: BTreeMap<u64, AttestedRound>,
+    latest_round_id: u64,
+}
+
+impl SchnorrOracle {
+    pub fn new(
+        babel_ownable: BabelOwnable,
+        token: String,
+        decimals: u8,
+        oracle_keys: Vec<[u8; 32]>,
+        threshold: u8,
+        max_age: u64,
+    ) -> Result<Self, ProtocolError> {
+        Self::validate_oracle_set(&oracle_keys, threshold)?;
+        Ok(Self {
+            babel_ownable,
+            token,
+            decimals,
+            oracle_keys,
+            threshold,
+            max_age,
+            rounds: BTreeMap::new(),
+            latest_round_id: 0,
+        })
+    }
+
+    // Function to replace the signer set and threshold
+    pub fn set_oracle_keys(&mut self, caller: &String, oracle_keys: Vec<[u8; 32]>, threshold: u8) -> Result<(), ProtocolError> {
+        if !self.babel_ownable.only_owner(caller) {
+            return Err(ProtocolError::Unauthorized);
+        }
+        Self::validate_oracle_set(&oracle_keys, threshold)?;
+        msg!("OracleKeysSet: signers = {}, threshold = {}", oracle_keys.len(), threshold);
+        self.oracle_keys = oracle_keys;
+        self.threshold = threshold;
+        Ok(())
+    }
+
+    // Message each oracle signs for a round
+    pub fn attestation_digest(token: &str, price: i128, timestamp: u64, round_id: u64) -> [u8; 32] {
+        let mut data = Vec::with_capacity(token.len() + 36);
+        data.extend_from_slice(&(token.len() as u32).to_le_bytes());
+        data.extend_from_slice(token.as_bytes());
+        data.extend_from_slice(&price.to_le_bytes());
+        data.extend_from_slice(&timestamp.to_le_bytes());
+        data.extend_from_slice(&round_id.to_le_bytes());
+        SignedPermit::tagged_hash(ATTESTATION_TAG, &data)
+    }
+
+    // Function to accept a new round. `signatures` pairs each signer's x-only key with its signature;
+    // at least `threshold` distinct configured signers must have signed the same message.
+    pub fn submit(
+        &mut self,
+        price: i128,
+        timestamp: u64,
+        round_id: u64,
+        signatures: Vec<([u8; 32], [u8; 64])>,
+    ) -> Result<(), ProtocolError> {
+        if price <= 0 {
+            return Err(ProtocolError::InvalidParameter);
+        }
+        if round_id <= self.latest_round_id {
+            msg!("SchnorrOracle: round id must increase");
+            return Err(ProtocolError::RoundNotOpen);
+        }
+        let now = Self::current_time();
+        if timestamp > now + MAX_FUTURE_DRIFT || now.saturating_sub(timestamp) > self.max_age {
+            msg!("SchnorrOracle: stale attestation");
+            return Err(ProtocolError::StalePrice);
+        }
+        if let Some(latest) = self.rounds.get(&self.latest_round_id) {
+            if timestamp < latest.timestamp {
+                msg!("SchnorrOracle: attestation older than latest round");
+                return Err(ProtocolError::StalePrice);
+            }
+        }
+
+        let digest = Self::attestation_digest(&self.token, price, timestamp, round_id);
+        let mut signers: Vec<[u8; 32]> = Vec::new();
+        for (key, signature) in signatures.iter() {
+            if signers.contains(key) || !self.oracle_keys.contains(key) {
+                continue;
+            }
+            if SignedPermit::verify(key, &digest, signature) {
+                signers.push(*key);
+            }
+        }
+        if signers.len() < self.threshold as usize {
+            msg!("SchnorrOracle: {} of {} required signatures", signers.len(), self.threshold);
+            return Err(ProtocolError::InsufficientSignatures);
+        }
+
+        self.rounds.insert(round_id, AttestedRound { answer: price, timestamp, accepted_at: now });
+        self.latest_round_id = round_id;
+        while self.rounds.len() > MAX_ROUND_HISTORY {
+            let oldest = *self.rounds.keys().next().unwrap();
+            self.rounds.remove(&oldest);
+        }
+
+        msg!("AnswerUpdated: token = {}, price = {}, round = {}, timestamp = {}", self.token, price, round_id, timestamp);
+        Ok(())
+    }
+
+    pub fn latest_round_id(&self) -> u64 {
+        self.latest_round_id
+    }
+
+    fn validate_oracle_set(oracle_keys: &[[u8; 32]], threshold: u8) -> Result<(), ProtocolError> {
+        if threshold == 0 || threshold as usize > oracle_keys.len() {
+            return Err(ProtocolError::InvalidOracleConfig);
+        }
+        for (i, key) in oracle_keys.iter().enumerate() {
+            if oracle_keys[..i].contains(key) {
+                return Err(ProtocolError::InvalidOracleConfig); // Duplicate signer
+            }
+        }
+        Ok(())
+    }
+
+    fn round_data(round_id: u64, round: &AttestedRound) -> RoundData {
+        RoundData {
+            round_id: round_id as u128,
+            answer: round.answer,
+            started_at: round.timestamp as u128,
+            updated_at: round.timestamp as u128,
+            answered_in_round: round_id as u128,
+        }
+    }
+
+    fn current_time() -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+    }
+}
+
+impl AggregatorV3Interface for SchnorrOracle {
+    fn decimals(&self) -> u8 {
+        self.decimals
+    }
+
+    fn description(&self) -> String {
+        format!("{} / USD (Schnorr attested)", self.token)
+    }
+
+    fn version(&self) -> u128 {
+        1
+    }
+
+    // Unknown or out of range rounds return a zeroed RoundData, which consumers discard as no data
+    fn get_round_data(&self, round_id: u128) -> RoundData {
+        u64::try_from(round_id)
+            .ok()
+            .and_then(|round_id| self.rounds.get(&round_id).map(|round| Self::round_data(round_id, round)))
+            .unwrap_or_default()
+    }
+
+    fn latest_round_data(&self) -> RoundData {
+        self.rounds
+            .get(&self.latest_round_id)
+            .map(|round| Self::round_data(self.latest_round_id, round))
+            .unwrap_or_default()
+    }
+}
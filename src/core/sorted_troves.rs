@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 use borsh::{BorshDeserialize, BorshSerialize};
-use crate::interfaces::trove_manager::TroveManager;
+use crate::dependencies::protocol_error::ProtocolError;
 use bitcoin::{self, Transaction};
 use arch_program::{
     account::AccountInfo,
@@ -38,13 +38,14 @@ pub struct Node {
     nicr: u256,
 }
 
+// Each trove manager owns its list as a private field and is the only code holding it mutably,
+// so ownership rather than a caller check restricts who can modify it
 #[derive(BorshSerialize, BorshDeserialize)]
 pub struct SortedTroves {
     head: Option<u32>,
     tail: Option<u32>,
     size: u32,
     nodes: HashMap<u32, Node>,
-    trove_manager: Option<TroveManager>,
     transactions: Vec<Transaction>,
     utxos: HashMap<OutPoint, UtxoMeta>,
 }
@@ -56,24 +57,22 @@ impl SortedTroves {
             tail: None,
             size: 0,
             nodes: HashMap::new(),
-            trove_manager: None,
             transactions: Vec::new(),
             utxos: HashMap::new(),
         }
     }
 
-    pub fn set_addresses(&mut self, trove_manager: TroveManager) {
-        assert!(self.trove_manager.is_none(), "Already set");
-        self.trove_manager = Some(trove_manager);
-    }
-
-    fn require_caller_is_trove_manager(&self) {
-        let caller: Pubkey = get_caller_address();
-        assert!(self.trove_manager.as_ref().map_or(false, |tm| tm.address == caller), "Caller is not the TroveManager");
-    }
+    // Inserts a node, using `prev_id` / `next_id` as hints for its position. Stale or wrong hints
+    // are corrected by find_insert_position, so the list always stays ordered by descending NICR.
+    pub fn insert(&mut self, id: u32, nicr: u256, prev_id: Option<u32>, next_id: Option<u32>) -> Result<(), ProtocolError> {
+        if self.contains(id) {
+            return Err(ProtocolError::TroveAlreadyListed);
+        }
+        if nicr == 0u128.into() {
+            return Err(ProtocolError::InvalidNicr);
+        }
 
-    pub fn insert(&mut self, id: u32, nicr: u256, prev_id: Option<u32>, next_id: Option<u32>) {
-        self.require_caller_is_trove_manager();
+        let (prev_id, next_id) = self.find_insert_position(nicr, prev_id, next_id);
 
         let node = Node {
             exists: true,
@@ -82,20 +81,24 @@ impl SortedTroves {
             nicr,
         };
 
-        if let Some(prev_id) = prev_id {
-            if let Some(prev_node) = self.nodes.get_mut(&prev_id) {
-                prev_node.next_id = Some(id);
+        match prev_id {
+            Some(prev_id) => {
+                if let Some(prev_node) = self.nodes.get_mut(&prev_id) {
+                    prev_node.next_id = Some(id);
+                }
             }
-        } else {
-            self.head = Some(id);
+            // Insert as head
+            None => self.head = Some(id),
         }
 
-        if let Some(next_id) = next_id {
-            if let Some(next_node) = self.nodes.get_mut(&next_id) {
-                next_node.prev_id = Some(id);
+        match next_id {
+            Some(next_id) => {
+                if let Some(next_node) = self.nodes.get_mut(&next_id) {
+                    next_node.prev_id = Some(id);
+                }
             }
-        } else {
-            self.tail = Some(id);
+            // Insert as tail
+            None => self.tail = Some(id),
         }
 
         self.nodes.insert(id, node);
@@ -115,11 +118,10 @@ impl SortedTroves {
         msg!("Transaction to sign: {:?}", tx_to_sign);
 
         set_transaction_to_sign(&self.nodes, tx_to_sign);
+        Ok(())
     }
 
-    pub fn remove(&mut self, id: u32) {
-        self.require_caller_is_trove_manager();
-
+    pub fn remove(&mut self, id: u32) -> Result<(), ProtocolError> {
         if let Some(node) = self.nodes.remove(&id) {
             if let Some(prev_id) = node.prev_id {
                 if let Some(prev_node) = self.nodes.get_mut(&prev_id) {
@@ -154,37 +156,148 @@ impl SortedTroves {
 
             set_transaction_to_sign(&self.nodes, tx_to_sign);
         }
+        Ok(())
     }
 
-    pub fn re_insert(&mut self, id: u32, new_nicr: u256, new_prev_id: Option<u32>, new_next_id: Option<u32>) {
-        self.remove(id);
-        self.insert(id, new_nicr, new_prev_id, new_next_id);
+    pub fn re_insert(&mut self, id: u32, new_nicr: u256, new_prev_id: Option<u32>, new_next_id: Option<u32>) -> Result<(), ProtocolError> {
+        if !self.contains(id) {
+            return Err(ProtocolError::TroveNotListed);
+        }
+        if new_nicr == 0u128.into() {
+            return Err(ProtocolError::InvalidNicr);
+        }
+        // The hints may point at the node being moved, which is no longer in the list once removed
+        let new_prev_id = new_prev_id.filter(|prev| *prev != id);
+        let new_next_id = new_next_id.filter(|next| *next != id);
+        self.remove(id)?;
+        self.insert(id, new_nicr, new_prev_id, new_next_id)
     }
 
-    fn valid_insert_position(&self, nicr: u256, prev_id: Option<u32>, next_id: Option<u32>) -> bool {
-        if let Some(prev_id) = prev_id {
-            if let Some(prev_node) = self.nodes.get(&prev_id) {
-                if prev_node.nicr > nicr {
-                    return false;
-                }
-            } else {
-                return false;
+    pub fn contains(&self, id: u32) -> bool {
+        self.nodes.get(&id).map_or(false, |node| node.exists)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub fn get_size(&self) -> u32 {
+        self.size
+    }
+
+    // Returns the first node in the list (the trove with the largest NICR)
+    pub fn get_first(&self) -> Option<u32> {
+        self.head
+    }
+
+    // Returns the last node in the list (the trove with the smallest NICR)
+    pub fn get_last(&self) -> Option<u32> {
+        self.tail
+    }
+
+    // Returns the next node (with a smaller NICR) in the list for a given node
+    pub fn get_next(&self, id: u32) -> Option<u32> {
+        self.nodes.get(&id).and_then(|node| node.next_id)
+    }
+
+    // Returns the previous node (with a larger NICR) in the list for a given node
+    pub fn get_prev(&self, id: u32) -> Option<u32> {
+        self.nodes.get(&id).and_then(|node| node.prev_id)
+    }
+
+    // Checks that (prev_id, next_id) is a valid insert position for a node with the given NICR:
+    // prev_id must have a larger or equal NICR, next_id a smaller or equal one, and they must be adjacent
+    pub fn valid_insert_position(&self, nicr: u256, prev_id: Option<u32>, next_id: Option<u32>) -> bool {
+        match (prev_id, next_id) {
+            // `(None, None)` is a valid insert position only if the list is empty
+            (None, None) => self.is_empty(),
+            // `(None, next)` is valid if `next` is the head of the list
+            (None, Some(next)) => self.head == Some(next) && self.nicr_of(next).map_or(false, |n| nicr >= n),
+            // `(prev, None)` is valid if `prev` is the tail of the list
+            (Some(prev), None) => self.tail == Some(prev) && self.nicr_of(prev).map_or(false, |p| nicr <= p),
+            // `(prev, next)` is valid if they are adjacent nodes and NICR falls between them
+            (Some(prev), Some(next)) => {
+                self.get_next(prev) == Some(next)
+                    && self.nicr_of(prev).map_or(false, |p| p >= nicr)
+                    && self.nicr_of(next).map_or(false, |n| nicr >= n)
             }
         }
+    }
 
-        if let Some(next_id) = next_id {
-            if let Some(next_node) = self.nodes.get(&next_id) {
-                if next_node.nicr < nicr {
-                    return false;
-                }
-            } else {
-                return false;
+    // Finds the insert position for a node with the given NICR, using `prev_id` / `next_id` as hints.
+    // Hints that no longer exist or are on the wrong side of `nicr` are dropped, and the list is walked
+    // from the remaining hint, or from the head if none is usable.
+    pub fn find_insert_position(&self, nicr: u256, prev_id: Option<u32>, next_id: Option<u32>) -> (Option<u32>, Option<u32>) {
+        // `prev_id` does not exist anymore or now has a smaller NICR than the given NICR
+        let prev_id = prev_id.filter(|prev| self.contains(*prev) && self.nicr_of(*prev).map_or(false, |p| nicr <= p));
+        // `next_id` does not exist anymore or now has a larger NICR than the given NICR
+        let next_id = next_id.filter(|next| self.contains(*next) && self.nicr_of(*next).map_or(false, |n| nicr >= n));
+
+        if self.valid_insert_position(nicr, prev_id, next_id) {
+            return (prev_id, next_id);
+        }
+
+        match (prev_id, next_id) {
+            // No hint - descend list starting from head
+            (None, None) => self.descend_list(nicr, self.head),
+            // No `prev_id` for hint - ascend list starting from `next_id`
+            (None, Some(next)) => self.ascend_list(nicr, Some(next)),
+            // No `next_id` for hint - descend list starting from `prev_id`, same when both hints are usable
+            (Some(prev), _) => self.descend_list(nicr, Some(prev)),
+        }
+    }
+
+    // Descends the list (larger NICRs to smaller NICRs) to find a valid insert position
+    fn descend_list(&self, nicr: u256, start_id: Option<u32>) -> (Option<u32>, Option<u32>) {
+        let start = match start_id {
+            Some(start) => start,
+            None => return (None, None),
+        };
+        // If `start_id` is the head, check if the insert position is before the head
+        if self.head == Some(start) && self.nicr_of(start).map_or(false, |s| nicr >= s) {
+            return (None, Some(start));
+        }
+
+        let mut prev_id = Some(start);
+        let mut next_id = self.get_next(start);
+        // Descend the list until we reach the end or until we find a valid insert position
+        while let Some(prev) = prev_id {
+            if self.valid_insert_position(nicr, Some(prev), next_id) {
+                break;
             }
+            prev_id = next_id;
+            next_id = prev_id.and_then(|p| self.get_next(p));
+        }
+        (prev_id, next_id)
+    }
+
+    // Ascends the list (smaller NICRs to larger NICRs) to find a valid insert position
+    fn ascend_list(&self, nicr: u256, start_id: Option<u32>) -> (Option<u32>, Option<u32>) {
+        let start = match start_id {
+            Some(start) => start,
+            None => return (None, None),
+        };
+        // If `start_id` is the tail, check if the insert position is after the tail
+        if self.tail == Some(start) && self.nicr_of(start).map_or(false, |s| nicr <= s) {
+            return (Some(start), None);
         }
 
-        true
+        let mut next_id = Some(start);
+        let mut prev_id = self.get_prev(start);
+        // Ascend the list until we reach the end or until we find a valid insert position
+        while let Some(next) = next_id {
+            if self.valid_insert_position(nicr, prev_id, Some(next)) {
+                break;
+            }
+            next_id = prev_id;
+            prev_id = next_id.and_then(|n| self.get_prev(n));
+        }
+        (prev_id, next_id)
     }
 
+    fn nicr_of(&self, id: u32) -> Option<u256> {
+        self.nodes.get(&id).map(|node| node.nicr.clone())
+    }
 
     pub fn serialize(&self) -> Vec<u8> {
         self.try_to_vec().expect("Serialization failed")
@@ -221,4 +334,98 @@ impl SortedTroves {
     pub fn spend_utxo(&mut self, outpoint: OutPoint) {
         self.utxos.remove(&outpoint);
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Builds a list of ids 1..=5 with NICRs 500, 400, 300, 200, 100
+    fn sorted_troves() -> SortedTroves {
+        let mut list = SortedTroves::new();
+        for id in 1..=5u32 {
+            list.insert(id, nicr(600 - 100 * id as u128), None, None).unwrap();
+        }
+        list
+    }
+
+    fn nicr(value: u128) -> u256 {
+        value.into()
+    }
+
+    fn ids(list: &SortedTroves) -> Vec<u32> {
+        let mut ids = Vec::new();
+        let mut current = list.get_first();
+        while let Some(id) = current {
+            ids.push(id);
+            current = list.get_next(id);
+        }
+        ids
+    }
+
+    #[test]
+    fn test_valid_hints_are_used_as_is() {
+        let list = sorted_troves();
+        assert_eq!(list.find_insert_position(nicr(350), Some(2), Some(3)), (Some(2), Some(3)));
+        assert_eq!(list.find_insert_position(nicr(600), None, Some(1)), (None, Some(1)));
+        assert_eq!(list.find_insert_position(nicr(50), Some(5), None), (Some(5), None));
+    }
+
+    #[test]
+    fn test_hints_on_the_wrong_side_are_dropped() {
+        let list = sorted_troves();
+        // prev_id now has a smaller NICR, next_id a larger one: both are ignored and the list is descended from the head
+        assert_eq!(list.find_insert_position(nicr(350), Some(4), None), (Some(2), Some(3)));
+        assert_eq!(list.find_insert_position(nicr(350), None, Some(1)), (Some(2), Some(3)));
+        assert_eq!(list.find_insert_position(nicr(350), Some(5), Some(1)), (Some(2), Some(3)));
+        assert_eq!(list.find_insert_position(nicr(600), Some(5), Some(1)), (None, Some(1)));
+    }
+
+    #[test]
+    fn test_removed_hints_are_dropped() {
+        let mut list = sorted_troves();
+        list.remove(3).unwrap();
+        // next_id was removed: descend from prev_id
+        assert_eq!(list.find_insert_position(nicr(350), Some(2), Some(3)), (Some(2), Some(4)));
+        // prev_id was removed: ascend from next_id
+        assert_eq!(list.find_insert_position(nicr(350), Some(3), Some(5)), (Some(2), Some(4)));
+        // Both were removed or unknown: descend from the head
+        assert_eq!(list.find_insert_position(nicr(150), Some(3), Some(42)), (Some(4), Some(5)));
+    }
+
+    #[test]
+    fn test_descend_and_ascend_list() {
+        let list = sorted_troves();
+        assert_eq!(list.descend_list(nicr(250), Some(1)), (Some(3), Some(4)));
+        assert_eq!(list.descend_list(nicr(700), Some(1)), (None, Some(1)));
+        assert_eq!(list.descend_list(nicr(50), Some(2)), (Some(5), None));
+        assert_eq!(list.ascend_list(nicr(250), Some(5)), (Some(3), Some(4)));
+        assert_eq!(list.ascend_list(nicr(50), Some(5)), (Some(5), None));
+        assert_eq!(list.ascend_list(nicr(700), Some(4)), (None, Some(1)));
+        // Equal NICRs are placed before the existing node when descending and after it when ascending
+        assert_eq!(list.descend_list(nicr(300), Some(1)), (Some(2), Some(3)));
+        assert_eq!(list.ascend_list(nicr(300), Some(5)), (Some(3), Some(4)));
+    }
+
+    #[test]
+    fn test_insert_and_re_insert_with_stale_hints_keep_the_list_ordered() {
+        let mut list = sorted_troves();
+        list.insert(6, nicr(350), Some(4), Some(1)).unwrap();
+        assert_eq!(ids(&list), vec![1, 2, 6, 3, 4, 5]);
+
+        // Hints may point at the node being moved
+        list.re_insert(6, nicr(150), Some(6), Some(6)).unwrap();
+        assert_eq!(ids(&list), vec![1, 2, 3, 4, 6, 5]);
+        list.re_insert(1, nicr(10), Some(2), None).unwrap();
+        assert_eq!(ids(&list), vec![2, 3, 4, 6, 5, 1]);
+        assert_eq!(list.get_last(), Some(1));
+        assert_eq!(list.get_size(), 6);
+    }
+
+    #[test]
+    fn test_duplicate_and_unknown_nodes_are_rejected() {
+        let mut list = sorted_troves();
+        assert_eq!(list.insert(1, nicr(350), None, None), Err(ProtocolError::TroveAlreadyListed));
+        assert_eq!(list.insert(6, nicr(0), None, None), Err(ProtocolError::InvalidNicr));
+        assert_eq!(list.re_insert(6, nicr(10), None, None), Err(ProtocolError::TroveNotListed));
+    }
+}
@@ -2,6 +2,7 @@ use std::collections::HashMap;
 use std::time::{SystemTime, UNIX_EPOCH};
 use borsh::{BorshDeserialize, BorshSerialize};
 use bitcoin::{self, Transaction, TxIn, TxOut}; // Import the bitcoin crate and Transaction struct
+use bitcoin::hashes::Hash;
 use archnetwork::{
     transaction_to_sign::TransactionToSign, // Import the TransactionToSign struct
     program::{
@@ -11,13 +12,41 @@ use archnetwork::{
     helper::get_state_transition_tx, // Import get_state_transition_tx
     input_to_sign::InputToSign, // Import InputToSign
 };
+use crate::core::sorted_troves::SortedTroves;
+use crate::core::coll_surplus_pool::CollSurplusPool;
+use crate::core::Debt_token::DebtToken;
+use crate::core::factory::DeploymentParams;
+use crate::core::price_feed::{PriceFeed, PriceSource};
+use crate::dependencies::babel_base::BabelBase;
+use crate::dependencies::babel_math::BabelMath;
+use crate::dependencies::protocol_event::ProtocolEvent;
+use crate::dependencies::protocol_error::ProtocolError;
 use arch_program::{
+    account::AccountInfo,
     pubkey::Pubkey, // Import Pubkey from Arch SDK
-    program_error::ProgramError,
     msg, // Import msg for logging
     utxo::UtxoMeta, // Import UtxoMeta
 };
 
+const DECIMAL_PRECISION: Balance = 1_000_000_000_000_000_000; // 1e18
+const MCR: Balance = 1_100_000_000_000_000_000; // 110%
+const SECONDS_IN_ONE_MINUTE: u64 = 60;
+// Half-life of 12h. 12h = 720 min, (1/2) = d^720 => d = (1/2)^(1/720)
+const MINUTE_DECAY_FACTOR: Balance = 999_037_758_833_783_000;
+const REDEMPTION_FEE_FLOOR: Balance = DECIMAL_PRECISION / 1000 * 5; // 0.5%
+const MAX_REDEMPTION_FEE: Balance = DECIMAL_PRECISION; // 100%
+const SECONDS_IN_YEAR: Balance = 365 * 24 * 60 * 60;
+const MAX_INTEREST_RATE_IN_BPS: Balance = 10_000; // 100%
+// Interest charged on a sunsetting collateral to push borrowers to close their troves
+const SUNSETTING_INTEREST_RATE_IN_BPS: Balance = 5_000; // 50%
+const BORROWING_FEE_FLOOR: Balance = DECIMAL_PRECISION / 1000 * 5; // 0.5%
+const MAX_BORROWING_FEE: Balance = DECIMAL_PRECISION / 100 * 5; // 5%
+// Bounds for the minute decay factor: half-life between 30 minutes and 1 week
+const MIN_MINUTE_DECAY_FACTOR: Balance = 977_159_968_434_245_000;
+const MAX_MINUTE_DECAY_FACTOR: Balance = 999_931_237_762_985_000;
+// Divisor applied to the redeemed fraction of the supply when raising the base rate
+const BETA: Balance = 2;
+
 pub struct TroveManager {
     troves: HashMap<Pubkey, Trove>, // Change AccountId to Pubkey
     total_stakes: Balance,
@@ -32,10 +61,50 @@ pub struct TroveManager {
     period_finish: u64,
     reward_integral_for: HashMap<Pubkey, Balance>, // Change AccountId to Pubkey
     stored_pending_reward: HashMap<Pubkey, Balance>, // Change AccountId to Pubkey
-    surplus_balances: HashMap<Pubkey, Balance>, // Change AccountId to Pubkey
+    coll_surplus_pool: CollSurplusPool, // Collateral claimable by owners of closed troves
+    coll_gas_compensation: CollSurplusPool, // Collateral gas compensation claimable by liquidators
     paused: bool,
     sunsetting: bool,
     utxos: HashMap<Pubkey, Vec<UtxoMeta>>, // Add UTXOs management
+    trove_owners: Vec<Pubkey>, // Active trove owners, indexed by Trove::array_index
+    // Redistribution accumulators: collateral / debt received per unit of stake. L_debt is divided
+    // by the interest index at redistribution, so pending debt accrues interest like active debt.
+    l_collateral: Balance,
+    l_debt: Balance,
+    last_collateral_error_redistribution: Balance,
+    last_debt_error_redistribution: Balance,
+    reward_snapshots: HashMap<Pubkey, RewardSnapshot>,
+    // Redistributed amounts not yet applied to individual troves
+    defaulted_collateral: Balance,
+    defaulted_debt: Balance,
+    // Snapshots taken after the latest liquidation, used to compute new stakes
+    total_stakes_snapshot: Balance,
+    total_collateral_snapshot: Balance,
+    sorted_troves: SortedTroves,
+    // Stable ids used to key troves in SortedTroves
+    sorted_trove_ids: HashMap<Pubkey, u32>,
+    sorted_trove_owners: HashMap<u32, Pubkey>,
+    next_sorted_trove_id: u32,
+    mcr: Balance,
+    // Per-collateral fee parameters
+    minute_decay_factor: Balance,
+    redemption_fee_floor: Balance,
+    max_redemption_fee: Balance,
+    borrowing_fee_floor: Balance,
+    max_borrowing_fee: Balance,
+    // Interest: per-second rate and global index, both with DECIMAL_PRECISION
+    interest_rate: Balance,
+    active_interest_index: Balance,
+    last_active_index_update: u64,
+    interest_payable: Balance,
+    // Cap on the total debt that can be borrowed against this collateral, the only per-collateral
+    // ceiling. Zero until the factory applies the deployment parameters.
+    max_system_debt: Balance,
+    collateral_token: String,
+    price_feed: String,
+    // Price used for ICR and liquidation checks
+    price_source: PriceSource,
+    twap_window_secs: u32,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
@@ -57,6 +126,42 @@ pub enum Status {
     ClosedByRedemption,
 }
 
+// Hints produced by MultiCollateralHintHelpers::get_redemption_hints
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct RedemptionHints {
+    pub first_redemption_hint: Option<Pubkey>,
+    pub upper_partial_redemption_hint: Option<u32>,
+    pub lower_partial_redemption_hint: Option<u32>,
+    pub partial_redemption_hint_nicr: Balance,
+}
+
+// Result of a redemption. `total_debt_to_redeem` has been burned from the redeemer and
+// `gas_compensation_to_burn`, the liquidation reserve of the troves the redemption closed, from
+// the gas pool. The caller sends `collateral_fee` to the fee receiver and
+// `collateral_to_send_to_redeemer` to the redeemer.
+#[derive(Debug, Clone, Default, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct RedemptionTotals {
+    pub total_debt_to_redeem: Balance,
+    pub total_collateral_drawn: Balance,
+    pub collateral_fee: Balance,
+    pub collateral_to_send_to_redeemer: Balance,
+    pub gas_compensation_to_burn: Balance,
+}
+
+struct SingleRedemptionValues {
+    debt_lot: Balance,
+    collateral_lot: Balance,
+    cancelled_partial: bool,
+    closed: bool,
+}
+
+// Values of L_collateral and L_debt at the time a trove was last touched
+#[derive(Debug, Clone, Default, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct RewardSnapshot {
+    collateral: Balance,
+    debt: Balance,
+}
+
 impl TroveManager {
     pub fn new(owner: Pubkey) -> Self { // Change AccountId to Pubkey
         Self {
@@ -73,25 +178,55 @@ impl TroveManager {
             period_finish: 0,
             reward_integral_for: HashMap::new(),
             stored_pending_reward: HashMap::new(),
-            surplus_balances: HashMap::new(),
+            coll_surplus_pool: CollSurplusPool::new(String::new()),
+            coll_gas_compensation: CollSurplusPool::new(String::new()),
             paused: false,
             sunsetting: false,
             utxos: HashMap::new(), // Initialize UTXOs
+            trove_owners: Vec::new(),
+            l_collateral: 0,
+            l_debt: 0,
+            last_collateral_error_redistribution: 0,
+            last_debt_error_redistribution: 0,
+            reward_snapshots: HashMap::new(),
+            defaulted_collateral: 0,
+            defaulted_debt: 0,
+            total_stakes_snapshot: 0,
+            total_collateral_snapshot: 0,
+            sorted_troves: SortedTroves::new(),
+            sorted_trove_ids: HashMap::new(),
+            sorted_trove_owners: HashMap::new(),
+            next_sorted_trove_id: 0,
+            mcr: MCR,
+            minute_decay_factor: MINUTE_DECAY_FACTOR,
+            redemption_fee_floor: REDEMPTION_FEE_FLOOR,
+            max_redemption_fee: MAX_REDEMPTION_FEE,
+            borrowing_fee_floor: BORROWING_FEE_FLOOR,
+            max_borrowing_fee: MAX_BORROWING_FEE,
+            interest_rate: 0,
+            active_interest_index: DECIMAL_PRECISION,
+            last_active_index_update: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+            interest_payable: 0,
+            max_system_debt: 0,
+            collateral_token: String::new(),
+            price_feed: String::new(),
+            price_source: PriceSource::Spot,
+            twap_window_secs: 0,
         }
     }
 
-    pub fn set_paused(&mut self, paused: bool) -> Result<(), ProgramError> {
+    pub fn set_paused(&mut self, paused: bool) -> Result<(), ProtocolError> {
         let caller = self.get_caller();
         if self.owner == caller {
             self.paused = paused;
             Ok(())
         } else {
             msg!("Unauthorized: caller is not the owner");
-            Err(ProgramError::Custom(1)) // Custom error code for unauthorized access
+            Err(ProtocolError::Unauthorized)
         }
     }
 
-    pub fn adjust_base_rate(&mut self, adjustment: Balance) -> Result<(), String> {
+    pub fn adjust_base_rate(&mut self, adjustment: Balance) -> Result<(), ProtocolError> {
         let caller = self.get_caller();
         if self.owner == caller {
             self.base_rate = self.base_rate.saturating_add(adjustment);
@@ -103,37 +238,1087 @@ impl TroveManager {
 
             Ok(())
         } else {
-            Err("Unauthorized: caller is not the owner".to_string())
+            Err(ProtocolError::Unauthorized)
         }
     }
 
-    pub fn add_collateral(&mut self, borrower: Pubkey, amount: Balance) { // Change AccountId to Pubkey
-        let trove = self.troves.get_mut(&borrower).unwrap();
+    pub fn open_trove(
+        &mut self,
+        borrower: Pubkey,
+        coll: Balance,
+        debt: Balance,
+        upper_hint: Option<u32>,
+        lower_hint: Option<u32>,
+    ) -> Result<u32, ProtocolError> {
+        if self.sunsetting {
+            return Err(ProtocolError::CollateralSunsetting);
+        }
+        if self.troves.get(&borrower).map_or(false, |t| t.status == Status::Active) {
+            return Err(ProtocolError::TroveAlreadyActive);
+        }
+
+        let current_interest_index = self.accrue_active_interests();
+        self.require_debt_capacity(debt)?;
+        let array_index = self.trove_owners.len() as u32;
+        self.troves.insert(borrower, Trove {
+            debt,
+            coll,
+            stake: 0,
+            status: Status::Active,
+            array_index,
+            active_interest_index: current_interest_index,
+        });
+        self.trove_owners.push(borrower);
+
+        self.update_trove_reward_snapshots(borrower);
+        let stake = self.update_stake_and_total_stakes(borrower);
+        self.total_active_collateral += coll;
+        self.total_active_debt += debt;
+
+        let id = self.sorted_trove_id(borrower);
+        let nicr = BabelMath::compute_nominal_cr(coll, debt);
+        self.sorted_troves.insert(id, nicr.into(), upper_hint, lower_hint)?;
+
+        msg!("TroveOpened: borrower = {:?}, coll = {}, debt = {}", borrower, coll, debt);
+        ProtocolEvent::TroveOpened {
+            collateral: self.collateral_token.clone(),
+            borrower: borrower.to_string(),
+            coll,
+            debt,
+            stake,
+        }
+        .emit();
+        Ok(array_index)
+    }
+
+    pub fn add_collateral(&mut self, borrower: Pubkey, amount: Balance) -> Result<(), ProtocolError> { // Change AccountId to Pubkey
+        self.apply_pending_redistribution(borrower);
+        let trove = self.troves.get_mut(&borrower).ok_or(ProtocolError::TroveNotActive)?;
         trove.coll += amount;
         self.total_active_collateral += amount;
+        self.update_stake_and_total_stakes(borrower);
+        self.re_insert_trove(borrower, None, None)
+    }
+
+    pub fn adjust_trove(
+        &mut self,
+        borrower: Pubkey,
+        coll_change: i64,
+        debt_change: i64,
+        upper_hint: Option<u32>,
+        lower_hint: Option<u32>,
+    ) -> Result<(), ProtocolError> {
+        if self.troves.get(&borrower).map_or(true, |t| t.status != Status::Active) {
+            return Err(ProtocolError::TroveNotActive);
+        }
+        if debt_change > 0 && self.sunsetting {
+            return Err(ProtocolError::CollateralSunsetting);
+        }
+        self.apply_pending_redistribution(borrower);
+        if debt_change > 0 {
+            self.require_debt_capacity(debt_change as Balance)?;
+        }
+
+        let trove = self.troves.get_mut(&borrower).unwrap();
+        if coll_change < 0 && trove.coll < coll_change.unsigned_abs() as Balance {
+            return Err(ProtocolError::CollateralWithdrawalExceedsTrove);
+        }
+        if debt_change < 0 && trove.debt < debt_change.unsigned_abs() as Balance {
+            return Err(ProtocolError::RepaymentExceedsDebt);
+        }
+
+        if coll_change >= 0 {
+            trove.coll += coll_change as Balance;
+            self.total_active_collateral += coll_change as Balance;
+        } else {
+            trove.coll -= coll_change.unsigned_abs() as Balance;
+            self.total_active_collateral -= coll_change.unsigned_abs() as Balance;
+        }
+        if debt_change >= 0 {
+            trove.debt += debt_change as Balance;
+            self.total_active_debt += debt_change as Balance;
+        } else {
+            trove.debt -= debt_change.unsigned_abs() as Balance;
+            self.total_active_debt -= debt_change.unsigned_abs() as Balance;
+        }
+
+        let (coll, debt) = (trove.coll, trove.debt);
+        let stake = self.update_stake_and_total_stakes(borrower);
+        self.re_insert_trove(borrower, upper_hint, lower_hint)?;
+        ProtocolEvent::TroveAdjusted {
+            collateral: self.collateral_token.clone(),
+            borrower: borrower.to_string(),
+            coll,
+            debt,
+            stake,
+        }
+        .emit();
+        Ok(())
+    }
+
+    // Moves a trove to its new position in SortedTroves after its NICR changed
+    fn re_insert_trove(&mut self, borrower: Pubkey, upper_hint: Option<u32>, lower_hint: Option<u32>) -> Result<(), ProtocolError> {
+        let id = self.sorted_trove_id(borrower);
+        let nicr = self.get_nominal_icr(borrower);
+        self.sorted_troves.re_insert(id, nicr.into(), upper_hint, lower_hint)
+    }
+
+    pub fn close_trove(&mut self, borrower: Pubkey, closed_status: Status) -> Result<(Balance, Balance), ProtocolError> {
+        if self.troves.get(&borrower).map_or(true, |t| t.status != Status::Active) {
+            return Err(ProtocolError::TroveNotActive);
+        }
+        if self.trove_owners.len() <= 1 {
+            return Err(ProtocolError::OnlyOneTroveInSystem);
+        }
+        self.apply_pending_redistribution(borrower);
+        self.remove_stake(borrower);
+
+        let trove = self.troves.get_mut(&borrower).unwrap();
+        let (debt, coll) = (trove.debt, trove.coll);
+        trove.status = closed_status;
+        trove.debt = 0;
+        trove.coll = 0;
+        self.total_active_collateral -= coll;
+        self.total_active_debt -= debt;
+
+        self.reward_snapshots.remove(&borrower);
+        self.remove_trove_owner(borrower);
+        if let Some(id) = self.sorted_trove_ids.get(&borrower).cloned() {
+            self.sorted_troves.remove(id)?;
+        }
+        // Liquidations report the closed trove through TroveLiquidated instead
+        if closed_status == Status::ClosedByOwner {
+            ProtocolEvent::TroveClosed {
+                collateral: self.collateral_token.clone(),
+                borrower: borrower.to_string(),
+                coll,
+                debt,
+            }
+            .emit();
+        }
+        Ok((debt, coll))
     }
 
-    pub fn claim_collateral(&mut self, receiver: Pubkey) -> Result<Balance, String> { // Change AccountId to Pubkey
-        let claimable_coll = self.surplus_balances.get(&receiver).cloned().unwrap_or(0);
-        if claimable_coll > 0 {
-            self.surplus_balances.insert(receiver, 0);
-            Ok(claimable_coll)
+    // Redeems `debt_amount` of debt tokens for collateral at face value, starting from the trove
+    // with the lowest ICR that is still above MCR, and burns the redeemed debt together with the
+    // reserves of the troves it closed. `account_info` is this trove manager's account.
+    pub fn redeem_collateral(
+        &mut self,
+        debt_token: &mut DebtToken,
+        account_info: &AccountInfo,
+        redeemer: Pubkey,
+        debt_amount: Balance,
+        price: Balance,
+        hints: RedemptionHints,
+        max_iterations: u32,
+        max_fee_percentage: Balance,
+        min_net_debt: Balance,
+    ) -> Result<RedemptionTotals, ProtocolError> {
+        if debt_token.balance_of(redeemer) < debt_amount {
+            return Err(ProtocolError::InsufficientBalance);
+        }
+        // The reserve held for each trove in the gas pool is never redeemed
+        let debt_gas_compensation = debt_token.debt_gas_compensation();
+        let totals = self.redeem_from_troves(
+            redeemer,
+            debt_amount,
+            price,
+            hints,
+            max_iterations,
+            max_fee_percentage,
+            min_net_debt,
+            debt_gas_compensation,
+        )?;
+        debt_token.burn_redemption(redeemer, totals.total_debt_to_redeem, totals.gas_compensation_to_burn, account_info)?;
+        Ok(totals)
+    }
+
+    // Redeems from the troves and updates the system totals, leaving the burn to redeem_collateral
+    fn redeem_from_troves(
+        &mut self,
+        redeemer: Pubkey,
+        debt_amount: Balance,
+        price: Balance,
+        hints: RedemptionHints,
+        mut max_iterations: u32,
+        max_fee_percentage: Balance,
+        min_net_debt: Balance,
+        debt_gas_compensation: Balance,
+    ) -> Result<RedemptionTotals, ProtocolError> {
+        if max_fee_percentage < self.redemption_fee_floor || max_fee_percentage > DECIMAL_PRECISION {
+            return Err(ProtocolError::InvalidMaxFeePercentage);
+        }
+        if debt_amount == 0 {
+            return Err(ProtocolError::ZeroAmount);
+        }
+        let tcr = BabelMath::compute_cr(self.get_entire_system_coll(), self.get_entire_system_debt(), price);
+        if tcr < self.mcr {
+            return Err(ProtocolError::TcrBelowMcr);
+        }
+
+        let total_debt_supply_at_start = self.get_entire_system_debt();
+        let mut totals = RedemptionTotals::default();
+        let mut remaining_debt = debt_amount;
+
+        let mut current_borrower = if self.is_valid_first_redemption_hint(hints.first_redemption_hint, price) {
+            hints.first_redemption_hint
         } else {
-            Err("No collateral available to claim".to_string())
+            let mut candidate = self.sorted_troves.get_last().and_then(|id| self.sorted_trove_owners.get(&id).cloned());
+            while let Some(borrower) = candidate {
+                if self.get_current_icr(borrower, price) >= self.mcr {
+                    break;
+                }
+                candidate = self.get_prev_trove(borrower);
+            }
+            candidate
+        };
+
+        if max_iterations == 0 {
+            max_iterations = u32::MAX;
         }
+
+        // Loop through the troves starting from the one with the lowest ICR >= MCR and move up the list
+        while let Some(borrower) = current_borrower {
+            if remaining_debt == 0 || max_iterations == 0 {
+                break;
+            }
+            max_iterations -= 1;
+
+            // Save the neighbour now, as the current trove may be removed from the list
+            let next_user_to_check = self.get_prev_trove(borrower);
+            self.apply_pending_redistribution(borrower);
+
+            let single_redemption = self.redeem_collateral_from_trove(
+                borrower,
+                remaining_debt,
+                price,
+                &hints,
+                min_net_debt,
+                debt_gas_compensation,
+            )?;
+            // Partial redemption was cancelled (out-of-date hint, or new net debt < minimum), so we stop here
+            if single_redemption.cancelled_partial {
+                break;
+            }
+
+            totals.total_debt_to_redeem += single_redemption.debt_lot;
+            totals.total_collateral_drawn += single_redemption.collateral_lot;
+            if single_redemption.closed {
+                totals.gas_compensation_to_burn += debt_gas_compensation;
+            }
+            remaining_debt -= single_redemption.debt_lot;
+            current_borrower = next_user_to_check;
+        }
+
+        if totals.total_collateral_drawn == 0 {
+            return Err(ProtocolError::NothingToRedeem);
+        }
+
+        // Redemptions of a sunsetting collateral are free and do not move the base rate
+        if self.sunsetting {
+            totals.collateral_fee = 0;
+        } else {
+            // Raise the base rate according to the redeemed fraction of the total supply
+            self.update_base_rate_from_redemption(totals.total_collateral_drawn, price, total_debt_supply_at_start);
+            totals.collateral_fee = self.calc_redemption_fee(self.get_redemption_rate(), totals.total_collateral_drawn);
+        }
+        if BabelMath::mul_div(totals.collateral_fee, DECIMAL_PRECISION, totals.total_collateral_drawn) > max_fee_percentage {
+            return Err(ProtocolError::FeeExceedsMax);
+        }
+        totals.collateral_to_send_to_redeemer = totals.total_collateral_drawn - totals.collateral_fee;
+
+        // The reserves of closed troves leave the active debt together with the redeemed debt
+        self.total_active_debt -= totals.total_debt_to_redeem + totals.gas_compensation_to_burn;
+        self.total_active_collateral -= totals.total_collateral_drawn;
+
+        msg!(
+            "Redemption: redeemer = {:?}, attempted = {}, actual = {}, collateral_sent = {}, fee = {}",
+            redeemer, debt_amount, totals.total_debt_to_redeem, totals.collateral_to_send_to_redeemer, totals.collateral_fee
+        );
+        ProtocolEvent::Redemption {
+            collateral: self.collateral_token.clone(),
+            redeemer: redeemer.to_string(),
+            attempted_debt: debt_amount,
+            actual_debt: totals.total_debt_to_redeem,
+            collateral_sent: totals.collateral_to_send_to_redeemer,
+            fee: totals.collateral_fee,
+        }
+        .emit();
+        Ok(totals)
+    }
+
+    // Redeems as much debt as possible from a single trove, closing it once only the liquidation reserve is left
+    fn redeem_collateral_from_trove(
+        &mut self,
+        borrower: Pubkey,
+        max_debt_amount: Balance,
+        price: Balance,
+        hints: &RedemptionHints,
+        min_net_debt: Balance,
+        debt_gas_compensation: Balance,
+    ) -> Result<SingleRedemptionValues, ProtocolError> {
+        let (debt, coll) = self.troves.get(&borrower).map_or((0, 0), |t| (t.debt, t.coll));
+
+        // Determine the remaining amount (lot) to be redeemed, capped by the entire debt of the trove
+        // minus the liquidation reserve, which is burned from the gas pool instead
+        let debt_lot = BabelMath::min(max_debt_amount, debt.saturating_sub(debt_gas_compensation));
+        // Get the collateral lot of equivalent value in USD
+        let collateral_lot = BabelMath::mul_div(debt_lot, DECIMAL_PRECISION, price);
+
+        let new_debt = debt - debt_lot;
+        let new_coll = coll - collateral_lot;
+
+        if new_debt <= debt_gas_compensation {
+            // Only the reserve is left, so the trove is closed and the remaining collateral becomes claimable
+            self.remove_stake(borrower);
+            let trove = self.troves.get_mut(&borrower).unwrap();
+            trove.status = Status::ClosedByRedemption;
+            trove.debt = 0;
+            trove.coll = 0;
+            self.reward_snapshots.remove(&borrower);
+            self.remove_trove_owner(borrower);
+            if let Some(id) = self.sorted_trove_ids.get(&borrower).cloned() {
+                self.sorted_troves.remove(id)?;
+            }
+
+            // The surplus leaves the active pool and is held until the owner claims it
+            self.total_active_collateral -= new_coll;
+            self.coll_surplus_pool.account_surplus(borrower, new_coll);
+
+            msg!("TroveUpdated: borrower = {:?}, closed by redemption, surplus = {}", borrower, new_coll);
+            ProtocolEvent::TroveRedeemed {
+                collateral: self.collateral_token.clone(),
+                borrower: borrower.to_string(),
+                coll: 0,
+                debt: 0,
+                closed: true,
+            }
+            .emit();
+        } else {
+            let new_nicr = BabelMath::compute_nominal_cr(new_coll, new_debt);
+
+            // If the provided hint is out of date, we bail since trying to reinsert without a good hint will almost
+            // certainly result in running out of compute, and the remaining debt would not be redeemed anyway
+            if new_nicr != hints.partial_redemption_hint_nicr || new_debt - debt_gas_compensation < min_net_debt {
+                return Ok(SingleRedemptionValues {
+                    debt_lot: 0,
+                    collateral_lot: 0,
+                    cancelled_partial: true,
+                    closed: false,
+                });
+            }
+
+            let id = self.sorted_trove_id(borrower);
+            self.sorted_troves.re_insert(
+                id,
+                new_nicr.into(),
+                hints.upper_partial_redemption_hint,
+                hints.lower_partial_redemption_hint,
+            )?;
+
+            let trove = self.troves.get_mut(&borrower).unwrap();
+            trove.debt = new_debt;
+            trove.coll = new_coll;
+            self.update_stake_and_total_stakes(borrower);
+
+            msg!("TroveUpdated: borrower = {:?}, debt = {}, coll = {}, redeemed", borrower, new_debt, new_coll);
+            ProtocolEvent::TroveRedeemed {
+                collateral: self.collateral_token.clone(),
+                borrower: borrower.to_string(),
+                coll: new_coll,
+                debt: new_debt,
+                closed: false,
+            }
+            .emit();
+        }
+
+        Ok(SingleRedemptionValues {
+            debt_lot,
+            collateral_lot,
+            cancelled_partial: false,
+            closed: new_debt <= debt_gas_compensation,
+        })
     }
 
-    pub fn claim_reward(&mut self, account: Pubkey) -> Result<Balance, String> { // Change AccountId to Pubkey
+    fn is_valid_first_redemption_hint(&self, first_redemption_hint: Option<Pubkey>, price: Balance) -> bool {
+        let hint = match first_redemption_hint {
+            Some(hint) => hint,
+            None => return false,
+        };
+        let id = match self.sorted_trove_ids.get(&hint) {
+            Some(id) if self.sorted_troves.contains(*id) => *id,
+            _ => return false,
+        };
+        if self.get_current_icr(hint, price) < self.mcr {
+            return false;
+        }
+
+        match self.sorted_troves.get_next(id).and_then(|next| self.sorted_trove_owners.get(&next)) {
+            Some(next) => self.get_current_icr(*next, price) < self.mcr,
+            None => true,
+        }
+    }
+
+    // Updates the base rate from a redemption operation. Impacts on the base rate:
+    // 1. decays the base rate based on time passed since the last redemption or borrowing operation
+    // 2. increases the base rate based on the amount redeemed, as a proportion of total supply
+    fn update_base_rate_from_redemption(&mut self, collateral_drawn: Balance, price: Balance, total_debt_supply: Balance) {
+        let decayed_base_rate = self.calc_decayed_base_rate();
+        let redeemed_debt_fraction = BabelMath::mul_div(collateral_drawn, price, total_debt_supply);
+        let new_base_rate = decayed_base_rate + redeemed_debt_fraction / BETA;
+        // The base rate can decay to 0 but never exceeds 100%
+        self.base_rate = BabelMath::min(new_base_rate, DECIMAL_PRECISION);
+        msg!("BaseRateUpdated: {}", self.base_rate);
+        self.update_last_fee_op_time();
+    }
+
+    // --- Redemption fee functions ---
+
+    pub fn get_redemption_rate(&self) -> Balance {
+        self.calc_redemption_rate(self.base_rate)
+    }
+
+    // Rate a redemption would pay right now, without changing state
+    pub fn get_redemption_rate_with_decay(&self) -> Balance {
+        self.calc_redemption_rate(self.calc_decayed_base_rate())
+    }
+
+    fn calc_redemption_rate(&self, base_rate: Balance) -> Balance {
+        BabelMath::min(self.redemption_fee_floor + base_rate, self.max_redemption_fee)
+    }
+
+    pub fn get_redemption_fee_with_decay(&self, collateral_drawn: Balance) -> Balance {
+        self.calc_redemption_fee(self.get_redemption_rate_with_decay(), collateral_drawn)
+    }
+
+    fn calc_redemption_fee(&self, redemption_rate: Balance, collateral_drawn: Balance) -> Balance {
+        BabelMath::mul_div(redemption_rate, collateral_drawn, DECIMAL_PRECISION)
+    }
+
+    // --- Borrowing fee functions ---
+
+    pub fn get_borrowing_rate(&self) -> Balance {
+        self.calc_borrowing_rate(self.base_rate)
+    }
+
+    // Rate a borrowing operation would pay right now, without changing state
+    pub fn get_borrowing_rate_with_decay(&self) -> Balance {
+        self.calc_borrowing_rate(self.calc_decayed_base_rate())
+    }
+
+    fn calc_borrowing_rate(&self, base_rate: Balance) -> Balance {
+        BabelMath::min(self.borrowing_fee_floor + base_rate, self.max_borrowing_fee)
+    }
+
+    pub fn get_borrowing_fee(&self, debt: Balance) -> Balance {
+        self.calc_borrowing_fee(self.get_borrowing_rate(), debt)
+    }
+
+    pub fn get_borrowing_fee_with_decay(&self, debt: Balance) -> Balance {
+        self.calc_borrowing_fee(self.get_borrowing_rate_with_decay(), debt)
+    }
+
+    fn calc_borrowing_fee(&self, borrowing_rate: Balance, debt: Balance) -> Balance {
+        BabelMath::mul_div(borrowing_rate, debt, DECIMAL_PRECISION)
+    }
+
+    // Updates the base rate based on time elapsed since the last redemption or borrowing operation,
+    // then returns the borrowing fee for `debt` at the decayed rate
+    pub fn decay_base_rate_and_get_borrowing_fee(&mut self, debt: Balance) -> Balance {
+        self.decay_base_rate();
+        self.get_borrowing_fee(debt)
+    }
+
+    fn decay_base_rate(&mut self) {
+        let decayed_base_rate = self.calc_decayed_base_rate();
+        // The decayed base rate is always <= the current base rate
+        self.base_rate = decayed_base_rate;
+        msg!("BaseRateUpdated: {}", decayed_base_rate);
+        self.update_last_fee_op_time();
+    }
+
+    // Base rate decayed by MINUTE_DECAY_FACTOR^minutes_elapsed since the last fee operation
+    fn calc_decayed_base_rate(&self) -> Balance {
+        let minutes_passed = self.minutes_passed_since_last_fee_op();
+        let decay_factor = BabelMath::dec_pow(self.minute_decay_factor, minutes_passed);
+        self.base_rate * decay_factor / DECIMAL_PRECISION
+    }
+
+    // Only whole minutes are consumed, so frequent operations cannot stall the decay
+    fn update_last_fee_op_time(&mut self) {
+        let minutes_passed = self.minutes_passed_since_last_fee_op();
+        if minutes_passed > 0 {
+            self.last_fee_operation_time += minutes_passed * SECONDS_IN_ONE_MINUTE;
+            msg!("LastFeeOpTimeUpdated: {}", self.last_fee_operation_time);
+        }
+    }
+
+    fn minutes_passed_since_last_fee_op(&self) -> u64 {
+        self.current_time().saturating_sub(self.last_fee_operation_time) / SECONDS_IN_ONE_MINUTE
+    }
+
+    fn current_time(&self) -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+    }
+
+    pub fn get_nominal_icr(&self, borrower: Pubkey) -> Balance {
+        let (debt, coll, _, _) = self.get_entire_debt_and_coll(borrower);
+        BabelMath::compute_nominal_cr(coll, debt)
+    }
+
+    pub fn get_current_icr(&self, borrower: Pubkey, price: Balance) -> Balance {
+        let (debt, coll, _, _) = self.get_entire_debt_and_coll(borrower);
+        BabelMath::compute_cr(coll, debt, price)
+    }
+
+    pub fn mcr(&self) -> Balance {
+        self.mcr
+    }
+
+    pub fn sorted_troves(&self) -> &SortedTroves {
+        &self.sorted_troves
+    }
+
+    // Returns the borrower whose trove sits directly above `borrower` (larger NICR) in SortedTroves
+    fn get_prev_trove(&self, borrower: Pubkey) -> Option<Pubkey> {
+        self.sorted_trove_ids.get(&borrower)
+            .and_then(|id| self.sorted_troves.get_prev(*id))
+            .and_then(|id| self.sorted_trove_owners.get(&id).cloned())
+    }
+
+    pub fn get_trove_owner_by_sorted_id(&self, id: u32) -> Option<Pubkey> {
+        self.sorted_trove_owners.get(&id).cloned()
+    }
+
+    pub fn get_sorted_trove_id(&self, borrower: Pubkey) -> Option<u32> {
+        self.sorted_trove_ids.get(&borrower).cloned()
+    }
+
+    // Returns the SortedTroves id of a borrower, allocating one the first time a borrower opens a trove
+    fn sorted_trove_id(&mut self, borrower: Pubkey) -> u32 {
+        if let Some(id) = self.sorted_trove_ids.get(&borrower) {
+            return *id;
+        }
+        let id = self.next_sorted_trove_id;
+        self.next_sorted_trove_id += 1;
+        self.sorted_trove_ids.insert(borrower, id);
+        self.sorted_trove_owners.insert(id, borrower);
+        id
+    }
+
+    // Returns the trove's debt and collateral including accrued interest and pending redistribution rewards,
+    // followed by the pending debt and collateral rewards themselves
+    pub fn get_entire_debt_and_coll(&self, borrower: Pubkey) -> (Balance, Balance, Balance, Balance) {
+        let trove = match self.troves.get(&borrower) {
+            Some(trove) => trove,
+            None => return (0, 0, 0, 0),
+        };
+        let mut debt = trove.debt;
+        if trove.status == Status::Active && trove.active_interest_index > 0 {
+            let (current_interest_index, _) = self.calculate_interest_index();
+            debt = BabelMath::mul_div(debt, current_interest_index, trove.active_interest_index);
+        }
+        let (pending_coll, pending_debt) = self.get_pending_collateral_and_debt_rewards(borrower);
+        (debt + pending_debt, trove.coll + pending_coll, pending_debt, pending_coll)
+    }
+
+    pub fn get_entire_system_coll(&self) -> Balance {
+        self.total_active_collateral + self.defaulted_collateral
+    }
+
+    pub fn get_entire_system_debt(&self) -> Balance {
+        let (_, interest_factor) = self.calculate_interest_index();
+        let debt = self.total_active_debt + self.defaulted_debt;
+        debt + BabelMath::mul_div(debt, interest_factor, DECIMAL_PRECISION)
+    }
+
+    // --- Interest functions ---
+
+    // Function to set the price feed and collateral token, called once by the factory at deployment
+    pub fn set_addresses(&mut self, price_feed: String, collateral_token: String) {
+        self.price_feed = price_feed;
+        self.coll_surplus_pool.set_collateral(collateral_token.clone());
+        self.coll_gas_compensation.set_collateral(collateral_token.clone());
+        self.collateral_token = collateral_token;
+    }
+
+    // Function to set the per-collateral parameters chosen at deployment
+    pub fn set_parameters(&mut self, caller: &Pubkey, params: &DeploymentParams) -> Result<(), ProtocolError> {
+        self.require_owner(caller)?;
+        if self.sunsetting {
+            return Err(ProtocolError::CollateralSunsetting);
+        }
+        if params.minute_decay_factor < MIN_MINUTE_DECAY_FACTOR || params.minute_decay_factor > MAX_MINUTE_DECAY_FACTOR {
+            return Err(ProtocolError::MinuteDecayFactorOutOfRange);
+        }
+        if params.redemption_fee_floor > params.max_redemption_fee || params.max_redemption_fee > DECIMAL_PRECISION {
+            return Err(ProtocolError::InvalidRedemptionFeeBounds);
+        }
+        if params.borrowing_fee_floor > params.max_borrowing_fee || params.max_borrowing_fee > DECIMAL_PRECISION {
+            return Err(ProtocolError::InvalidBorrowingFeeBounds);
+        }
+        if params.interest_rate_in_bps > MAX_INTEREST_RATE_IN_BPS {
+            return Err(ProtocolError::InterestRateTooHigh);
+        }
+        if params.mcr < MCR || params.mcr > BabelBase::CCR {
+            return Err(ProtocolError::InvalidMcr);
+        }
+        if params.price_source != PriceSource::Spot && params.twap_window_secs == 0 {
+            return Err(ProtocolError::TwapWindowNotSet);
+        }
+
+        // The base rate decays under the old factor up to now
+        self.decay_base_rate();
+
+        self.minute_decay_factor = params.minute_decay_factor;
+        self.redemption_fee_floor = params.redemption_fee_floor;
+        self.max_redemption_fee = params.max_redemption_fee;
+        self.borrowing_fee_floor = params.borrowing_fee_floor;
+        self.max_borrowing_fee = params.max_borrowing_fee;
+        self.max_system_debt = params.max_debt;
+        self.mcr = params.mcr;
+        self.price_source = params.price_source;
+        self.twap_window_secs = params.twap_window_secs;
+        self.set_interest_rate(caller, params.interest_rate_in_bps)
+    }
+
+    // Function to start delisting this collateral: no new debt, a punitive interest rate
+    // and zero-fee redemptions so that borrowers exit
+    pub fn start_sunset(&mut self, caller: &Pubkey) -> Result<(), ProtocolError> {
+        self.require_owner(caller)?;
+        if self.sunsetting {
+            return Err(ProtocolError::AlreadySunsetting);
+        }
+        // Interest accrued so far is charged at the old rate
+        self.accrue_active_interests();
+        self.sunsetting = true;
+        self.interest_rate = SUNSETTING_INTEREST_RATE_IN_BPS * DECIMAL_PRECISION / (10_000 * SECONDS_IN_YEAR);
+        self.redemption_fee_floor = 0;
+        self.max_system_debt = 0;
+        msg!("SunsetStarted: interest rate = {} bps", SUNSETTING_INTEREST_RATE_IN_BPS);
+        Ok(())
+    }
+
+    pub fn is_sunsetting(&self) -> bool {
+        self.sunsetting
+    }
+
+    pub fn max_system_debt(&self) -> Balance {
+        self.max_system_debt
+    }
+
+    // Function for governance to raise or lower the debt ceiling of this collateral
+    pub fn set_max_system_debt(&mut self, caller: &Pubkey, max_system_debt: Balance) -> Result<(), ProtocolError> {
+        self.require_owner(caller)?;
+        if self.sunsetting {
+            return Err(ProtocolError::CollateralSunsetting);
+        }
+        self.max_system_debt = max_system_debt;
+        msg!("MaxSystemDebtSet: {}", max_system_debt);
+        Ok(())
+    }
+
+    // Debt that can still be minted against this collateral, pending interest included
+    pub fn get_remaining_debt_capacity(&self) -> Balance {
+        self.max_system_debt.saturating_sub(self.get_entire_system_debt())
+    }
+
+    pub fn require_debt_capacity(&self, debt_increase: Balance) -> Result<(), ProtocolError> {
+        let headroom = self.get_remaining_debt_capacity();
+        if debt_increase > headroom {
+            return Err(ProtocolError::MaxSystemDebtExceeded { requested: debt_increase, headroom });
+        }
+        Ok(())
+    }
+
+    pub fn get_collateral_token(&self) -> String {
+        self.collateral_token.clone()
+    }
+
+    pub fn price_feed(&self) -> String {
+        self.price_feed.clone()
+    }
+
+    // Function to fetch the collateral price used for ICR and liquidation checks
+    pub fn fetch_price(&self, price_feed: &mut PriceFeed) -> Option<Balance> {
+        price_feed.fetch_price_with_source(&self.collateral_token, self.price_source, self.twap_window_secs)
+    }
+
+    // Sets the annual interest rate for this collateral, converted to a per-second rate
+    pub fn set_interest_rate(&mut self, caller: &Pubkey, interest_rate_in_bps: Balance) -> Result<(), ProtocolError> {
+        self.require_owner(caller)?;
+        if self.sunsetting {
+            return Err(ProtocolError::CollateralSunsetting);
+        }
+        if interest_rate_in_bps > MAX_INTEREST_RATE_IN_BPS {
+            return Err(ProtocolError::InterestRateTooHigh);
+        }
+        // Interest accrued so far is charged at the old rate
+        self.accrue_active_interests();
+        self.interest_rate = interest_rate_in_bps * DECIMAL_PRECISION / (10_000 * SECONDS_IN_YEAR);
+        msg!("InterestRateSet: {} bps", interest_rate_in_bps);
+        Ok(())
+    }
+
+    pub fn interest_rate(&self) -> Balance {
+        self.interest_rate
+    }
+
+    pub fn interest_payable(&self) -> Balance {
+        self.interest_payable
+    }
+
+    // Mints the interest accrued since the last collection to the fee receiver
+    pub fn collect_interests(
+        &mut self,
+        debt_token: &mut DebtToken,
+        fee_receiver: Pubkey,
+        account_info: &AccountInfo,
+    ) -> Result<Balance, ProtocolError> {
+        self.accrue_active_interests();
+        let interest_payable = self.interest_payable;
+        if interest_payable == 0 {
+            return Err(ProtocolError::NothingToCollect);
+        }
+        // Interest stays payable if the global debt cap rejects the mint
+        debt_token.mint(fee_receiver, interest_payable, account_info)?;
+        self.interest_payable = 0;
+        msg!("InterestCollected: fee_receiver = {:?}, amount = {}", fee_receiver, interest_payable);
+        Ok(interest_payable)
+    }
+
+    // Compounds the global interest index up to now and adds the interest on active and pending
+    // redistributed debt to the payable amount
+    fn accrue_active_interests(&mut self) -> Balance {
+        let (current_interest_index, interest_factor) = self.calculate_interest_index();
+        if interest_factor > 0 {
+            let active_interests = BabelMath::mul_div(self.total_active_debt, interest_factor, DECIMAL_PRECISION);
+            let defaulted_interests = BabelMath::mul_div(self.defaulted_debt, interest_factor, DECIMAL_PRECISION);
+            self.total_active_debt += active_interests;
+            self.defaulted_debt += defaulted_interests;
+            self.interest_payable += active_interests + defaulted_interests;
+        }
+        self.active_interest_index = current_interest_index;
+        self.last_active_index_update = self.current_time();
+        current_interest_index
+    }
+
+    // Returns the index compounded per second since the last update, and the growth factor over that period
+    fn calculate_interest_index(&self) -> (Balance, Balance) {
+        let now = self.current_time();
+        if self.interest_rate == 0 || now <= self.last_active_index_update {
+            return (self.active_interest_index, 0);
+        }
+        let seconds_passed = now - self.last_active_index_update;
+        let compound_factor = BabelMath::dec_pow_seconds(DECIMAL_PRECISION + self.interest_rate, seconds_passed);
+        let interest_factor = compound_factor - DECIMAL_PRECISION;
+        let current_interest_index = BabelMath::mul_div(self.active_interest_index, compound_factor, DECIMAL_PRECISION);
+        (current_interest_index, interest_factor)
+    }
+
+    // Scales a trove's debt by the growth of the global index since its snapshot
+    fn apply_trove_interest(&mut self, borrower: Pubkey) {
+        let current_interest_index = self.accrue_active_interests();
+        if let Some(trove) = self.troves.get_mut(&borrower) {
+            if trove.status != Status::Active {
+                return;
+            }
+            if trove.active_interest_index > 0 && trove.active_interest_index < current_interest_index {
+                trove.debt = BabelMath::mul_div(trove.debt, current_interest_index, trove.active_interest_index);
+            }
+            trove.active_interest_index = current_interest_index;
+        }
+    }
+
+    pub fn has_pending_rewards(&self, borrower: Pubkey) -> bool {
+        match self.troves.get(&borrower) {
+            Some(trove) if trove.status == Status::Active => {
+                self.reward_snapshots.get(&borrower).map_or(0, |s| s.collateral) < self.l_collateral
+            }
+            _ => false,
+        }
+    }
+
+    pub fn get_pending_collateral_and_debt_rewards(&self, borrower: Pubkey) -> (Balance, Balance) {
+        let trove = match self.troves.get(&borrower) {
+            Some(trove) if trove.status == Status::Active => trove,
+            _ => return (0, 0),
+        };
+        let snapshot = self.reward_snapshots.get(&borrower).cloned().unwrap_or_default();
+        let l_collateral_diff = self.l_collateral - snapshot.collateral;
+        let l_debt_diff = self.l_debt - snapshot.debt;
+        if l_collateral_diff == 0 && l_debt_diff == 0 {
+            return (0, 0);
+        }
+
+        let (current_interest_index, _) = self.calculate_interest_index();
+        let pending_coll = BabelMath::mul_div(trove.stake, l_collateral_diff, DECIMAL_PRECISION);
+        let pending_debt = BabelMath::mul_div(
+            BabelMath::mul_div(trove.stake, l_debt_diff, DECIMAL_PRECISION),
+            current_interest_index,
+            DECIMAL_PRECISION,
+        );
+        (pending_coll, pending_debt)
+    }
+
+    // Applies accrued interest, then moves a trove's share of redistributed debt and collateral
+    // from the defaulted totals into the trove
+    pub fn apply_pending_redistribution(&mut self, borrower: Pubkey) {
+        self.apply_trove_interest(borrower);
+        if !self.has_pending_rewards(borrower) {
+            return;
+        }
+        let (pending_coll, pending_debt) = self.get_pending_collateral_and_debt_rewards(borrower);
+
+        let trove = self.troves.get_mut(&borrower).unwrap();
+        trove.coll += pending_coll;
+        trove.debt += pending_debt;
+        self.update_trove_reward_snapshots(borrower);
+
+        self.defaulted_collateral -= pending_coll;
+        // Pending debt is rounded per trove, so the sum can exceed the defaulted total by a few wei
+        self.defaulted_debt = self.defaulted_debt.saturating_sub(pending_debt);
+        self.total_active_collateral += pending_coll;
+        self.total_active_debt += pending_debt;
+
+        msg!("TroveUpdated: borrower = {:?}, pending_coll = {}, pending_debt = {}", borrower, pending_coll, pending_debt);
+    }
+
+    // Distributes debt and collateral the Stability Pool could not absorb across all active troves, pro-rata to stake
+    pub fn redistribute_debt_and_coll(&mut self, debt: Balance, coll: Balance) {
+        if debt == 0 || self.total_stakes == 0 {
+            return;
+        }
+        // Interest on the debt already pending is charged before the new debt joins it
+        let current_interest_index = self.accrue_active_interests();
+
+        // Carry the rounding error from the previous redistribution into this one. The error is always
+        // below total_stakes, so it is added after the division instead of to the 1e18-scaled numerator.
+        let (coll_reward_per_unit_staked, coll_error) =
+            Self::per_unit_staked(coll, self.last_collateral_error_redistribution, self.total_stakes);
+        let (debt_reward_per_unit_staked, debt_error) =
+            Self::per_unit_staked(debt, self.last_debt_error_redistribution, self.total_stakes);
+
+        self.last_collateral_error_redistribution = coll_error;
+        self.last_debt_error_redistribution = debt_error;
+
+        self.l_collateral += coll_reward_per_unit_staked;
+        self.l_debt += BabelMath::mul_div(debt_reward_per_unit_staked, DECIMAL_PRECISION, current_interest_index);
+
+        self.defaulted_collateral += coll;
+        self.defaulted_debt += debt;
+
+        msg!("LTermsUpdated: l_collateral = {}, l_debt = {}", self.l_collateral, self.l_debt);
+    }
+
+    // Splits (amount * DECIMAL_PRECISION + last_error) across `total_stakes`, returning the share per unit
+    // staked and the new rounding error, without forming the 1e18-scaled product in 128 bits
+    fn per_unit_staked(amount: Balance, last_error: Balance, total_stakes: Balance) -> (Balance, Balance) {
+        let (share, remainder) = BabelMath::mul_div_rem(amount, DECIMAL_PRECISION, total_stakes);
+        // Both the remainder and the carried error are below total_stakes
+        let carried = remainder + last_error;
+        (share + carried / total_stakes, carried % total_stakes)
+    }
+
+    // Credits collateral left over after a capped liquidation to the owner's claimable surplus
+    pub fn account_collateral_surplus(&mut self, borrower: Pubkey, amount: Balance) {
+        self.coll_surplus_pool.account_surplus(borrower, amount);
+    }
+
+    // Snapshots total stakes and collateral after a liquidation. close_trove has already taken the
+    // liquidated collateral, including any surplus left to the owner, out of the active total.
+    pub fn update_system_snapshots(&mut self) {
+        self.total_stakes_snapshot = self.total_stakes;
+        self.total_collateral_snapshot = self.total_active_collateral + self.defaulted_collateral;
+        msg!("SystemSnapshotsUpdated: total_stakes = {}, total_collateral = {}", self.total_stakes_snapshot, self.total_collateral_snapshot);
+    }
+
+    fn update_trove_reward_snapshots(&mut self, borrower: Pubkey) {
+        self.reward_snapshots.insert(borrower, RewardSnapshot {
+            collateral: self.l_collateral,
+            debt: self.l_debt,
+        });
+    }
+
+    fn compute_new_stake(&self, coll: Balance) -> Balance {
+        if self.total_collateral_snapshot == 0 {
+            coll
+        } else {
+            // totalStakesSnapshot is always non-zero when totalCollateralSnapshot is
+            BabelMath::mul_div(coll, self.total_stakes_snapshot, self.total_collateral_snapshot)
+        }
+    }
+
+    fn update_stake_and_total_stakes(&mut self, borrower: Pubkey) -> Balance {
+        let coll = self.troves.get(&borrower).map_or(0, |t| t.coll);
+        let new_stake = self.compute_new_stake(coll);
+        let trove = self.troves.get_mut(&borrower).unwrap();
+        let old_stake = trove.stake;
+        trove.stake = new_stake;
+        self.total_stakes = self.total_stakes - old_stake + new_stake;
+        new_stake
+    }
+
+    fn remove_stake(&mut self, borrower: Pubkey) {
+        if let Some(trove) = self.troves.get_mut(&borrower) {
+            self.total_stakes -= trove.stake;
+            trove.stake = 0;
+        }
+    }
+
+    fn remove_trove_owner(&mut self, borrower: Pubkey) {
+        let index = self.troves.get(&borrower).map_or(0, |t| t.array_index) as usize;
+        if index >= self.trove_owners.len() || self.trove_owners[index] != borrower {
+            return;
+        }
+        self.trove_owners.swap_remove(index);
+        if let Some(moved) = self.trove_owners.get(index).cloned() {
+            if let Some(trove) = self.troves.get_mut(&moved) {
+                trove.array_index = index as u32;
+            }
+        }
+    }
+
+    pub fn is_active_trove(&self, borrower: Pubkey) -> bool {
+        self.troves.get(&borrower).map_or(false, |t| t.status == Status::Active)
+    }
+
+    pub fn get_trove_owners_count(&self) -> usize {
+        self.trove_owners.len()
+    }
+
+    pub fn get_trove_from_trove_owners_array(&self, index: usize) -> Option<Pubkey> {
+        self.trove_owners.get(index).cloned()
+    }
+
+    // Function to withdraw the owner's whole surplus. `inputs` are collateral UTXOs held by this
+    // trove manager that fund the transfer; whatever they hold above the surplus is sent back to
+    // this trove manager as change.
+    pub fn claim_collateral(&mut self, receiver: Pubkey, inputs: Vec<TxIn>) -> Result<TransactionToSign, ProtocolError> {
+        let claimable_coll = self.coll_surplus_pool.surplus_of(receiver);
+        if claimable_coll == 0 {
+            return Err(ProtocolError::NothingToClaim);
+        }
+        let input_value = self.require_inputs_cover(&inputs, claimable_coll)?;
+
+        self.coll_surplus_pool.take_surplus(receiver)?;
+        let tx_to_sign = self.send_collateral(receiver, claimable_coll, inputs, input_value)?;
+
+        msg!("CollateralClaimed: receiver = {:?}, amount = {}", receiver, claimable_coll);
+        Ok(tx_to_sign)
+    }
+
+    // Function to withdraw the collateral gas compensation a liquidator earned, funded the same way
+    // as claim_collateral
+    pub fn claim_collateral_gas_compensation(&mut self, liquidator: Pubkey, inputs: Vec<TxIn>) -> Result<TransactionToSign, ProtocolError> {
+        let claimable_coll = self.coll_gas_compensation.surplus_of(liquidator);
+        if claimable_coll == 0 {
+            return Err(ProtocolError::NothingToClaim);
+        }
+        let input_value = self.require_inputs_cover(&inputs, claimable_coll)?;
+
+        self.coll_gas_compensation.take_surplus(liquidator)?;
+        let tx_to_sign = self.send_collateral(liquidator, claimable_coll, inputs, input_value)?;
+
+        msg!("CollateralGasCompensationClaimed: liquidator = {:?}, amount = {}", liquidator, claimable_coll);
+        Ok(tx_to_sign)
+    }
+
+    // Checks that every input is a UTXO held by this trove manager and that together they hold at
+    // least `amount`. Returns their total value.
+    fn require_inputs_cover(&self, inputs: &[TxIn], amount: Balance) -> Result<u64, ProtocolError> {
+        if inputs.is_empty() {
+            return Err(ProtocolError::NoUtxosProvided);
+        }
+        let amount = u64::try_from(amount).map_err(|_| ProtocolError::UtxoValueOverflow)?;
+        let mut input_value: u64 = 0;
+        for input in inputs.iter() {
+            let txid = input.previous_output.txid.to_byte_array();
+            let value = self
+                .utxo_value(self.owner, txid, input.previous_output.vout)
+                .ok_or(ProtocolError::InvalidUtxoOwnership)?;
+            input_value = input_value.checked_add(value).ok_or(ProtocolError::UtxoValueOverflow)?;
+        }
+        if input_value < amount {
+            return Err(ProtocolError::InsufficientUtxoValue { required: amount, provided: input_value });
+        }
+        Ok(input_value)
+    }
+
+    // Spends `inputs` into a transfer of `amount` to the receiver, with the change paid back to
+    // this trove manager and tracked as a new UTXO
+    fn send_collateral(&mut self, receiver: Pubkey, amount: Balance, inputs: Vec<TxIn>, input_value: u64) -> Result<TransactionToSign, ProtocolError> {
+        let amount = u64::try_from(amount).map_err(|_| ProtocolError::UtxoValueOverflow)?;
+        for input in inputs.iter() {
+            self.spend_utxo(self.owner, input.previous_output.txid.to_byte_array(), input.previous_output.vout)?;
+        }
+
+        let mut outputs = vec![TxOut {
+            value: amount,
+            script_pubkey: bitcoin::Script::from(get_account_script_pubkey(&receiver).to_vec()),
+        }];
+        let change = input_value - amount;
+        let change_script_pubkey = get_account_script_pubkey(&self.owner).to_vec();
+        if change > 0 {
+            outputs.push(TxOut {
+                value: change,
+                script_pubkey: bitcoin::Script::from(change_script_pubkey.clone()),
+            });
+        }
+
+        let tx = Self::build_bitcoin_transaction(inputs, outputs);
+        if change > 0 {
+            self.add_utxo(self.owner, UtxoMeta {
+                txid: tx.txid().to_byte_array(),
+                vout: 1,
+                value: change,
+                script_pubkey: change_script_pubkey,
+            });
+        }
+        Ok(self.create_bitcoin_transaction(tx.input, tx.output))
+    }
+
+    // Function to pay a liquidator: the debt reserve comes from the gas pool, the collateral
+    // compensation is credited to the liquidator's own balance, kept apart from borrower surpluses,
+    // and withdrawn through claim_collateral_gas_compensation
+    pub fn send_gas_compensation(
+        &mut self,
+        debt_token: &mut DebtToken,
+        liquidator: Pubkey,
+        debt_amount: Balance,
+        coll_amount: Balance,
+        account_info: &AccountInfo,
+    ) -> Result<(), ProtocolError> {
+        if debt_amount > 0 {
+            debt_token.pay_gas_compensation(liquidator, debt_amount, account_info)?;
+        }
+        if coll_amount > 0 {
+            self.coll_gas_compensation.account_surplus(liquidator, coll_amount);
+        }
+        Ok(())
+    }
+
+    // Function to get the collateral gas compensation a liquidator can claim
+    pub fn coll_gas_compensation_of(&self, liquidator: Pubkey) -> Balance {
+        self.coll_gas_compensation.surplus_of(liquidator)
+    }
+
+    // Function to get the collateral surplus an owner can claim from this trove manager
+    pub fn surplus_of(&self, owner: Pubkey) -> Balance {
+        self.coll_surplus_pool.surplus_of(owner)
+    }
+
+    pub fn claim_reward(&mut self, account: Pubkey) -> Result<Balance, ProtocolError> { // Change AccountId to Pubkey
         let amount = self.apply_pending_rewards(account)?;
         if amount > 0 {
             self.stored_pending_reward.insert(account, 0);
             Ok(amount)
         } else {
-            Err("No rewards available to claim".to_string())
+            Err(ProtocolError::NothingToClaim)
         }
     }
 
-    pub fn apply_pending_rewards(&mut self, account: Pubkey) -> Result<Balance, String> { // Change AccountId to Pubkey
+    pub fn apply_pending_rewards(&mut self, account: Pubkey) -> Result<Balance, ProtocolError> { // Change AccountId to Pubkey
         let reward_integral = self.reward_integral;
         let reward_integral_for = self.reward_integral_for.get(&account).cloned().unwrap_or(0);
         if reward_integral > reward_integral_for {
@@ -151,6 +1336,15 @@ impl TroveManager {
         self.owner 
     }
 
+    // Governance setters are called by the factory with the caller it authenticated as its owner
+    fn require_owner(&self, caller: &Pubkey) -> Result<(), ProtocolError> {
+        if *caller != self.owner {
+            msg!("Unauthorized: caller is not the owner");
+            return Err(ProtocolError::Unauthorized);
+        }
+        Ok(())
+    }
+
     pub fn serialize(&self) -> Vec<u8> {
         self.try_to_vec().expect("Serialization should not fail")
     }
@@ -159,7 +1353,7 @@ impl TroveManager {
         Self::try_from_slice(data).expect("Deserialization should not fail")
     }
 
-    pub fn process_bitcoin_transaction(&self, tx: TransactionToSign) -> Result<(), String> {
+    pub fn process_bitcoin_transaction(&self, tx: TransactionToSign) -> Result<(), ProtocolError> {
         // Example function to process a Bitcoin transaction
         let script_pubkey = get_account_script_pubkey(&self.owner);
         msg!("script_pubkey {:?}", script_pubkey);
@@ -167,7 +1361,7 @@ impl TroveManager {
         // Validate UTXO ownership
         let input = &tx.inputs_to_sign[0];
         if !self.validate_utxo(input.signer, input.txid, input.vout) {
-            return Err("Invalid UTXO ownership".to_string());
+            return Err(ProtocolError::InvalidUtxoOwnership);
         }
 
         // Invoke another program if needed
@@ -176,14 +1370,17 @@ impl TroveManager {
         Ok(())
     }
 
-    pub fn create_bitcoin_transaction(&self, inputs: Vec<TxIn>, outputs: Vec<TxOut>) -> TransactionToSign {
-        // Example function to create a Bitcoin transaction
-        let tx = Transaction {
+    fn build_bitcoin_transaction(inputs: Vec<TxIn>, outputs: Vec<TxOut>) -> Transaction {
+        Transaction {
             version: 1,
             lock_time: 0,
-            input: inputs.clone(),
-            output: outputs.clone(),
-        };
+            input: inputs,
+            output: outputs,
+        }
+    }
+
+    pub fn create_bitcoin_transaction(&self, inputs: Vec<TxIn>, outputs: Vec<TxOut>) -> TransactionToSign {
+        let tx = Self::build_bitcoin_transaction(inputs.clone(), outputs);
         let tx_bytes = tx.serialize(); // Serialize the transaction to bytes
         let tx_to_sign = TransactionToSign {
             tx_bytes,
@@ -205,19 +1402,24 @@ impl TroveManager {
         self.utxos.entry(owner).or_insert_with(Vec::new).push(utxo);
     }
 
-    pub fn spend_utxo(&mut self, owner: Pubkey, txid: [u8; 32], vout: u32) -> Result<(), String> {
+    pub fn spend_utxo(&mut self, owner: Pubkey, txid: [u8; 32], vout: u32) -> Result<(), ProtocolError> {
         if let Some(utxos) = self.utxos.get_mut(&owner) {
             if let Some(pos) = utxos.iter().position(|u| u.txid == txid && u.vout == vout) {
                 utxos.remove(pos);
                 Ok(())
             } else {
-                Err("UTXO not found".to_string())
+                Err(ProtocolError::UtxoNotFound)
             }
         } else {
-            Err("Owner has no UTXOs".to_string())
+            Err(ProtocolError::UtxoNotFound)
         }
     }
 
+    // Value of a UTXO held by `owner`, None if it is not tracked
+    pub fn utxo_value(&self, owner: Pubkey, txid: [u8; 32], vout: u32) -> Option<u64> {
+        self.utxos.get(&owner)?.iter().find(|u| u.txid == txid && u.vout == vout).map(|u| u.value)
+    }
+
     pub fn validate_utxo(&self, owner: Pubkey, txid: [u8; 32], vout: u32) -> bool {
         if let Some(utxos) = self.utxos.get(&owner) {
             utxos.iter().any(|u| u.txid == txid && u.vout == vout)
@@ -227,5 +1429,315 @@ impl TroveManager {
     }
 }
 
-type Balance = u64; // type definition
-type Timestamp = u64; // type definition
\ No newline at end of file
+type Balance = u128; // type definition
+type Timestamp = u64; // type definition
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRICE: Balance = 2 * DECIMAL_PRECISION;
+    const GAS_COMPENSATION: Balance = DECIMAL_PRECISION;
+
+    fn trove_manager() -> (TroveManager, Pubkey) {
+        let owner = Pubkey::new_unique();
+        let mut trove_manager = TroveManager::new(owner);
+        trove_manager.set_max_system_debt(&owner, 1_000 * DECIMAL_PRECISION).unwrap();
+        (trove_manager, owner)
+    }
+
+    #[test]
+    fn test_open_trove_lists_the_trove() {
+        let (mut trove_manager, _) = trove_manager();
+        let borrower = Pubkey::new_unique();
+        trove_manager.open_trove(borrower, 2 * DECIMAL_PRECISION, DECIMAL_PRECISION, None, None).unwrap();
+        let id = trove_manager.get_sorted_trove_id(borrower).unwrap();
+        assert!(trove_manager.sorted_troves().contains(id));
+    }
+
+    #[test]
+    fn test_open_redeem_close() {
+        let (mut trove_manager, _) = trove_manager();
+        let (a, b, c) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        // ICRs at PRICE: a 400%, b 150%, c 500%. Debts include the liquidation reserve.
+        trove_manager.open_trove(a, 10 * DECIMAL_PRECISION, 5 * DECIMAL_PRECISION, None, None).unwrap();
+        trove_manager.open_trove(b, 3 * DECIMAL_PRECISION, 4 * DECIMAL_PRECISION, None, None).unwrap();
+        trove_manager.open_trove(c, 10 * DECIMAL_PRECISION, 4 * DECIMAL_PRECISION, None, None).unwrap();
+        assert_eq!(trove_manager.sorted_troves().get_size(), 3);
+        assert_eq!(trove_manager.sorted_troves().get_last(), trove_manager.get_sorted_trove_id(b));
+
+        // Redeeming more than b's net debt stops at the reserve and closes b
+        let hints = RedemptionHints {
+            first_redemption_hint: Some(b),
+            upper_partial_redemption_hint: None,
+            lower_partial_redemption_hint: None,
+            partial_redemption_hint_nicr: 0,
+        };
+        let totals = trove_manager
+            .redeem_from_troves(Pubkey::new_unique(), 3 * DECIMAL_PRECISION, PRICE, hints, 1, DECIMAL_PRECISION, 0, GAS_COMPENSATION)
+            .unwrap();
+        assert_eq!(totals.total_debt_to_redeem, 3 * DECIMAL_PRECISION);
+        assert_eq!(totals.total_collateral_drawn, 3 * DECIMAL_PRECISION / 2);
+        assert_eq!(totals.gas_compensation_to_burn, GAS_COMPENSATION);
+        assert_eq!(totals.collateral_to_send_to_redeemer + totals.collateral_fee, totals.total_collateral_drawn);
+
+        assert!(!trove_manager.is_active_trove(b));
+        assert_eq!(trove_manager.surplus_of(b), 3 * DECIMAL_PRECISION / 2);
+        assert_eq!(trove_manager.sorted_troves().get_size(), 2);
+        assert_eq!(trove_manager.get_entire_system_debt(), 9 * DECIMAL_PRECISION);
+        assert_eq!(trove_manager.get_entire_system_coll(), 20 * DECIMAL_PRECISION);
+
+        assert_eq!(trove_manager.close_trove(a, Status::ClosedByOwner), Ok((5 * DECIMAL_PRECISION, 10 * DECIMAL_PRECISION)));
+        assert_eq!(trove_manager.sorted_troves().get_size(), 1);
+        assert_eq!(trove_manager.get_trove_owners_count(), 1);
+        assert_eq!(trove_manager.close_trove(c, Status::ClosedByOwner), Err(ProtocolError::OnlyOneTroveInSystem));
+    }
+
+    #[test]
+    fn test_liquidation_snapshots_exclude_the_closed_trove_only_once() {
+        let (mut trove_manager, _) = trove_manager();
+        let (a, b, c) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        trove_manager.open_trove(a, 10 * DECIMAL_PRECISION, 5 * DECIMAL_PRECISION, None, None).unwrap();
+        trove_manager.open_trove(b, 10 * DECIMAL_PRECISION, 5 * DECIMAL_PRECISION, None, None).unwrap();
+        trove_manager.open_trove(c, 3 * DECIMAL_PRECISION, 2 * DECIMAL_PRECISION, None, None).unwrap();
+
+        // c is liquidated with a capped offset: 2e18 of its collateral is offset, 1e18 is left as surplus
+        trove_manager.close_trove(c, Status::ClosedByLiquidation).unwrap();
+        trove_manager.account_collateral_surplus(c, DECIMAL_PRECISION);
+        trove_manager.update_system_snapshots();
+        assert_eq!(trove_manager.total_collateral_snapshot, 20 * DECIMAL_PRECISION);
+        assert_eq!(trove_manager.total_stakes_snapshot, 20 * DECIMAL_PRECISION);
+
+        // A new trove gets a stake equal to its collateral, as no collateral was redistributed
+        let d = Pubkey::new_unique();
+        trove_manager.open_trove(d, 4 * DECIMAL_PRECISION, 2 * DECIMAL_PRECISION, None, None).unwrap();
+        assert_eq!(trove_manager.troves[&d].stake, 4 * DECIMAL_PRECISION);
+    }
+
+    #[test]
+    fn test_new_stake_with_large_snapshots() {
+        let (mut trove_manager, _) = trove_manager();
+        // The product of collateral and stakes snapshot does not fit in 128 bits
+        trove_manager.total_collateral_snapshot = 400 * DECIMAL_PRECISION;
+        trove_manager.total_stakes_snapshot = 200 * DECIMAL_PRECISION;
+        assert_eq!(trove_manager.compute_new_stake(100 * DECIMAL_PRECISION), 50 * DECIMAL_PRECISION);
+    }
+
+    #[test]
+    fn test_partial_redemption_keeps_the_reserve_and_min_net_debt() {
+        let (mut trove_manager, _) = trove_manager();
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        trove_manager.open_trove(a, 10 * DECIMAL_PRECISION, 5 * DECIMAL_PRECISION, None, None).unwrap();
+        trove_manager.open_trove(b, 4 * DECIMAL_PRECISION, 5 * DECIMAL_PRECISION, None, None).unwrap();
+
+        // b keeps 3e18 of debt, 2e18 of it net of the reserve, which is below a 3e18 minimum
+        let new_nicr = BabelMath::compute_nominal_cr(3 * DECIMAL_PRECISION, 3 * DECIMAL_PRECISION);
+        let hints = RedemptionHints {
+            first_redemption_hint: Some(b),
+            upper_partial_redemption_hint: None,
+            lower_partial_redemption_hint: None,
+            partial_redemption_hint_nicr: new_nicr,
+        };
+        let redeemer = Pubkey::new_unique();
+        let result = trove_manager.redeem_from_troves(redeemer, 2 * DECIMAL_PRECISION, PRICE, hints.clone(), 1, DECIMAL_PRECISION, 3 * DECIMAL_PRECISION, GAS_COMPENSATION);
+        assert_eq!(result, Err(ProtocolError::NothingToRedeem));
+
+        let totals = trove_manager
+            .redeem_from_troves(redeemer, 2 * DECIMAL_PRECISION, PRICE, hints, 1, DECIMAL_PRECISION, DECIMAL_PRECISION, GAS_COMPENSATION)
+            .unwrap();
+        assert_eq!(totals.gas_compensation_to_burn, 0);
+        assert!(trove_manager.is_active_trove(b));
+        assert_eq!(trove_manager.get_nominal_icr(b), new_nicr);
+    }
+
+    #[test]
+    fn test_redemption_at_a_btc_price() {
+        let (mut trove_manager, _) = trove_manager();
+        let price = 60_000 * DECIMAL_PRECISION;
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        trove_manager.open_trove(a, 2 * DECIMAL_PRECISION, 400 * DECIMAL_PRECISION, None, None).unwrap();
+        trove_manager.open_trove(b, DECIMAL_PRECISION, 450 * DECIMAL_PRECISION, None, None).unwrap();
+        // Collateral times the price does not fit in 128 bits either
+        assert_eq!(trove_manager.get_current_icr(b, price), 60_000 * DECIMAL_PRECISION / 450);
+
+        // The lot times the 1e18 precision does not fit in 128 bits
+        let hints = RedemptionHints {
+            first_redemption_hint: Some(b),
+            upper_partial_redemption_hint: None,
+            lower_partial_redemption_hint: None,
+            partial_redemption_hint_nicr: 0,
+        };
+        let totals = trove_manager
+            .redeem_from_troves(Pubkey::new_unique(), 500 * DECIMAL_PRECISION, price, hints, 1, DECIMAL_PRECISION, 0, GAS_COMPENSATION)
+            .unwrap();
+        assert_eq!(totals.total_debt_to_redeem, 449 * DECIMAL_PRECISION);
+        assert_eq!(totals.total_collateral_drawn, 449 * DECIMAL_PRECISION / 60_000);
+        assert!(!trove_manager.is_active_trove(b));
+    }
+
+    #[test]
+    fn test_fees_on_large_amounts() {
+        let (trove_manager, _) = trove_manager();
+        // A 5% rate on a million tokens
+        let rate = 5 * DECIMAL_PRECISION / 100;
+        let amount = 1_000_000 * DECIMAL_PRECISION;
+        assert_eq!(trove_manager.calc_borrowing_fee(rate, amount), 50_000 * DECIMAL_PRECISION);
+        assert_eq!(trove_manager.calc_redemption_fee(rate, amount), 50_000 * DECIMAL_PRECISION);
+    }
+
+    #[test]
+    fn test_debt_ceiling_denies_new_debt_until_configured() {
+        let owner = Pubkey::new_unique();
+        let mut trove_manager = TroveManager::new(owner);
+        assert_eq!(
+            trove_manager.require_debt_capacity(1),
+            Err(ProtocolError::MaxSystemDebtExceeded { requested: 1, headroom: 0 })
+        );
+
+        trove_manager.set_max_system_debt(&owner, 10 * DECIMAL_PRECISION).unwrap();
+        assert!(trove_manager.require_debt_capacity(10 * DECIMAL_PRECISION).is_ok());
+        trove_manager.open_trove(Pubkey::new_unique(), 10 * DECIMAL_PRECISION, 4 * DECIMAL_PRECISION, None, None).unwrap();
+        assert_eq!(trove_manager.get_remaining_debt_capacity(), 6 * DECIMAL_PRECISION);
+        assert!(trove_manager.require_debt_capacity(6 * DECIMAL_PRECISION + 1).is_err());
+    }
+
+    #[test]
+    fn test_sunset_blocks_new_debt_but_keeps_accruing_interest() {
+        let (mut trove_manager, owner) = trove_manager();
+        trove_manager.open_trove(Pubkey::new_unique(), 10 * DECIMAL_PRECISION, 4 * DECIMAL_PRECISION, None, None).unwrap();
+        trove_manager.start_sunset(&owner).unwrap();
+        assert!(trove_manager.require_debt_capacity(1).is_err());
+
+        // A year later the existing debt has grown at the sunset rate and the interest is payable
+        trove_manager.last_active_index_update -= SECONDS_IN_YEAR as u64;
+        trove_manager.accrue_active_interests();
+        assert!(trove_manager.interest_payable() > 0);
+        assert_eq!(trove_manager.total_active_debt, 4 * DECIMAL_PRECISION + trove_manager.interest_payable());
+    }
+
+    fn tracked_input(trove_manager: &mut TroveManager, seed: u8, value: u64) -> TxIn {
+        let txid = [seed; 32];
+        let owner = trove_manager.owner;
+        trove_manager.add_utxo(owner, UtxoMeta { txid, vout: 0, value, script_pubkey: Vec::new() });
+        TxIn {
+            previous_output: bitcoin::OutPoint::new(bitcoin::Txid::from_byte_array(txid), 0),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_claim_collateral_requires_inputs_covering_the_surplus() {
+        let (mut trove_manager, _) = trove_manager();
+        let owner = Pubkey::new_unique();
+        trove_manager.account_collateral_surplus(owner, 1_000);
+
+        assert_eq!(trove_manager.claim_collateral(owner, Vec::new()), Err(ProtocolError::NoUtxosProvided));
+
+        let untracked = TxIn {
+            previous_output: bitcoin::OutPoint::new(bitcoin::Txid::from_byte_array([9; 32]), 0),
+            ..Default::default()
+        };
+        assert_eq!(trove_manager.claim_collateral(owner, vec![untracked]), Err(ProtocolError::InvalidUtxoOwnership));
+
+        let inputs = vec![tracked_input(&mut trove_manager, 1, 400), tracked_input(&mut trove_manager, 2, 500)];
+        assert_eq!(
+            trove_manager.claim_collateral(owner, inputs),
+            Err(ProtocolError::InsufficientUtxoValue { required: 1_000, provided: 900 })
+        );
+
+        // Rejected claims leave the surplus and the UTXOs in place
+        assert_eq!(trove_manager.surplus_of(owner), 1_000);
+        assert_eq!(trove_manager.utxo_value(trove_manager.owner, [1; 32], 0), Some(400));
+        assert_eq!(trove_manager.claim_collateral(Pubkey::new_unique(), Vec::new()), Err(ProtocolError::NothingToClaim));
+    }
+
+    #[test]
+    fn test_claim_collateral_rejects_surplus_above_the_utxo_range() {
+        let (mut trove_manager, _) = trove_manager();
+        let owner = Pubkey::new_unique();
+        trove_manager.account_collateral_surplus(owner, u64::MAX as Balance + 1);
+
+        let inputs = vec![tracked_input(&mut trove_manager, 1, u64::MAX)];
+        assert_eq!(trove_manager.claim_collateral(owner, inputs), Err(ProtocolError::UtxoValueOverflow));
+    }
+
+    #[test]
+    fn test_liquidator_gas_compensation_is_kept_apart_from_surpluses() {
+        let (mut trove_manager, _) = trove_manager();
+        let mut debt_token = DebtToken::new("Babel USD".to_string(), "bUSD".to_string(), Pubkey::new_unique(), Pubkey::new_unique(), GAS_COMPENSATION);
+        let liquidator = Pubkey::new_unique();
+        let account_info = AccountInfo::new(&trove_manager.owner);
+
+        trove_manager.account_collateral_surplus(liquidator, 700);
+        trove_manager.send_gas_compensation(&mut debt_token, liquidator, 0, 300, &account_info).unwrap();
+        assert_eq!(trove_manager.surplus_of(liquidator), 700);
+        assert_eq!(trove_manager.coll_gas_compensation_of(liquidator), 300);
+    }
+
+    fn deployment_params() -> DeploymentParams {
+        DeploymentParams {
+            minute_decay_factor: 999_037_758_833_783_000,
+            redemption_fee_floor: DECIMAL_PRECISION / 200,
+            max_redemption_fee: DECIMAL_PRECISION,
+            borrowing_fee_floor: DECIMAL_PRECISION / 200,
+            max_borrowing_fee: DECIMAL_PRECISION / 20,
+            interest_rate_in_bps: 100,
+            max_debt: 1_000 * DECIMAL_PRECISION,
+            mcr: 1_200_000_000_000_000_000,
+            price_source: PriceSource::Spot,
+            twap_window_secs: 0,
+        }
+    }
+
+    #[test]
+    fn test_set_parameters_rejects_out_of_range_values() {
+        let (mut trove_manager, owner) = trove_manager();
+        let cases: Vec<(fn(&mut DeploymentParams), ProtocolError)> = vec![
+            (|p| p.minute_decay_factor = MIN_MINUTE_DECAY_FACTOR - 1, ProtocolError::MinuteDecayFactorOutOfRange),
+            (|p| p.minute_decay_factor = MAX_MINUTE_DECAY_FACTOR + 1, ProtocolError::MinuteDecayFactorOutOfRange),
+            (|p| p.redemption_fee_floor = p.max_redemption_fee + 1, ProtocolError::InvalidRedemptionFeeBounds),
+            (|p| p.max_redemption_fee = DECIMAL_PRECISION + 1, ProtocolError::InvalidRedemptionFeeBounds),
+            (|p| p.borrowing_fee_floor = p.max_borrowing_fee + 1, ProtocolError::InvalidBorrowingFeeBounds),
+            (|p| p.max_borrowing_fee = DECIMAL_PRECISION + 1, ProtocolError::InvalidBorrowingFeeBounds),
+            (|p| p.interest_rate_in_bps = MAX_INTEREST_RATE_IN_BPS + 1, ProtocolError::InterestRateTooHigh),
+            (|p| p.mcr = MCR - 1, ProtocolError::InvalidMcr),
+            (|p| p.mcr = BabelBase::CCR + 1, ProtocolError::InvalidMcr),
+            (|p| p.price_source = PriceSource::Twap, ProtocolError::TwapWindowNotSet),
+        ];
+        for (mutate, expected) in cases {
+            let mut params = deployment_params();
+            mutate(&mut params);
+            assert_eq!(trove_manager.set_parameters(&owner, &params), Err(expected));
+        }
+        // Rejected parameters leave the trove manager untouched
+        assert_eq!(trove_manager.mcr(), MCR);
+
+        // The bounds themselves are accepted
+        let mut params = deployment_params();
+        params.minute_decay_factor = MIN_MINUTE_DECAY_FACTOR;
+        params.redemption_fee_floor = params.max_redemption_fee;
+        params.mcr = BabelBase::CCR;
+        params.interest_rate_in_bps = MAX_INTEREST_RATE_IN_BPS;
+        assert!(trove_manager.set_parameters(&owner, &params).is_ok());
+        assert_eq!(trove_manager.mcr(), BabelBase::CCR);
+        assert_eq!(trove_manager.max_system_debt, params.max_debt);
+
+        trove_manager.start_sunset(&owner).unwrap();
+        assert_eq!(trove_manager.set_parameters(&owner, &deployment_params()), Err(ProtocolError::CollateralSunsetting));
+    }
+
+    #[test]
+    fn test_governance_setters_reject_other_callers() {
+        let (mut trove_manager, _) = trove_manager();
+        let stranger = Pubkey::new_unique();
+        assert_eq!(trove_manager.set_parameters(&stranger, &deployment_params()), Err(ProtocolError::Unauthorized));
+        assert_eq!(trove_manager.set_max_system_debt(&stranger, 0), Err(ProtocolError::Unauthorized));
+        assert_eq!(trove_manager.set_interest_rate(&stranger, 0), Err(ProtocolError::Unauthorized));
+        assert_eq!(trove_manager.start_sunset(&stranger), Err(ProtocolError::Unauthorized));
+
+        // Nothing changed
+        assert_eq!(trove_manager.max_system_debt(), 1_000 * DECIMAL_PRECISION);
+        assert_eq!(trove_manager.mcr(), MCR);
+        assert!(!trove_manager.is_sunsetting());
+    }
+}
@@ -18,6 +18,7 @@ use arch_program::{
 
 };
 use bitcoin::{self, Transaction}; // Import the bitcoin crate and Transaction struct
+use crate::dependencies::protocol_error::ProtocolError;
 
 
 
@@ -31,8 +32,11 @@ impl BabelOwnable {
         Self { owner }
     }
 
-    fn only_owner(&self, caller: AccountId) {
-        assert_eq!(caller, self.owner, "Only owner can call this function");
+    fn only_owner(&self, caller: AccountId) -> Result<(), ProtocolError> {
+        if caller != self.owner {
+            return Err(ProtocolError::Unauthorized);
+        }
+        Ok(())
     }
 }
 
@@ -61,16 +65,19 @@ impl EmissionSchedule {
         weekly_pct: u64,
         scheduled_weekly_pct: Vec<(u64, u64)>,
         utxos: Vec<UtxoMeta>, // Add UTXO metadata
-    ) -> Self {
-        assert!(initial_lock_weeks <= MAX_LOCK_WEEKS, "Cannot exceed MAX_LOCK_WEEKS");
-        assert!(lock_decay_weeks > 0, "Decay weeks cannot be 0");
-        assert!(weekly_pct <= MAX_PCT, "Cannot exceed MAX_PCT");
+    ) -> Result<Self, ProtocolError> {
+        if initial_lock_weeks > MAX_LOCK_WEEKS {
+            return Err(ProtocolError::InvalidLockWeeks);
+        }
+        if lock_decay_weeks == 0 || weekly_pct > MAX_PCT {
+            return Err(ProtocolError::InvalidEmissionSchedule);
+        }
 
         // Use Arch SDK to validate the owner account
         let owner_info = AccountInfo::new(&owner);
-        assert!(validate_utxo_ownership(&owner_info).is_ok(), "Invalid owner account");
+        validate_utxo_ownership(&owner_info).map_err(|_| ProtocolError::InvalidUtxoOwnership)?;
 
-        Self {
+        Ok(Self {
             owner,
             system_start,
             vault,
@@ -81,15 +88,17 @@ impl EmissionSchedule {
             scheduled_weekly_pct: scheduled_weekly_pct.into_iter().collect(),
             babel_ownable: BabelOwnable::new(owner),
             utxos, // Initialize UTXOs
-        }
+        })
     }
 
-    pub fn set_weekly_pct_schedule(&mut self, caller: Pubkey, schedule: Vec<(u64, u64)>) {
-        self.babel_ownable.only_owner(caller);
+    pub fn set_weekly_pct_schedule(&mut self, caller: Pubkey, schedule: Vec<(u64, u64)>) -> Result<(), ProtocolError> {
+        self.babel_ownable.only_owner(caller)?;
         let mut last_week = u64::MAX;
         for &(week, pct) in &schedule {
-            assert!(week < last_week, "Must sort by week descending");
-            assert!(pct <= MAX_PCT, "Cannot exceed MAX_PCT");
+            // Must sort by week descending
+            if week >= last_week || pct > MAX_PCT {
+                return Err(ProtocolError::InvalidEmissionSchedule);
+            }
             last_week = week;
         }
         self.scheduled_weekly_pct = schedule.into_iter().collect();
@@ -97,19 +106,20 @@ impl EmissionSchedule {
 
         // Use Arch SDK to validate the caller account
         let caller_info = AccountInfo::new(&caller);
-        assert!(validate_utxo_ownership(&caller_info).is_ok(), "Invalid caller account");
+        validate_utxo_ownership(&caller_info).map_err(|_| ProtocolError::InvalidUtxoOwnership)?;
+        Ok(())
     }
 
 
         // Validate UTXO ownership
         for utxo in &self.utxos {
             let utxo_info = AccountInfo::new(&utxo.pubkey);
-            assert!(validate_utxo_ownership(&utxo_info).is_ok(), "Invalid UTXO ownership");
+            validate_utxo_ownership(&utxo_info).map_err(|_| ProtocolError::InvalidUtxoOwnership)?;
         }
 
         // Use Arch SDK to validate the caller account
         let caller_info = AccountInfo::new(&caller);
-        assert!(validate_utxo_ownership(&caller_info).is_ok(), "Invalid caller account");
+        validate_utxo_ownership(&caller_info).map_err(|_| ProtocolError::InvalidUtxoOwnership)?;
 
         // Create a state transition transaction
         let mut tx = get_state_transition_tx(&[caller_info]);
@@ -122,21 +132,23 @@ impl EmissionSchedule {
         set_transaction_to_sign(&[caller_info], tx);
     }
 
-    pub fn lock(&mut self, caller: Pubkey, weeks: u64) {
-        self.babel_ownable.only_owner(caller);
-        assert!(weeks <= MAX_LOCK_WEEKS, "Lock duration exceeds maximum allowed weeks");
+    pub fn lock(&mut self, caller: Pubkey, weeks: u64) -> Result<(), ProtocolError> {
+        self.babel_ownable.only_owner(caller)?;
+        if weeks > MAX_LOCK_WEEKS {
+            return Err(ProtocolError::InvalidLockWeeks);
+        }
         self.lock_weeks = weeks;
 
 
         // Validate UTXO ownership
         for utxo in &self.utxos {
             let utxo_info = AccountInfo::new(&utxo.pubkey);
-            assert!(validate_utxo_ownership(&utxo_info).is_ok(), "Invalid UTXO ownership");
+            validate_utxo_ownership(&utxo_info).map_err(|_| ProtocolError::InvalidUtxoOwnership)?;
         }
 
         // Use Arch SDK to validate the caller account
         let caller_info = AccountInfo::new(&caller);
-        assert!(validate_utxo_ownership(&caller_info).is_ok(), "Invalid caller account");
+        validate_utxo_ownership(&caller_info).map_err(|_| ProtocolError::InvalidUtxoOwnership)?;
 
         // Create a state transition transaction
         let mut tx = get_state_transition_tx(&[caller_info]);
@@ -150,23 +162,24 @@ impl EmissionSchedule {
 
         // Use Arch SDK to validate the caller account
         let caller_info = AccountInfo::new(&caller);
-        assert!(validate_utxo_ownership(&caller_info).is_ok(), "Invalid caller account");
+        validate_utxo_ownership(&caller_info).map_err(|_| ProtocolError::InvalidUtxoOwnership)?;
+        Ok(())
     }
 
-    pub fn unlock(&mut self, caller: Pubkey) {
-        self.babel_ownable.only_owner(caller);
+    pub fn unlock(&mut self, caller: Pubkey) -> Result<(), ProtocolError> {
+        self.babel_ownable.only_owner(caller)?;
         self.lock_weeks = 0;
 
 
         // Validate UTXO ownership
         for utxo in &self.utxos {
             let utxo_info = AccountInfo::new(&utxo.pubkey);
-            assert!(validate_utxo_ownership(&utxo_info).is_ok(), "Invalid UTXO ownership");
+            validate_utxo_ownership(&utxo_info).map_err(|_| ProtocolError::InvalidUtxoOwnership)?;
         }
 
         // Use Arch SDK to validate the caller account
         let caller_info = AccountInfo::new(&caller);
-        assert!(validate_utxo_ownership(&caller_info).is_ok(), "Invalid caller account");
+        validate_utxo_ownership(&caller_info).map_err(|_| ProtocolError::InvalidUtxoOwnership)?;
 
         // Create a state transition transaction
         let mut tx = get_state_transition_tx(&[caller_info]);
@@ -177,6 +190,7 @@ impl EmissionSchedule {
 
         // Set the transaction to sign
         set_transaction_to_sign(&[caller_info], tx);
+        Ok(())
     }
 
     fn create_transaction_bytes(&self, amount: u64) -> Vec<u8> {
@@ -190,13 +204,14 @@ impl EmissionSchedule {
     }
 
     // function to demonstrate usage of bitcoin::Transaction
-    pub fn process_transaction(&self, tx: Transaction) {
+    pub fn process_transaction(&self, tx: Transaction) -> Result<(), ProtocolError> {
         // Process the Bitcoin transaction using the Arch SDK
         let tx_info = get_bitcoin_tx(&tx);
         // Further processing logic here...
         // Use Arch SDK to validate the caller account
         let caller_info = AccountInfo::new(&caller);
-        assert!(validate_utxo_ownership(&caller_info).is_ok(), "Invalid caller account");
+        validate_utxo_ownership(&caller_info).map_err(|_| ProtocolError::InvalidUtxoOwnership)?;
+        Ok(())
     }
 }
 
@@ -4,6 +4,8 @@ use std::collections::HashMap;
 use std::time::{SystemTime, UNIX_EPOCH};
 use crate::interfaces::token_locker::ITokenLocker;
 use crate::interfaces::babel_core::BabelCore;
+use crate::dependencies::protocol_event::{AdminAction, ProtocolEvent};
+use crate::dependencies::protocol_error::ProtocolError;
 use borsh::{BorshDeserialize, BorshSerialize}; // Add Borsh imports
 use bitcoin::{self, Transaction}; // Import bitcoin crate and Transaction struct
 
@@ -86,24 +88,24 @@ impl AdminVoting {
         }
     }
 
-    pub fn create_new_proposal(&mut self, account: String, payload: Vec<Action>) {
+    pub fn create_new_proposal(&mut self, account: String, payload: Vec<Action>) -> Result<(), ProtocolError> {
         let current_time = self.get_current_time();
         let last_proposal_time = *self.latest_proposal_timestamp.get(&account).unwrap_or(&0);
 
         if current_time <= last_proposal_time + Self::min_time_between_proposals() {
-            panic!("Minimum time between proposals not met");
+            return Err(ProtocolError::ProposalTooSoon);
         }
 
         let week = self.system_start; 
         if week == 0 {
-            panic!("No proposals in the first week");
+            return Err(ProtocolError::ProposalsNotOpen);
         }
 
         let account_weight = 1000; // Placeholder for actual weight fetching logic
         let min_weight = 500; // Placeholder for actual minimum weight calculation
 
         if account_weight < min_weight {
-            panic!("Not enough weight to propose");
+            return Err(ProtocolError::InsufficientVotingWeight);
         }
 
         let proposal_id = self.proposal_data.len() as u32;
@@ -118,40 +120,46 @@ impl AdminVoting {
 
         self.proposal_data.insert(proposal_id, new_proposal);
         self.proposal_payloads.insert(proposal_id, payload);
+        ProtocolEvent::Admin { caller: account.clone(), action: AdminAction::ProposalCreated { proposal_id } }.emit();
         self.latest_proposal_timestamp.insert(account, current_time);
+        Ok(())
     }
 
-    pub fn vote_for_proposal(&mut self, account: String, proposal_id: u32, weight: u64) {
-        let proposal = self.proposal_data.get_mut(&proposal_id).expect("Invalid proposal ID");
+    pub fn vote_for_proposal(&mut self, account: String, proposal_id: u32, weight: u64) -> Result<(), ProtocolError> {
+        let proposal = self.proposal_data.get_mut(&proposal_id).ok_or(ProtocolError::InvalidParameter)?;
 
         if proposal.processed {
-            panic!("Proposal already processed");
+            return Err(ProtocolError::ProposalAlreadyProcessed);
         }
 
         let current_time = Self::get_current_time();
         if current_time > proposal.created_at + Self::voting_period() {
-            panic!("Voting period has closed");
+            return Err(ProtocolError::VotingClosed);
         }
 
         let current_weight = self.account_vote_weights.entry((account.clone(), proposal_id)).or_insert(0);
         if *current_weight > 0 {
-            panic!("Already voted");
+            return Err(ProtocolError::AlreadyVoted);
         }
 
         *current_weight = weight;
         proposal.current_weight += weight;
+        ProtocolEvent::ProposalVoted { account, proposal_id, weight }.emit();
 
         if proposal.current_weight >= proposal.required_weight {
             proposal.can_execute_after = current_time + Self::min_time_to_execution();
         }
+        Ok(())
     }
 
-    pub fn execute_proposal(&mut self, proposal_id: u32) {
-        let proposal = self.proposal_data.get_mut(&proposal_id).expect("Invalid proposal ID");
+    pub fn execute_proposal(&mut self, proposal_id: u32) -> Result<(), ProtocolError> {
+        let proposal = self.proposal_data.get_mut(&proposal_id).ok_or(ProtocolError::InvalidParameter)?;
 
         let current_time = Self::get_current_time();
         if !proposal.processed && proposal.can_execute_after != 0 && proposal.can_execute_after <= current_time && current_time <= proposal.can_execute_after + Self::max_time_to_execution() {
             proposal.processed = true;
+            // Execution is permissionless, so no caller is recorded
+            ProtocolEvent::Admin { caller: String::new(), action: AdminAction::ProposalExecuted { proposal_id } }.emit();
             // Execute the actions associated with the proposal
             let actions = self.proposal_payloads.get(&proposal_id).unwrap();
             for action in actions {
@@ -195,8 +203,9 @@ impl AdminVoting {
                 }
             }
         } else {
-            panic!("Proposal cannot be executed yet");
+            return Err(ProtocolError::ProposalNotExecutable);
         }
+        Ok(())
     }
 
     fn min_time_between_proposals() -> u64 {
@@ -31,6 +31,7 @@ use arch_program::{
     transaction_to_sign::TransactionToSign,
     utxo::UtxoMeta,
 };
+use crate::dependencies::protocol_error::ProtocolError;
 
 #[derive(BorshSerialize, BorshDeserialize)]
 struct AirdropDistributor {
@@ -54,16 +55,24 @@ impl AirdropDistributor {
         }
     }
 
-    pub fn set_merkle_root(&mut self, merkle_root: Vec<u8>) {
-        assert!(self.merkle_root.is_none(), "Merkle root already set");
+    pub fn set_merkle_root(&mut self, merkle_root: Vec<u8>) -> Result<(), ProtocolError> {
+        if self.merkle_root.is_some() {
+            return Err(ProtocolError::MerkleRootAlreadySet);
+        }
         self.merkle_root = Some(merkle_root);
         self.can_claim_until = Some(Self::current_timestamp() + 7889231); // Simulate CLAIM_DURATION
         // Emit event here
+        Ok(())
     }
 
     // Updated claim method with UTXO handling for Archnetwork
-    pub fn claim(&mut self, index: u32, claimant: Pubkey, receiver: Pubkey, amount: u64, merkle_proof: Vec<Vec<u8>>, accounts: &[AccountInfo], utxo_meta: UtxoMeta) { // Change to Pubkey
-        assert!(self.is_claim_period_active() && !self.is_claimed(index), "Claim period has ended or already claimed");
+    pub fn claim(&mut self, index: u32, claimant: Pubkey, receiver: Pubkey, amount: u64, merkle_proof: Vec<Vec<u8>>, accounts: &[AccountInfo], utxo_meta: UtxoMeta) -> Result<(), ProtocolError> { // Change to Pubkey
+        if !self.is_claim_period_active() {
+            return Err(ProtocolError::ClaimPeriodEnded);
+        }
+        if self.is_claimed(index) {
+            return Err(ProtocolError::AlreadyClaimed);
+        }
         if self.verify_merkle_proof(index, &claimant, amount, &merkle_proof) {
             // Validate UTXO ownership
             validate_utxo_ownership(&utxo_meta, &claimant)?;
@@ -107,8 +116,9 @@ impl AirdropDistributor {
 
             set_transaction_to_sign(accounts, tx_to_sign);
         } else {
-            panic!("Invalid merkle proof");
+            return Err(ProtocolError::InvalidMerkleProof);
         }
+        Ok(())
     }
 
     fn create_transaction_bytes(&self, amount: u64) -> Vec<u8> {
@@ -3,6 +3,7 @@
 use std::collections::HashMap;
 use crate::interfaces::token_locker::{ITokenLocker, LockData};
 use crate::dependencies::babel_ownable::BabelOwnable;
+use crate::dependencies::protocol_error::ProtocolError;
 use borsh::{BorshDeserialize, BorshSerialize};
 use bitcoin::{self, Transaction};  // Importing bitcoin crate
 use archnetwork::transaction_to_sign::TransactionToSign;  // Importing TransactionToSign
@@ -41,31 +42,15 @@ pub struct AllocationVesting {
     utxos: HashMap<OutPoint, UtxoMeta>,  // Add UTXO management
 }
 
-#[derive(Debug)]
-pub enum ContractError {
-    NothingToClaim,
-    CannotLock,
-    WrongMaxTotalPreclaimPct,
-    PreclaimTooLarge,
-    AllocationsMismatch,
-    ZeroTotalAllocation,
-    ZeroAllocation,
-    ZeroNumberOfWeeks,
-    DuplicateAllocation,
-    InsufficientPoints,
-    LockedAllocation,
-    IllegalVestingStart,
-    VestingAlreadyStarted,
-    IncompatibleVestingPeriod,
-    UtxoNotFound,
-    InvalidUtxo,
-}
-
 impl AllocationVesting {
-    pub fn new(total_allocation: u128, max_total_preclaim_pct: u32, owner: Pubkey, token: Box<dyn ERC20Trait>, token_locker: Box<dyn ITokenLocker>) -> Self {
-        assert!(total_allocation > 0);
-        assert!(max_total_preclaim_pct <= 20);
-        Self {
+    pub fn new(total_allocation: u128, max_total_preclaim_pct: u32, owner: Pubkey, token: Box<dyn ERC20Trait>, token_locker: Box<dyn ITokenLocker>) -> Result<Self, ProtocolError> {
+        if total_allocation == 0 {
+            return Err(ProtocolError::ZeroTotalAllocation);
+        }
+        if max_total_preclaim_pct > 20 {
+            return Err(ProtocolError::WrongMaxTotalPreclaimPct);
+        }
+        Ok(Self {
             allocations: HashMap::new(),
             max_total_preclaim_pct,
             total_allocation,
@@ -74,31 +59,31 @@ impl AllocationVesting {
             token,
             token_locker,
             utxos: HashMap::new(),
-        }
+        })
     }
 
-    pub fn set_allocations(&mut self, allocation_splits: Vec<AllocationSplit>, vesting_start: u64) -> Result<(), ContractError> {
+    pub fn set_allocations(&mut self, allocation_splits: Vec<AllocationSplit>, vesting_start: u64) -> Result<(), ProtocolError> {
         if self.vesting_start.is_some() {
-            return Err(ContractError::VestingAlreadyStarted);
+            return Err(ProtocolError::VestingAlreadyStarted);
         }
         self.vesting_start = Some(vesting_start);
 
         let mut total_points = 0;
         for split in &allocation_splits {
             if split.points == 0 {
-                return Err(ContractError::ZeroAllocation);
+                return Err(ProtocolError::ZeroAllocation);
             }
             if split.number_of_weeks == 0 {
-                return Err(ContractError::ZeroNumberOfWeeks);
+                return Err(ProtocolError::ZeroNumberOfWeeks);
             }
             if self.allocations.contains_key(&split.recipient) {
-                return Err(ContractError::DuplicateAllocation);
+                return Err(ProtocolError::DuplicateAllocation);
             }
             total_points += split.points;
         }
 
         if total_points == 0 {
-            return Err(ContractError::InsufficientPoints);
+            return Err(ProtocolError::InsufficientPoints);
         }
 
         for split in allocation_splits {
@@ -116,8 +101,8 @@ impl AllocationVesting {
         Ok(())
     }
 
-    pub fn transfer_points(&mut self, from: &Pubkey, to: &Pubkey, points: u32) -> Result<(), ContractError> {
-        let from_allocation = self.allocations.get(from).ok_or(ContractError::NothingToClaim)?;
+    pub fn transfer_points(&mut self, from: &Pubkey, to: &Pubkey, points: u32) -> Result<(), ProtocolError> {
+        let from_allocation = self.allocations.get(from).ok_or(ProtocolError::NothingToClaim)?;
         let to_allocation = self.allocations.get(to).unwrap_or(&AllocationState {
             points: 0,
             number_of_weeks: 0,
@@ -126,20 +111,20 @@ impl AllocationVesting {
         });
 
         if to_allocation.number_of_weeks != 0 && to_allocation.number_of_weeks != from_allocation.number_of_weeks {
-            return Err(ContractError::IncompatibleVestingPeriod);
+            return Err(ProtocolError::IncompatibleVestingPeriod);
         }
 
         let total_vested = self.vested_at(self.current_timestamp(), from_allocation.points, from_allocation.number_of_weeks);
         if total_vested < from_allocation.claimed {
-            return Err(ContractError::LockedAllocation);
+            return Err(ProtocolError::LockedAllocation);
         }
 
         if points == 0 {
-            return Err(ContractError::ZeroAllocation);
+            return Err(ProtocolError::ZeroAllocation);
         }
 
         if from_allocation.points < points {
-            return Err(ContractError::InsufficientPoints);
+            return Err(ProtocolError::InsufficientPoints);
         }
 
         let claimed = self.claim(from)?;
@@ -162,14 +147,14 @@ impl AllocationVesting {
         Ok(())
     }
 
-    pub fn lock_future_claims(&mut self, account: &Pubkey, amount: u128) -> Result<(), ContractError> {
+    pub fn lock_future_claims(&mut self, account: &Pubkey, amount: u128) -> Result<(), ProtocolError> {
         self.lock_future_claims_with_receiver(account, account, amount)
     }
 
-    pub fn lock_future_claims_with_receiver(&mut self, account: &Pubkey, receiver: &Pubkey, amount: u128) -> Result<(), ContractError> {
-        let allocation = self.allocations.get(account).ok_or(ContractError::CannotLock)?;
+    pub fn lock_future_claims_with_receiver(&mut self, account: &Pubkey, receiver: &Pubkey, amount: u128) -> Result<(), ProtocolError> {
+        let allocation = self.allocations.get(account).ok_or(ProtocolError::CannotLock)?;
         if allocation.points == 0 || self.vesting_start.is_none() {
-            return Err(ContractError::CannotLock);
+            return Err(ProtocolError::CannotLock);
         }
 
         let claimed_updated = if self.claimable_at(self.current_timestamp(), allocation.points, allocation.claimed, allocation.number_of_weeks) > 0 {
@@ -188,7 +173,7 @@ impl AllocationVesting {
             left_to_preclaim.min(unclaimed)
         } else {
             if preclaimed + amount > max_total_preclaim || amount > unclaimed {
-                return Err(ContractError::PreclaimTooLarge);
+                return Err(ProtocolError::PreclaimTooLarge);
             }
             amount
         };
@@ -204,19 +189,19 @@ impl AllocationVesting {
         Ok(())
     }
 
-    pub fn claim(&mut self, account: &Pubkey) -> Result<u128, ContractError> {
-        let allocation = self.allocations.get(account).ok_or(ContractError::NothingToClaim)?;
+    pub fn claim(&mut self, account: &Pubkey) -> Result<u128, ProtocolError> {
+        let allocation = self.allocations.get(account).ok_or(ProtocolError::NothingToClaim)?;
         self._claim(account, allocation.points, allocation.claimed, allocation.number_of_weeks)
     }
 
-    fn _claim(&mut self, account: &Pubkey, points: u32, claimed: u128, number_of_weeks: u8) -> Result<u128, ContractError> {
+    fn _claim(&mut self, account: &Pubkey, points: u32, claimed: u128, number_of_weeks: u8) -> Result<u128, ProtocolError> {
         if points == 0 {
-            return Err(ContractError::NothingToClaim);
+            return Err(ProtocolError::NothingToClaim);
         }
 
         let claimable = self.claimable_at(self.current_timestamp(), points, claimed, number_of_weeks);
         if claimable == 0 {
-            return Err(ContractError::NothingToClaim);
+            return Err(ProtocolError::NothingToClaim);
         }
 
         let claimed_updated = claimed + claimable;
@@ -308,9 +293,9 @@ impl AllocationVesting {
         self.utxos.insert(outpoint, utxo);
     }
 
-    pub fn spend_utxo(&mut self, outpoint: OutPoint) -> Result<(), ContractError> {
+    pub fn spend_utxo(&mut self, outpoint: OutPoint) -> Result<(), ProtocolError> {
         if self.utxos.remove(&outpoint).is_none() {
-            return Err(ContractError::UtxoNotFound);
+            return Err(ProtocolError::UtxoNotFound);
         }
         Ok(())
     }
@@ -326,7 +311,7 @@ impl AllocationVesting {
 
 fn main() {
     let owner_pubkey = Pubkey::new_unique();
-    let mut vesting = AllocationVesting::new(1000000, 20, owner_pubkey, Box::new(ERC20Trait::default()), Box::new(ITokenLocker::default()));
+    let mut vesting = AllocationVesting::new(1000000, 20, owner_pubkey, Box::new(ERC20Trait::default()), Box::new(ITokenLocker::default())).unwrap();
     println!("{:?}", vesting);
 }
 
@@ -340,7 +325,7 @@ mod tests {
         let owner = Pubkey::new_unique();
         let token = Box::new(MockERC20Trait);
         let token_locker = Box::new(MockITokenLocker);
-        let vesting = AllocationVesting::new(1000000, 20, owner, token, token_locker);
+        let vesting = AllocationVesting::new(1000000, 20, owner, token, token_locker).unwrap();
 
         assert_eq!(vesting.total_allocation, 1000000);
         assert_eq!(vesting.max_total_preclaim_pct, 20);
@@ -350,7 +335,7 @@ mod tests {
     #[test]
     fn test_set_allocations() {
         let owner = Pubkey::new_unique();
-        let mut vesting = AllocationVesting::new(1000000, 20, owner, Box::new(MockERC20Trait), Box::new(MockITokenLocker));
+        let mut vesting = AllocationVesting::new(1000000, 20, owner, Box::new(MockERC20Trait), Box::new(MockITokenLocker)).unwrap();
         
         let allocation_splits = vec![
             AllocationSplit { recipient: Pubkey::new_unique(), points: 100, number_of_weeks: 10 },
@@ -364,7 +349,7 @@ mod tests {
     #[test]
     fn test_transfer_points() {
         let owner = Pubkey::new_unique();
-        let mut vesting = AllocationVesting::new(1000000, 20, owner, Box::new(MockERC20Trait), Box::new(MockITokenLocker));
+        let mut vesting = AllocationVesting::new(1000000, 20, owner, Box::new(MockERC20Trait), Box::new(MockITokenLocker)).unwrap();
         
         let from = Pubkey::new_unique();
         let to = Pubkey::new_unique();
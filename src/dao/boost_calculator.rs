@@ -3,6 +3,7 @@
 use std::collections::HashMap;
 use std::time::{SystemTime, UNIX_EPOCH};
 use crate::dependencies::system_start::SystemStart; // Import SystemStart
+use crate::dependencies::protocol_error::ProtocolError;
 use borsh::{BorshDeserialize, BorshSerialize}; // Import Borsh traits
 use bitcoin::{self, Transaction}; // Import bitcoin crate and Transaction struct
 use archnetwork::transaction_to_sign::TransactionToSign; // Import TransactionToSign
@@ -106,9 +107,9 @@ impl BoostCalculator {
         self.utxos.insert(outpoint, utxo_meta);
     }
 
-    pub fn spend_utxo(&mut self, outpoint: OutPoint) -> Result<(), ProgramError> {
+    pub fn spend_utxo(&mut self, outpoint: OutPoint) -> Result<(), ProtocolError> {
         if self.utxos.remove(&outpoint).is_none() {
-            return Err(ProgramError::Custom(502)); // UTXO not found
+            return Err(ProtocolError::UtxoNotFound);
         }
         Ok(())
     }
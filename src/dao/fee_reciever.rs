@@ -14,6 +14,7 @@ use arch_program::{
     msg,
     utxo::UtxoMeta,
 };
+use crate::dependencies::protocol_error::ProtocolError;
 
 #[derive(BorshSerialize, BorshDeserialize)]
 struct Token {
@@ -29,10 +30,10 @@ impl Token {
         }
     }
 
-    fn transfer(&mut self, from: Pubkey, to: Pubkey, amount: Balance) -> Result<(), String> {
+    fn transfer(&mut self, from: Pubkey, to: Pubkey, amount: Balance) -> Result<(), ProtocolError> {
         let from_balance = *self.balances.get(&from).unwrap_or(&0);
         if from_balance < amount {
-            return Err("Insufficient balance".to_string());
+            return Err(ProtocolError::InsufficientBalance);
         }
         self.balances.insert(from, from_balance - amount);
         let to_balance = *self.balances.get(&to).unwrap_or(&0);
@@ -63,18 +64,18 @@ impl FeeReceiver {
         }
     }
 
-    fn transfer_ownership(&mut self, new_owner: Pubkey) -> Result<(), String> {
+    fn transfer_ownership(&mut self, new_owner: Pubkey) -> Result<(), ProtocolError> {
         self.only_owner()?;
         self.owner = new_owner;
         Ok(())
     }
 
-    fn transfer_token(&mut self, token_id: String, receiver: Pubkey, amount: Balance) -> Result<(), String> {
+    fn transfer_token(&mut self, token_id: String, receiver: Pubkey, amount: Balance) -> Result<(), ProtocolError> {
         self.only_owner()?;
-        let token = self.tokens.get_mut(&token_id).ok_or("Token not found".to_string())?;
+        let token = self.tokens.get_mut(&token_id).ok_or(ProtocolError::UnknownToken)?;
         let from_balance = *token.balances.get(&self.owner).unwrap_or(&0);
         if from_balance < amount {
-            return Err("Insufficient balance".to_string());
+            return Err(ProtocolError::InsufficientBalance);
         }
         let to_balance = *token.balances.get(&receiver).unwrap_or(&0);
         token.balances.insert(self.owner, from_balance - amount);
@@ -82,14 +83,14 @@ impl FeeReceiver {
         Ok(())
     }
 
-    fn set_token_approval(&mut self, token_id: String, spender: Pubkey, amount: Balance) -> Result<(), String> {
+    fn set_token_approval(&mut self, token_id: String, spender: Pubkey, amount: Balance) -> Result<(), ProtocolError> {
         self.only_owner()?;
-        let token = self.tokens.get_mut(&token_id).ok_or("Token not found".to_string())?;
+        let token = self.tokens.get_mut(&token_id).ok_or(ProtocolError::UnknownToken)?;
         token.allowances.insert((self.owner, spender), amount);
         Ok(())
     }
 
-    fn process_bitcoin_transaction(&mut self, tx_id: String, transaction: Transaction) -> Result<(), String> {
+    fn process_bitcoin_transaction(&mut self, tx_id: String, transaction: Transaction) -> Result<(), ProtocolError> {
         self.only_owner()?;
         
         let tx_bytes = self.create_transaction_bytes(&transaction);
@@ -110,9 +111,9 @@ impl FeeReceiver {
         println!("Signing transaction with inputs: {:?}", transaction.inputs_to_sign);
     }
 
-    fn only_owner(&self) -> Result<(), String> {
+    fn only_owner(&self) -> Result<(), ProtocolError> {
         if self.env_caller() != self.owner {
-            return Err("Caller is not owner".to_string());
+            return Err(ProtocolError::Unauthorized);
         }
         Ok(())
     }
@@ -132,13 +133,13 @@ impl FeeReceiver {
         self.utxos.insert(outpoint, utxo_meta);
     }
 
-    fn spend_utxo(&mut self, outpoint: OutPoint) -> Result<(), String> {
+    fn spend_utxo(&mut self, outpoint: OutPoint) -> Result<(), ProtocolError> {
         self.only_owner()?;
-        self.utxos.remove(&outpoint).ok_or("UTXO not found".to_string())?;
+        self.utxos.remove(&outpoint).ok_or(ProtocolError::UtxoNotFound)?;
         Ok(())
     }
 
-    fn process_arch_transaction(&mut self, tx_id: String, transaction: Transaction) -> Result<(), String> {
+    fn process_arch_transaction(&mut self, tx_id: String, transaction: Transaction) -> Result<(), ProtocolError> {
         self.only_owner()?;
         
         msg!("Processing Arch transaction with ID: {}", tx_id);
@@ -4,6 +4,8 @@ use core::collections::BTreeMap;
 use borsh::{BorshDeserialize, BorshSerialize};
 use bitcoin::{self, Transaction}; // Import bitcoin crate and Transaction struct
 use archnetwork::transaction_to_sign::TransactionToSign; // Import TransactionToSign
+use crate::dependencies::protocol_event::ProtocolEvent;
+use crate::dependencies::protocol_error::ProtocolError;
 
 // Import Arch SDK modules
 use arch_program::{
@@ -133,7 +135,7 @@ impl IncentiveVoting {
         }
     }
 
-    pub fn register_account_weight(&mut self, account: Pubkey, min_weeks: u64) {
+    pub fn register_account_weight(&mut self, account: Pubkey, min_weeks: u64) -> Result<(), ProtocolError> {
         // Ensure caller or delegated
         // Get lock data
         let account_data = self.account_lock_data.get_mut(&account).unwrap();
@@ -149,7 +151,7 @@ impl IncentiveVoting {
         }
 
         // Get updated account lock weights and store locally
-        let frozen_weight = self.register_account_weight_internal(account, min_weeks);
+        let frozen_weight = self.register_account_weight_internal(account, min_weeks)?;
 
         // Resubmit the account's active vote using the newly registered weights
         self.add_vote_weights(account, &existing_votes, frozen_weight);
@@ -167,12 +169,15 @@ impl IncentiveVoting {
 
         // Log the transaction to sign
         msg!("State transition transaction: {:?}", tx);
+        Ok(())
     }
 
-    pub fn vote(&mut self, account: Pubkey, votes: Vec<Vote>, clear_previous: bool) {
-        let account_data = self.account_lock_data.get_mut(&account).unwrap();
+    pub fn vote(&mut self, account: Pubkey, votes: Vec<Vote>, clear_previous: bool) -> Result<(), ProtocolError> {
+        let account_data = self.account_lock_data.get_mut(&account).ok_or(ProtocolError::NoRegisteredWeight)?;
         let frozen_weight = account_data.frozen_weight;
-        assert!(frozen_weight > 0 || account_data.lock_length > 0, "No registered weight");
+        if frozen_weight == 0 && account_data.lock_length == 0 {
+            return Err(ProtocolError::NoRegisteredWeight);
+        }
 
         let mut points = 0;
         let mut offset = 0;
@@ -201,9 +206,10 @@ impl IncentiveVoting {
 
         // Log the transaction to sign
         msg!("State transition transaction: {:?}", tx);
+        Ok(())
     }
 
-    fn register_account_weight_internal(&mut self, account: Pubkey, min_weeks: u64) -> u64 {
+    fn register_account_weight_internal(&mut self, account: Pubkey, min_weeks: u64) -> Result<u64, ProtocolError> {
         let account_data = self.account_lock_data.get_mut(&account).unwrap();
 
         // Get updated account lock weights and store locally
@@ -218,7 +224,7 @@ impl IncentiveVoting {
                 account_data.weeks_to_unlock[i] = lock.weeks_to_unlock;
             }
         } else {
-            panic!("No active locks");
+            return Err(ProtocolError::NoActiveLocks);
         }
         let week = self.get_week();
         account_data.week = week;
@@ -232,7 +238,7 @@ impl IncentiveVoting {
             lock_data,
         });
 
-        account_data.frozen_weight
+        Ok(account_data.frozen_weight)
     }
 
     fn add_vote_weights(&mut self, account: Pubkey, votes: &[Vote], frozen_weight: u64) {
@@ -320,6 +326,25 @@ impl IncentiveVoting {
         });
     }
 
+    // Reports an event through the protocol event sink
+    fn emit_event(&self, event: Event) {
+        let event = match event {
+            Event::AccountWeightRegistered { account, week, frozen_weight, .. } => {
+                ProtocolEvent::AccountWeightRegistered { account: account.to_string(), week, frozen_weight }
+            }
+            Event::VotesUpdated { account, week, votes, points } => ProtocolEvent::Voted {
+                account: account.to_string(),
+                week,
+                votes: votes.iter().map(|vote| (vote.id, vote.points)).collect(),
+                points,
+            },
+            Event::ClearedVotes { account, week } => ProtocolEvent::VotesCleared { account: account.to_string(), week },
+            // The stored votes were already reported by VotesUpdated
+            Event::AccountVotesStored { .. } => return,
+        };
+        event.emit();
+    }
+
     fn get_account_current_votes(&self, account: Pubkey) -> Vec<Vote> {
         if let Some(account_data) = self.account_lock_data.get(&account) {
             account_data.active_votes.iter().map(|(id, points)| Vote {
@@ -371,19 +396,19 @@ impl IncentiveVoting {
     }
 
     // Add a method to spend UTXOs
-    pub fn spend_utxo(&mut self, outpoint: OutPoint) -> Result<(), ProgramError> {
+    pub fn spend_utxo(&mut self, outpoint: OutPoint) -> Result<(), ProtocolError> {
         if self.utxo_set.remove(&outpoint).is_none() {
-            return Err(ProgramError::Custom(502)); // UTXO not found
+            return Err(ProtocolError::UtxoNotFound);
         }
         Ok(())
     }
 
     // Add a method to validate UTXOs
-    pub fn validate_utxo(&self, outpoint: &OutPoint) -> Result<(), ProgramError> {
+    pub fn validate_utxo(&self, outpoint: &OutPoint) -> Result<(), ProtocolError> {
         if self.utxo_set.contains_key(outpoint) {
             Ok(())
         } else {
-            Err(ProgramError::Custom(503)) // UTXO not valid
+            Err(ProtocolError::InvalidUtxo)
         }
     }
 
@@ -3,6 +3,7 @@
 use borsh::{BorshDeserialize, BorshSerialize};
 use log::{info, warn};
 use crate::interfaces::babel_core::BabelCore; // Ensure BabelCore is in scope
+use crate::dependencies::protocol_error::ProtocolError;
 use arch_program::{
     account::AccountInfo,
     entrypoint,
@@ -94,15 +95,17 @@ impl InterimAdmin {
         }
     }
 
-    pub fn create_new_proposal(&mut self, caller: &str, payload: Vec<Action>) {
+    pub fn create_new_proposal(&mut self, caller: &str, payload: Vec<Action>) -> Result<(), ProtocolError> {
         if !self.is_owner(caller) {
             warn!("Unauthorized attempt to create proposal");
-            return;
+            return Err(ProtocolError::Unauthorized);
         }
 
         let current_time = self.get_current_time();
         let day = (current_time / 86400) as usize;
-        assert!(self.daily_proposals_count[day] < 3, "MAX_DAILY_PROPOSALS reached");
+        if self.daily_proposals_count[day] >= 3 {
+            return Err(ProtocolError::MaxDailyProposals);
+        }
 
         let proposal_id = self.proposals.len();
         self.proposals.push(Proposal {
@@ -136,7 +139,7 @@ impl InterimAdmin {
 
         // Use Arch SDK to log the proposal creation
         msg!("Proposal {} created", proposal_id);
-
+        Ok(())
     }
 
     pub fn cancel_proposal(&mut self, caller: &str, index: usize) {
@@ -256,7 +259,7 @@ mod tests {
         let mut admin = InterimAdmin::new("babel_core".to_string(), "owner".to_string());
         let payload = vec![Action { target: "target".to_string(), data: vec![1, 2, 3] }];
 
-        admin.create_new_proposal("owner", payload); // Implement the logic for this method...
+        admin.create_new_proposal("owner", payload).unwrap(); // Implement the logic for this method...
 
         // Assert the proposal was created successfully...
     }
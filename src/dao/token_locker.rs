@@ -2,6 +2,8 @@
 
 use crate::dependencies::system_start::SystemStart;
 use crate::dependencies::babel_ownable::BabelOwnable;
+use crate::dependencies::protocol_event::ProtocolEvent;
+use crate::dependencies::protocol_error::ProtocolError;
 use std::collections::HashMap;
 use std::time::{SystemTime, UNIX_EPOCH};
 use borsh::{BorshDeserialize, BorshSerialize};
@@ -76,12 +78,15 @@ impl TokenLocker {
             }],
         };
         set_transaction_to_sign(&[AccountInfo::default()], tx_to_sign); // Placeholder for actual accounts
+        ProtocolEvent::Locked { account: account.to_string(), amount: amount as u128, weeks }.emit();
     }
 
     fn unlock(&mut self, account: AccountId) {
         if let Some(account_data) = self.account_data.get_mut(&account) {
+            let amount = account_data.locked;
             account_data.unlocked += account_data.locked;
             account_data.locked = 0;
+            ProtocolEvent::Unlocked { account: account.to_string(), amount: amount as u128 }.emit();
             // Reset the bitfield for the week
             account_data.update_weeks[account_data.week as usize] &= !(1 << (account_data.week % 32));
         }
@@ -205,11 +210,11 @@ impl TokenLocker {
     }
 
     // Add method to validate UTXO
-    fn validate_utxo(&self, utxo: &UtxoMeta) -> Result<(), ProgramError> {
+    fn validate_utxo(&self, utxo: &UtxoMeta) -> Result<(), ProtocolError> {
         if self.utxo_set.contains_key(&OutPoint::new(utxo.txid, utxo.vout)) {
             Ok(())
         } else {
-            Err(ProgramError::Custom(502)) // Custom error for invalid UTXO
+            Err(ProtocolError::InvalidUtxo)
         }
     }
 }
@@ -1,5 +1,7 @@
 use std::collections::HashMap;
 use borsh::{BorshDeserialize, BorshSerialize};
+use crate::dependencies::protocol_error::ProtocolError;
+use crate::dependencies::protocol_event::ProtocolEvent;
 use arch_program::{
     account::AccountInfo,
     entrypoint,
@@ -62,10 +64,11 @@ impl Vault {
         let total_emissions = self.get_total_weekly_emissions(week); 
         self.weekly_emissions.insert(week, total_emissions);
         self.unallocated_total -= total_emissions;
+        ProtocolEvent::WeeklyEmission { week, amount: total_emissions }.emit();
         self.lock_tokens(amount, 52, utxo_meta); // Lock for 1 year
     }
 
-    pub fn transfer_tokens(&mut self, receiver: String, amount: u128, utxo_meta: UtxoMeta) {
+    pub fn transfer_tokens(&mut self, receiver: String, amount: u128, utxo_meta: UtxoMeta) -> Result<(), ProtocolError> {
         if self.unallocated_total >= amount {
             self.unallocated_total -= amount;
 
@@ -73,7 +76,7 @@ impl Vault {
             let tx = get_bitcoin_tx(&self.babel_token, &receiver_pubkey, amount);
 
             // Validate UTXO ownership
-            validate_utxo_ownership(&utxo_meta).expect("UTXO ownership validation failed");
+            validate_utxo_ownership(&utxo_meta).map_err(|_| ProtocolError::InvalidUtxoOwnership)?;
 
             // Create a transaction to sign
             let tx_bytes = bitcoin::consensus::serialize(&tx); // Adjusted for Arch Network
@@ -93,14 +96,15 @@ impl Vault {
             self.sign_transaction(&transaction);
 
             // Invoke the signed transaction
-            invoke(&tx, &[]).expect("Failed to invoke transaction"); // Adjusted for Arch Network
+            invoke(&tx, &[])?; // Adjusted for Arch Network
             // Use Arch SDK to transfer tokens
             let receiver_pubkey = Pubkey::new(&receiver.as_bytes());
             let tx = get_bitcoin_tx(&self.babel_token, &receiver_pubkey, amount);
             invoke(&tx);
 
+            Ok(())
         } else {
-            panic!("Insufficient unallocated tokens for transfer");
+            Err(ProtocolError::InsufficientBalance)
         }
     }
 
@@ -294,7 +298,7 @@ impl Vault {
         self.unallocated_total = self.unallocated_total.saturating_sub(total_allocated);
     }
 
-    fn transfer_allocated(&mut self, amount: u128, receiver: String) {
+    fn transfer_allocated(&mut self, amount: u128, receiver: String) -> Result<(), ProtocolError> {
         // Check if the receiver has enough allocated tokens
         if let Some(allocated_amount) = self.allocated.get_mut(&receiver) {
             if *allocated_amount >= amount {
@@ -305,15 +309,15 @@ impl Vault {
                 let receiver_pubkey = Pubkey::new(&receiver.as_bytes());
                 let tx = get_bitcoin_tx(&self.babel_token, &receiver_pubkey, amount);
 
-                invoke(&tx, &[]).expect("Failed to invoke transaction"); // Adjusted for Arch Network
-
+                invoke(&tx, &[])?; // Adjusted for Arch Network
+                Ok(())
             } else {
                 // Handle the case where the allocated amount is insufficient
-                panic!("Insufficient allocated tokens for transfer");
+                Err(ProtocolError::InsufficientBalance)
             }
         } else {
             // Handle the case where the receiver is not found in the allocated map
-            panic!("Receiver not found in allocated tokens");
+            Err(ProtocolError::InvalidRecipient)
         }
     }
 
@@ -1,6 +1,7 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
 use borsh::{BorshDeserialize, BorshSerialize};
+use crate::dependencies::protocol_error::ProtocolError;
 
 #[derive(BorshSerialize, BorshDeserialize)]
 pub struct BabelBase {
@@ -20,6 +21,10 @@ impl BabelBase {
 
     // --- Gas compensation functions ---
 
+    pub fn debt_gas_compensation(&self) -> u128 {
+        self.debt_gas_compensation
+    }
+
     // Returns the composite debt (drawn debt + gas compensation) of a trove, for the purpose of ICR calculation
     pub fn get_composite_debt(&self, debt: u128) -> u128 {
         debt + self.debt_gas_compensation
@@ -39,10 +44,10 @@ impl BabelBase {
         fee: u128,
         amount: u128,
         max_fee_percentage: u128,
-    ) -> Result<(), &'static str> {
+    ) -> Result<(), ProtocolError> {
         let fee_percentage = (fee * Self::DECIMAL_PRECISION) / amount;
         if fee_percentage > max_fee_percentage {
-            return Err("Fee exceeded provided maximum");
+            return Err(ProtocolError::FeeExceedsMax);
         }
         Ok(())
     }
@@ -73,7 +78,7 @@ mod tests {
     fn test_require_user_accepts_fee() {
         let contract = BabelBase::new(100);
         assert_eq!(contract.require_user_accepts_fee(50, 200, 1e18 as u128), Ok(()));
-        assert_eq!(contract.require_user_accepts_fee(100, 200, 0.25e18 as u128), Err("Fee exceeded provided maximum"));
+        assert_eq!(contract.require_user_accepts_fee(100, 200, 0.25e18 as u128), Err(ProtocolError::FeeExceedsMax));
     }
 
     #[test]
@@ -25,18 +25,37 @@ impl BabelMath {
         }
     }
 
+    // Rounds half up. The product is taken in 256 bits and the result saturates at Balance::MAX,
+    // so squaring large factors in dec_pow cannot overflow
     pub fn dec_mul(x: Balance, y: Balance) -> Balance {
-        let prod_xy = x * y;
         let half_precision = Self::DECIMAL_PRECISION / 2;
-        (prod_xy + half_precision) / Self::DECIMAL_PRECISION
+        if let Some(prod_xy) = x.checked_mul(y).and_then(|prod| prod.checked_add(half_precision)) {
+            return prod_xy / Self::DECIMAL_PRECISION;
+        }
+        let result = (BigUint::from(x) * BigUint::from(y) + BigUint::from(half_precision)) / BigUint::from(Self::DECIMAL_PRECISION);
+        result.try_into().unwrap_or(Balance::MAX)
     }
 
+    // Exponents are capped at 1000 years to bound the loop. Factors that grow past Balance::MAX
+    // within that time, e.g. interest left untouched for decades, saturate rather than overflow.
+    const CAP_MINUTES: u64 = 525_600_000;
+    const CAP_SECONDS: u64 = Self::CAP_MINUTES * 60;
+
+    // base^minutes, for per-minute factors such as the base rate decay
     pub fn dec_pow(base: Balance, minutes: u64) -> Balance {
-        let cap_minutes = 525600000;
-        let mut n = if minutes > cap_minutes {
-            cap_minutes
+        Self::dec_pow_capped(base, minutes, Self::CAP_MINUTES)
+    }
+
+    // base^seconds, for per-second factors such as interest rates
+    pub fn dec_pow_seconds(base: Balance, seconds: u64) -> Balance {
+        Self::dec_pow_capped(base, seconds, Self::CAP_SECONDS)
+    }
+
+    fn dec_pow_capped(base: Balance, exponent: u64, cap: u64) -> Balance {
+        let mut n = if exponent > cap {
+            cap
         } else {
-            minutes
+            exponent
         };
 
         if n == 0 {
@@ -60,6 +79,28 @@ impl BabelMath {
         Self::dec_mul(x, y)
     }
 
+    // Computes (x * y) / denominator with a 256-bit intermediate product, so large balances
+    // multiplied by 1e18-scaled factors do not overflow
+    pub fn mul_div(x: Balance, y: Balance, denominator: Balance) -> Balance {
+        if let Some(prod) = x.checked_mul(y) {
+            return prod / denominator;
+        }
+        let result = (BigUint::from(x) * BigUint::from(y)) / BigUint::from(denominator);
+        result.try_into().unwrap_or(Balance::MAX)
+    }
+
+    // Like mul_div, also returning the remainder of the division so callers can carry rounding errors
+    pub fn mul_div_rem(x: Balance, y: Balance, denominator: Balance) -> (Balance, Balance) {
+        if let Some(prod) = x.checked_mul(y) {
+            return (prod / denominator, prod % denominator);
+        }
+        let prod = BigUint::from(x) * BigUint::from(y);
+        let denominator = BigUint::from(denominator);
+        let quotient = (&prod / &denominator).try_into().unwrap_or(Balance::MAX);
+        let remainder = (&prod % &denominator).try_into().unwrap_or(0);
+        (quotient, remainder)
+    }
+
     pub fn get_absolute_difference(a: Balance, b: Balance) -> Balance {
         if a >= b {
             a - b
@@ -70,7 +111,7 @@ impl BabelMath {
 
     pub fn compute_nominal_cr(coll: Balance, debt: Balance) -> Balance {
         if debt > 0 {
-            (coll * Self::NICR_PRECISION) / debt
+            Self::mul_div(coll, Self::NICR_PRECISION, debt)
         } else {
             u128::MAX
         }
@@ -78,7 +119,7 @@ impl BabelMath {
 
     pub fn compute_cr(coll: Balance, debt: Balance, price: Balance) -> Balance {
         if debt > 0 {
-            (coll * price) / debt
+            Self::mul_div(coll, price, debt)
         } else {
             u128::MAX
         }
@@ -93,7 +134,7 @@ impl BabelMath {
     }
 }
 
-type Balance = u64; // Placeholder type definition
+type Balance = u128; // Placeholder type definition
 
 #[cfg(test)]
 mod tests {
@@ -123,6 +164,54 @@ mod tests {
         assert_eq!(BabelMath::dec_pow(2 * BabelMath::DECIMAL_PRECISION, 3), 8 * BabelMath::DECIMAL_PRECISION);
     }
 
+    #[test]
+    fn test_dec_pow_half_life() {
+        // 12h half-life minute decay factor
+        let decayed = BabelMath::dec_pow(999_037_758_833_783_000, 720);
+        assert!(BabelMath::get_absolute_difference(decayed, BabelMath::DECIMAL_PRECISION / 2) < 1_000_000_000);
+        assert_eq!(BabelMath::dec_pow(999_037_758_833_783_000, 0), BabelMath::DECIMAL_PRECISION);
+    }
+
+    #[test]
+    fn test_dec_pow_caps() {
+        let per_second = BabelMath::DECIMAL_PRECISION + 1;
+        assert_eq!(BabelMath::dec_pow(per_second, BabelMath::CAP_MINUTES + 1), BabelMath::dec_pow(per_second, BabelMath::CAP_MINUTES));
+        // Twenty years of per-second compounding is past the minute cap but not the second cap
+        let twenty_years = 20 * 365 * 24 * 60 * 60;
+        assert!(BabelMath::dec_pow_seconds(per_second, twenty_years) > BabelMath::dec_pow_seconds(per_second, BabelMath::CAP_MINUTES));
+    }
+
+    #[test]
+    fn test_dec_pow_seconds_does_not_overflow() {
+        // 100% APR compounded per second
+        let seconds_in_year = 365 * 24 * 60 * 60;
+        let per_second = BabelMath::DECIMAL_PRECISION + BabelMath::DECIMAL_PRECISION / seconds_in_year as u128;
+
+        // e^10 after ten untouched years, although squaring intermediates exceeds 128 bits
+        let ten_years = BabelMath::dec_pow_seconds(per_second, 10 * seconds_in_year);
+        assert!(ten_years > 22_000 * BabelMath::DECIMAL_PRECISION && ten_years < 22_100 * BabelMath::DECIMAL_PRECISION);
+
+        // Past what a u128 can hold the factor saturates
+        assert_eq!(BabelMath::dec_pow_seconds(per_second, 1_000 * seconds_in_year), u128::MAX);
+    }
+
+    #[test]
+    fn test_mul_div() {
+        assert_eq!(BabelMath::mul_div(6, 4, 3), 8);
+        // The intermediate product does not fit in 128 bits
+        let large = 1_000_000 * BabelMath::DECIMAL_PRECISION;
+        assert_eq!(BabelMath::mul_div(large, 3 * BabelMath::DECIMAL_PRECISION * BabelMath::DECIMAL_PRECISION, BabelMath::DECIMAL_PRECISION * BabelMath::DECIMAL_PRECISION), 3 * large);
+    }
+
+    #[test]
+    fn test_mul_div_rem() {
+        assert_eq!(BabelMath::mul_div_rem(7, 5, 3), (11, 2));
+        let large = 1_000_000_000 * BabelMath::DECIMAL_PRECISION + 7;
+        let (quotient, remainder) = BabelMath::mul_div_rem(large, BabelMath::DECIMAL_PRECISION, 3 * BabelMath::DECIMAL_PRECISION);
+        assert_eq!(quotient, large / 3);
+        assert_eq!(remainder, (large % 3) * BabelMath::DECIMAL_PRECISION);
+    }
+
     #[test]
     fn test_get_absolute_difference() {
         assert_eq!(BabelMath::get_absolute_difference(10, 5), 5);
@@ -133,12 +222,18 @@ mod tests {
     fn test_compute_nominal_cr() {
         assert_eq!(BabelMath::compute_nominal_cr(100, 50), 2 * BabelMath::NICR_PRECISION);
         assert_eq!(BabelMath::compute_nominal_cr(100, 0), u128::MAX);
+        // 10 units of collateral against 5 of debt
+        let unit = BabelMath::DECIMAL_PRECISION;
+        assert_eq!(BabelMath::compute_nominal_cr(10 * unit, 5 * unit), 2 * BabelMath::NICR_PRECISION);
     }
 
     #[test]
     fn test_compute_cr() {
         assert_eq!(BabelMath::compute_cr(100, 50, BabelMath::DECIMAL_PRECISION), 2 * BabelMath::DECIMAL_PRECISION);
         assert_eq!(BabelMath::compute_cr(100, 0, BabelMath::DECIMAL_PRECISION), u128::MAX);
+        // 1 BTC at 60,000 against 30,000 of debt
+        let unit = BabelMath::DECIMAL_PRECISION;
+        assert_eq!(BabelMath::compute_cr(unit, 30_000 * unit, 60_000 * unit), 2 * unit);
     }
 
     #[test]
@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use borsh::{BorshDeserialize, BorshSerialize};
+use crate::dependencies::protocol_error::ProtocolError;
 
 #[derive(BorshSerialize, BorshDeserialize)]
 pub struct DelegatedOps {
@@ -21,11 +22,11 @@ impl DelegatedOps {
         *self.is_approved_delegate.get(&(owner, caller)).unwrap_or(&false)
     }
 
-    pub fn ensure_caller_or_delegated(&self, account: String, caller: String) {
-        assert!(
-            caller == account || self.is_approved_delegate(account.clone(), caller),
-            "Delegate not approved"
-        );
+    pub fn ensure_caller_or_delegated(&self, account: String, caller: String) -> Result<(), ProtocolError> {
+        if caller != account && !self.is_approved_delegate(account, caller) {
+            return Err(ProtocolError::DelegateNotApproved);
+        }
+        Ok(())
     }
 }
 
@@ -0,0 +1,409 @@
+use std::fmt;
+use arch_program::program_error::ProgramError;
+
+type Balance = u128;
+
+// Errors returned across the protocol. Each variant has a stable numeric code, reported to clients
+// as ProgramError::Custom(code); codes are grouped by area and never reused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProtocolError {
+    // Access control (1xxx)
+    Unauthorized,
+    DelegateNotApproved,
+    AlreadyInitialized,
+    Paused,
+
+    // Troves and system collateralization (2xxx)
+    IcrBelowMcr,
+    IcrBelowCcr,
+    TcrBelowCcr,
+    TcrBelowMcr,
+    IcrDecreasedInRecoveryMode,
+    CollateralWithdrawalInRecoveryMode,
+    NotPermittedInRecoveryMode,
+    TroveNotActive,
+    TroveAlreadyActive,
+    OnlyOneTroveInSystem,
+    CollateralWithdrawalExceedsTrove,
+    RepaymentExceedsDebt,
+    CollateralSunsetting,
+    NothingToLiquidate,
+    NothingToRedeem,
+    ZeroAmount,
+    InvalidTroveManager,
+    TroveManagerAlreadyConfigured,
+    TroveAlreadyListed,
+    TroveNotListed,
+    InvalidNicr,
+    EmptyTroveList,
+
+    // Fees (3xxx)
+    FeeExceedsMax,
+    InvalidMaxFeePercentage,
+    NothingToCollect,
+
+    // Debt limits and the debt token (4xxx)
+    MaxSystemDebtExceeded { requested: Balance, headroom: Balance },
+    GlobalDebtCapExceeded { requested: Balance, headroom: Balance },
+    InsufficientBalance,
+    InvalidRecipient,
+    PermitExpired,
+    InvalidSignature,
+    FlashLoanCallbackFailed,
+    FlashLoanTooLarge,
+    FeeReceiverNotSet,
+
+    // Prices and oracles (5xxx)
+    StalePrice,
+    PriceUnavailable,
+    CircuitBreakerTripped,
+    CircuitBreakerNotTripped,
+    InvalidOracleConfig,
+    UnknownToken,
+    RoundNotOpen,
+    AlreadySubmitted,
+    InsufficientSignatures,
+
+    // Collateral parameters (6xxx)
+    InvalidParameter,
+    MinuteDecayFactorOutOfRange,
+    InvalidRedemptionFeeBounds,
+    InvalidBorrowingFeeBounds,
+    InterestRateTooHigh,
+    InvalidMcr,
+    TwapWindowNotSet,
+    AlreadySunsetting,
+    DuplicateCollateral,
+
+    // Stability pool (7xxx)
+    CollateralNotEnabled,
+    CollateralAlreadyEnabled,
+    SunsetNotExpired,
+    NoDeposit,
+    InsufficientDeposit,
+    PoolProductZero,
+
+    // DAO: locks, vesting, governance and rewards (8xxx)
+    NothingToClaim,
+    CannotLock,
+    WrongMaxTotalPreclaimPct,
+    PreclaimTooLarge,
+    AllocationsMismatch,
+    ZeroTotalAllocation,
+    ZeroAllocation,
+    ZeroNumberOfWeeks,
+    DuplicateAllocation,
+    InsufficientPoints,
+    LockedAllocation,
+    IllegalVestingStart,
+    VestingAlreadyStarted,
+    IncompatibleVestingPeriod,
+    InvalidLockWeeks,
+    InvalidEmissionSchedule,
+    NoRegisteredWeight,
+    NoActiveLocks,
+    ProposalTooSoon,
+    ProposalsNotOpen,
+    InsufficientVotingWeight,
+    ProposalAlreadyProcessed,
+    VotingClosed,
+    AlreadyVoted,
+    ProposalNotExecutable,
+    MaxDailyProposals,
+    MerkleRootAlreadySet,
+    ClaimPeriodEnded,
+    AlreadyClaimed,
+    InvalidMerkleProof,
+    RewardsAlreadyFetched,
+
+    // UTXOs (9xxx)
+    UtxoNotFound,
+    InvalidUtxo,
+    InvalidUtxoOwnership,
+    UtxoValueMismatch,
+    NoUtxosProvided,
+    InsufficientUtxoValue { required: u64, provided: u64 },
+    UtxoValueOverflow,
+
+    // Failure reported by the Arch runtime, passed through unchanged
+    Program(ProgramError),
+}
+
+impl ProtocolError {
+    pub fn code(&self) -> u32 {
+        match self {
+            ProtocolError::Unauthorized => 1000,
+            ProtocolError::DelegateNotApproved => 1001,
+            ProtocolError::AlreadyInitialized => 1002,
+            ProtocolError::Paused => 1003,
+
+            ProtocolError::IcrBelowMcr => 2000,
+            ProtocolError::IcrBelowCcr => 2001,
+            ProtocolError::TcrBelowCcr => 2002,
+            ProtocolError::TcrBelowMcr => 2003,
+            ProtocolError::IcrDecreasedInRecoveryMode => 2004,
+            ProtocolError::CollateralWithdrawalInRecoveryMode => 2005,
+            ProtocolError::NotPermittedInRecoveryMode => 2006,
+            ProtocolError::TroveNotActive => 2007,
+            ProtocolError::TroveAlreadyActive => 2008,
+            ProtocolError::OnlyOneTroveInSystem => 2009,
+            ProtocolError::CollateralWithdrawalExceedsTrove => 2010,
+            ProtocolError::RepaymentExceedsDebt => 2011,
+            ProtocolError::CollateralSunsetting => 2012,
+            ProtocolError::NothingToLiquidate => 2013,
+            ProtocolError::NothingToRedeem => 2014,
+            ProtocolError::ZeroAmount => 2015,
+            ProtocolError::InvalidTroveManager => 2016,
+            ProtocolError::TroveManagerAlreadyConfigured => 2017,
+            ProtocolError::TroveAlreadyListed => 2018,
+            ProtocolError::TroveNotListed => 2019,
+            ProtocolError::InvalidNicr => 2020,
+            ProtocolError::EmptyTroveList => 2021,
+
+            ProtocolError::FeeExceedsMax => 3000,
+            ProtocolError::InvalidMaxFeePercentage => 3001,
+            ProtocolError::NothingToCollect => 3002,
+
+            ProtocolError::MaxSystemDebtExceeded { .. } => 4000,
+            ProtocolError::GlobalDebtCapExceeded { .. } => 4001,
+            ProtocolError::InsufficientBalance => 4002,
+            ProtocolError::InvalidRecipient => 4003,
+            ProtocolError::PermitExpired => 4004,
+            ProtocolError::InvalidSignature => 4005,
+            ProtocolError::FlashLoanCallbackFailed => 4006,
+            ProtocolError::FlashLoanTooLarge => 4007,
+            ProtocolError::FeeReceiverNotSet => 4008,
+
+            ProtocolError::StalePrice => 5000,
+            ProtocolError::PriceUnavailable => 5001,
+            ProtocolError::CircuitBreakerTripped => 5002,
+            ProtocolError::CircuitBreakerNotTripped => 5003,
+            ProtocolError::InvalidOracleConfig => 5004,
+            ProtocolError::UnknownToken => 5005,
+            ProtocolError::RoundNotOpen => 5006,
+            ProtocolError::AlreadySubmitted => 5007,
+            ProtocolError::InsufficientSignatures => 5008,
+
+            ProtocolError::InvalidParameter => 6000,
+            ProtocolError::MinuteDecayFactorOutOfRange => 6001,
+            ProtocolError::InvalidRedemptionFeeBounds => 6002,
+            ProtocolError::InvalidBorrowingFeeBounds => 6003,
+            ProtocolError::InterestRateTooHigh => 6004,
+            ProtocolError::InvalidMcr => 6005,
+            ProtocolError::TwapWindowNotSet => 6006,
+            ProtocolError::AlreadySunsetting => 6007,
+            ProtocolError::DuplicateCollateral => 6008,
+
+            ProtocolError::CollateralNotEnabled => 7000,
+            ProtocolError::CollateralAlreadyEnabled => 7001,
+            ProtocolError::SunsetNotExpired => 7002,
+            ProtocolError::NoDeposit => 7003,
+            ProtocolError::InsufficientDeposit => 7004,
+            ProtocolError::PoolProductZero => 7005,
+
+            ProtocolError::NothingToClaim => 8000,
+            ProtocolError::CannotLock => 8001,
+            ProtocolError::WrongMaxTotalPreclaimPct => 8002,
+            ProtocolError::PreclaimTooLarge => 8003,
+            ProtocolError::AllocationsMismatch => 8004,
+            ProtocolError::ZeroTotalAllocation => 8005,
+            ProtocolError::ZeroAllocation => 8006,
+            ProtocolError::ZeroNumberOfWeeks => 8007,
+            ProtocolError::DuplicateAllocation => 8008,
+            ProtocolError::InsufficientPoints => 8009,
+            ProtocolError::LockedAllocation => 8010,
+            ProtocolError::IllegalVestingStart => 8011,
+            ProtocolError::VestingAlreadyStarted => 8012,
+            ProtocolError::IncompatibleVestingPeriod => 8013,
+            ProtocolError::InvalidLockWeeks => 8014,
+            ProtocolError::InvalidEmissionSchedule => 8015,
+            ProtocolError::NoRegisteredWeight => 8016,
+            ProtocolError::NoActiveLocks => 8017,
+            ProtocolError::ProposalTooSoon => 8018,
+            ProtocolError::ProposalsNotOpen => 8019,
+            ProtocolError::InsufficientVotingWeight => 8020,
+            ProtocolError::ProposalAlreadyProcessed => 8021,
+            ProtocolError::VotingClosed => 8022,
+            ProtocolError::AlreadyVoted => 8023,
+            ProtocolError::ProposalNotExecutable => 8024,
+            ProtocolError::MaxDailyProposals => 8025,
+            ProtocolError::MerkleRootAlreadySet => 8026,
+            ProtocolError::ClaimPeriodEnded => 8027,
+            ProtocolError::AlreadyClaimed => 8028,
+            ProtocolError::InvalidMerkleProof => 8029,
+            ProtocolError::RewardsAlreadyFetched => 8030,
+
+            ProtocolError::UtxoNotFound => 9000,
+            ProtocolError::InvalidUtxo => 9001,
+            ProtocolError::InvalidUtxoOwnership => 9002,
+            ProtocolError::UtxoValueMismatch => 9003,
+            ProtocolError::NoUtxosProvided => 9004,
+            ProtocolError::InsufficientUtxoValue { .. } => 9005,
+            ProtocolError::UtxoValueOverflow => 9006,
+
+            // Runtime errors keep their own ProgramError code
+            ProtocolError::Program(_) => 0,
+        }
+    }
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolError::Unauthorized => write!(f, "Caller is not authorized"),
+            ProtocolError::DelegateNotApproved => write!(f, "Delegate not approved"),
+            ProtocolError::AlreadyInitialized => write!(f, "Already initialized"),
+            ProtocolError::Paused => write!(f, "Protocol is paused"),
+
+            ProtocolError::IcrBelowMcr => write!(f, "ICR must be above the minimum collateral ratio"),
+            ProtocolError::IcrBelowCcr => write!(f, "ICR must be above the critical collateral ratio"),
+            ProtocolError::TcrBelowCcr => write!(f, "New TCR must be above the critical collateral ratio"),
+            ProtocolError::TcrBelowMcr => write!(f, "Cannot redeem when TCR < MCR"),
+            ProtocolError::IcrDecreasedInRecoveryMode => write!(f, "New ICR must be above the old ICR in Recovery Mode"),
+            ProtocolError::CollateralWithdrawalInRecoveryMode => write!(f, "Collateral withdrawal not permitted in Recovery Mode"),
+            ProtocolError::NotPermittedInRecoveryMode => write!(f, "Operation not permitted during Recovery Mode"),
+            ProtocolError::TroveNotActive => write!(f, "Trove does not exist or is closed"),
+            ProtocolError::TroveAlreadyActive => write!(f, "Trove already active"),
+            ProtocolError::OnlyOneTroveInSystem => write!(f, "Only one trove in the system"),
+            ProtocolError::CollateralWithdrawalExceedsTrove => write!(f, "Collateral withdrawal exceeds trove collateral"),
+            ProtocolError::RepaymentExceedsDebt => write!(f, "Repayment exceeds trove debt"),
+            ProtocolError::CollateralSunsetting => write!(f, "Collateral is being sunset"),
+            ProtocolError::NothingToLiquidate => write!(f, "Nothing to liquidate"),
+            ProtocolError::NothingToRedeem => write!(f, "Unable to redeem any amount"),
+            ProtocolError::ZeroAmount => write!(f, "Amount must be greater than zero"),
+            ProtocolError::InvalidTroveManager => write!(f, "Invalid Trove Manager"),
+            ProtocolError::TroveManagerAlreadyConfigured => write!(f, "Trove Manager already configured"),
+            ProtocolError::TroveAlreadyListed => write!(f, "List already contains the node"),
+            ProtocolError::TroveNotListed => write!(f, "List does not contain the id"),
+            ProtocolError::InvalidNicr => write!(f, "NICR must be positive"),
+            ProtocolError::EmptyTroveList => write!(f, "No troves to operate on"),
+
+            ProtocolError::FeeExceedsMax => write!(f, "Fee exceeded provided maximum"),
+            ProtocolError::InvalidMaxFeePercentage => write!(f, "Max fee percentage must be between the fee floor and 100%"),
+            ProtocolError::NothingToCollect => write!(f, "Nothing to collect"),
+
+            ProtocolError::MaxSystemDebtExceeded { requested, headroom } => {
+                write!(f, "Collateral debt limit reached: requested {}, headroom {}", requested, headroom)
+            }
+            ProtocolError::GlobalDebtCapExceeded { requested, headroom } => {
+                write!(f, "Global debt cap reached: requested {}, headroom {}", requested, headroom)
+            }
+            ProtocolError::InsufficientBalance => write!(f, "Insufficient balance"),
+            ProtocolError::InvalidRecipient => write!(f, "Cannot transfer tokens directly to protocol accounts"),
+            ProtocolError::PermitExpired => write!(f, "Permit expired"),
+            ProtocolError::InvalidSignature => write!(f, "Invalid signature"),
+            ProtocolError::FlashLoanCallbackFailed => write!(f, "Flash loan callback failed"),
+            ProtocolError::FlashLoanTooLarge => write!(f, "Flash loan amount exceeds the maximum"),
+            ProtocolError::FeeReceiverNotSet => write!(f, "Fee receiver not set"),
+
+            ProtocolError::StalePrice => write!(f, "Price is stale"),
+            ProtocolError::PriceUnavailable => write!(f, "Price unavailable"),
+            ProtocolError::CircuitBreakerTripped => write!(f, "Price circuit breaker is tripped for this collateral"),
+            ProtocolError::CircuitBreakerNotTripped => write!(f, "Price circuit breaker is not tripped"),
+            ProtocolError::InvalidOracleConfig => write!(f, "Invalid oracle configuration"),
+            ProtocolError::UnknownToken => write!(f, "Token has no price source"),
+            ProtocolError::RoundNotOpen => write!(f, "Round is not open"),
+            ProtocolError::AlreadySubmitted => write!(f, "Reporter already submitted for this round"),
+            ProtocolError::InsufficientSignatures => write!(f, "Not enough valid oracle signatures"),
+
+            ProtocolError::InvalidParameter => write!(f, "Invalid parameter"),
+            ProtocolError::MinuteDecayFactorOutOfRange => write!(f, "Minute decay factor out of range"),
+            ProtocolError::InvalidRedemptionFeeBounds => write!(f, "Invalid redemption fee bounds"),
+            ProtocolError::InvalidBorrowingFeeBounds => write!(f, "Invalid borrowing fee bounds"),
+            ProtocolError::InterestRateTooHigh => write!(f, "Interest rate too high"),
+            ProtocolError::InvalidMcr => write!(f, "MCR cannot be > CCR or < 110%"),
+            ProtocolError::TwapWindowNotSet => write!(f, "TWAP window must be set"),
+            ProtocolError::AlreadySunsetting => write!(f, "Already sunsetting"),
+            ProtocolError::DuplicateCollateral => write!(f, "Collateral already deployed"),
+
+            ProtocolError::CollateralNotEnabled => write!(f, "Collateral not enabled"),
+            ProtocolError::CollateralAlreadyEnabled => write!(f, "Collateral already enabled"),
+            ProtocolError::SunsetNotExpired => write!(f, "Collateral sunset grace period has not ended"),
+            ProtocolError::NoDeposit => write!(f, "No deposit"),
+            ProtocolError::InsufficientDeposit => write!(f, "Withdrawal exceeds deposit"),
+            ProtocolError::PoolProductZero => write!(f, "Stability pool product would be zero"),
+
+            ProtocolError::NothingToClaim => write!(f, "Nothing to claim"),
+            ProtocolError::CannotLock => write!(f, "Cannot lock"),
+            ProtocolError::WrongMaxTotalPreclaimPct => write!(f, "Wrong max total preclaim percentage"),
+            ProtocolError::PreclaimTooLarge => write!(f, "Preclaim too large"),
+            ProtocolError::AllocationsMismatch => write!(f, "Allocations mismatch"),
+            ProtocolError::ZeroTotalAllocation => write!(f, "Zero total allocation"),
+            ProtocolError::ZeroAllocation => write!(f, "Zero allocation"),
+            ProtocolError::ZeroNumberOfWeeks => write!(f, "Zero number of weeks"),
+            ProtocolError::DuplicateAllocation => write!(f, "Duplicate allocation"),
+            ProtocolError::InsufficientPoints => write!(f, "Insufficient points"),
+            ProtocolError::LockedAllocation => write!(f, "Allocation is locked"),
+            ProtocolError::IllegalVestingStart => write!(f, "Illegal vesting start"),
+            ProtocolError::VestingAlreadyStarted => write!(f, "Vesting already started"),
+            ProtocolError::IncompatibleVestingPeriod => write!(f, "Incompatible vesting period"),
+            ProtocolError::InvalidLockWeeks => write!(f, "Lock duration exceeds the maximum allowed weeks"),
+            ProtocolError::InvalidEmissionSchedule => write!(f, "Invalid emission schedule"),
+            ProtocolError::NoRegisteredWeight => write!(f, "No registered weight"),
+            ProtocolError::NoActiveLocks => write!(f, "No active locks"),
+            ProtocolError::ProposalTooSoon => write!(f, "Minimum time between proposals not met"),
+            ProtocolError::ProposalsNotOpen => write!(f, "No proposals in the first week"),
+            ProtocolError::InsufficientVotingWeight => write!(f, "Not enough weight to propose"),
+            ProtocolError::ProposalAlreadyProcessed => write!(f, "Proposal already processed"),
+            ProtocolError::VotingClosed => write!(f, "Voting period has closed"),
+            ProtocolError::AlreadyVoted => write!(f, "Already voted"),
+            ProtocolError::ProposalNotExecutable => write!(f, "Proposal cannot be executed yet"),
+            ProtocolError::MaxDailyProposals => write!(f, "Maximum daily proposals reached"),
+            ProtocolError::MerkleRootAlreadySet => write!(f, "Merkle root already set"),
+            ProtocolError::ClaimPeriodEnded => write!(f, "Claim period has ended"),
+            ProtocolError::AlreadyClaimed => write!(f, "Already claimed"),
+            ProtocolError::InvalidMerkleProof => write!(f, "Invalid merkle proof"),
+            ProtocolError::RewardsAlreadyFetched => write!(f, "Rewards can only be fetched once per week"),
+
+            ProtocolError::UtxoNotFound => write!(f, "UTXO not found"),
+            ProtocolError::InvalidUtxo => write!(f, "UTXO not valid"),
+            ProtocolError::InvalidUtxoOwnership => write!(f, "Invalid UTXO ownership"),
+            ProtocolError::UtxoValueMismatch => write!(f, "Input and output UTXO values do not match"),
+            ProtocolError::NoUtxosProvided => write!(f, "No UTXOs provided"),
+            ProtocolError::InsufficientUtxoValue { required, provided } => {
+                write!(f, "UTXOs hold {} but {} is required", provided, required)
+            }
+            ProtocolError::UtxoValueOverflow => write!(f, "Amount does not fit in a UTXO value"),
+
+            ProtocolError::Program(e) => write!(f, "Program error: {:?}", e),
+        }
+    }
+}
+
+impl From<ProtocolError> for ProgramError {
+    fn from(e: ProtocolError) -> Self {
+        match e {
+            ProtocolError::Program(e) => e,
+            e => ProgramError::Custom(e.code()),
+        }
+    }
+}
+
+impl From<ProgramError> for ProtocolError {
+    fn from(e: ProgramError) -> Self {
+        ProtocolError::Program(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes_are_stable() {
+        assert_eq!(ProtocolError::Unauthorized.code(), 1000);
+        assert_eq!(ProtocolError::IcrBelowMcr.code(), 2000);
+        assert_eq!(ProtocolError::TcrBelowCcr.code(), 2002);
+        assert_eq!(ProtocolError::FeeExceedsMax.code(), 3000);
+        assert_eq!(ProtocolError::StalePrice.code(), 5000);
+        assert_eq!(ProtocolError::GlobalDebtCapExceeded { requested: 1, headroom: 0 }.code(), 4001);
+    }
+
+    #[test]
+    fn test_into_program_error() {
+        assert_eq!(ProgramError::from(ProtocolError::IcrBelowMcr), ProgramError::Custom(2000));
+        // Runtime errors are passed through rather than renumbered
+        let runtime = ProtocolError::from(ProgramError::InvalidAccountData);
+        assert_eq!(ProgramError::from(runtime), ProgramError::InvalidAccountData);
+    }
+}
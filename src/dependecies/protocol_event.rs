@@ -0,0 +1,196 @@
+use std::cell::{Cell, RefCell};
+use borsh::{BorshDeserialize, BorshSerialize};
+use arch_program::{msg, program::set_return_data};
+
+// Prefix of event lines in the program log, followed by the hex-encoded borsh event
+pub const EVENT_LOG_PREFIX: &str = "EVENT:";
+// Largest return data the runtime accepts; events that no longer fit are written to the log
+pub const MAX_RETURN_DATA: usize = 1024;
+
+// Administrative changes, carried by ProtocolEvent::Admin
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum AdminAction {
+    FeeReceiverSet { fee_receiver: String },
+    PriceFeedSet { price_feed: String },
+    GuardianSet { guardian: String },
+    Paused,
+    Unpaused,
+    OwnershipTransferStarted { new_owner: String, deadline: u64 },
+    OwnershipTransferred { new_owner: String },
+    OwnershipTransferRevoked,
+    PriceBreakerCleared { token: String },
+    ProposalCreated { proposal_id: u32 },
+    ProposalExecuted { proposal_id: u32 },
+}
+
+// Machine-readable protocol events. Variants are only ever appended so the borsh tag of an
+// existing event never changes.
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum ProtocolEvent {
+    TroveOpened { collateral: String, borrower: String, coll: u128, debt: u128, stake: u128 },
+    TroveAdjusted { collateral: String, borrower: String, coll: u128, debt: u128, stake: u128 },
+    TroveClosed { collateral: String, borrower: String, coll: u128, debt: u128 },
+    TroveLiquidated { collateral: String, borrower: String, coll: u128, debt: u128, operation: u8 },
+    TroveRedeemed { collateral: String, borrower: String, coll: u128, debt: u128, closed: bool },
+    Redemption { collateral: String, redeemer: String, attempted_debt: u128, actual_debt: u128, collateral_sent: u128, fee: u128 },
+    StabilityDeposit { depositor: String, amount: u128, total_deposits: u128 },
+    StabilityWithdrawal { depositor: String, amount: u128, total_deposits: u128 },
+    StabilityGainWithdrawn { depositor: String, collateral: u64, amount: u128 },
+    Locked { account: String, amount: u128, weeks: u16 },
+    Unlocked { account: String, amount: u128 },
+    AccountWeightRegistered { account: String, week: u16, frozen_weight: u64 },
+    Voted { account: String, week: u16, votes: Vec<(u128, u128)>, points: u16 },
+    VotesCleared { account: String, week: u16 },
+    ProposalVoted { account: String, proposal_id: u32, weight: u64 },
+    WeeklyEmission { week: u64, amount: u128 },
+    Admin { caller: String, action: AdminAction },
+}
+
+// Where emitted events are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventSink {
+    // Appended to the program's return data as u32 length-prefixed borsh records
+    ReturnData,
+    // One program log line per event
+    Log,
+}
+
+thread_local! {
+    static EVENT_SINK: Cell<EventSink> = Cell::new(EventSink::Log);
+    static RETURN_DATA_EVENTS: RefCell<Vec<u8>> = RefCell::new(Vec::new());
+}
+
+impl ProtocolEvent {
+    // Return data is replaced rather than appended by the runtime, so the whole buffer is set again
+    // on each emit. MAX_RETURN_DATA bounds that cost.
+    pub fn emit(&self) {
+        match event_sink() {
+            EventSink::ReturnData => RETURN_DATA_EVENTS.with(|buffer| {
+                let mut buffer = buffer.borrow_mut();
+                if self.append_frame(&mut buffer) {
+                    set_return_data(&buffer);
+                } else {
+                    msg!("{}", self.to_log_line());
+                }
+            }),
+            EventSink::Log => msg!("{}", self.to_log_line()),
+        }
+    }
+
+    // Appends the framed event unless the buffer would exceed MAX_RETURN_DATA
+    fn append_frame(&self, buffer: &mut Vec<u8>) -> bool {
+        let frame = self.frame();
+        if buffer.len() + frame.len() > MAX_RETURN_DATA {
+            return false;
+        }
+        buffer.extend_from_slice(&frame);
+        true
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        self.try_to_vec().expect("Event serialization should not fail")
+    }
+
+    // u32 little-endian length followed by the encoded event
+    fn frame(&self) -> Vec<u8> {
+        let encoded = self.encode();
+        let mut framed = Vec::with_capacity(encoded.len() + 4);
+        framed.extend_from_slice(&(encoded.len() as u32).to_le_bytes());
+        framed.extend_from_slice(&encoded);
+        framed
+    }
+
+    fn to_log_line(&self) -> String {
+        let hex: String = self.encode().iter().map(|byte| format!("{:02x}", byte)).collect();
+        format!("{}{}", EVENT_LOG_PREFIX, hex)
+    }
+
+    // Decodes a log line written by the Log sink; other log lines return None
+    pub fn from_log_line(line: &str) -> Option<Self> {
+        let hex = line.strip_prefix(EVENT_LOG_PREFIX)?;
+        if hex.len() % 2 != 0 {
+            return None;
+        }
+        let bytes = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+            .collect::<Option<Vec<u8>>>()?;
+        Self::try_from_slice(&bytes).ok()
+    }
+
+    // Decodes the events written by the ReturnData sink
+    pub fn decode_return_data(mut data: &[u8]) -> Option<Vec<Self>> {
+        let mut events = Vec::new();
+        while !data.is_empty() {
+            if data.len() < 4 {
+                return None;
+            }
+            let len = u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize;
+            let record = data.get(4..4 + len)?;
+            events.push(Self::try_from_slice(record).ok()?);
+            data = &data[4 + len..];
+        }
+        Some(events)
+    }
+}
+
+// Selects the sink for the rest of the instruction. Switching sinks starts a new return data buffer.
+pub fn set_event_sink(sink: EventSink) {
+    EVENT_SINK.with(|current| current.set(sink));
+    RETURN_DATA_EVENTS.with(|buffer| buffer.borrow_mut().clear());
+}
+
+pub fn event_sink() -> EventSink {
+    EVENT_SINK.with(|current| current.get())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trove_opened() -> ProtocolEvent {
+        ProtocolEvent::TroveOpened {
+            collateral: "BTC".to_string(),
+            borrower: "borrower".to_string(),
+            coll: 2_000_000_000_000_000_000,
+            debt: 10_000_000_000_000_000_000_000,
+            stake: 2_000_000_000_000_000_000,
+        }
+    }
+
+    #[test]
+    fn test_log_line_round_trip() {
+        let event = trove_opened();
+        let line = event.to_log_line();
+        assert!(line.starts_with(EVENT_LOG_PREFIX));
+        assert_eq!(ProtocolEvent::from_log_line(&line), Some(event));
+        assert_eq!(ProtocolEvent::from_log_line("TroveOpened: borrower = x"), None);
+    }
+
+    #[test]
+    fn test_return_data_round_trip() {
+        let admin = ProtocolEvent::Admin {
+            caller: "guardian".to_string(),
+            action: AdminAction::PriceBreakerCleared { token: "BTC".to_string() },
+        };
+        let mut data = trove_opened().frame();
+        data.extend_from_slice(&admin.frame());
+        assert_eq!(ProtocolEvent::decode_return_data(&data), Some(vec![trove_opened(), admin]));
+
+        // A truncated record is rejected rather than partially decoded
+        assert_eq!(ProtocolEvent::decode_return_data(&data[..data.len() - 1]), None);
+    }
+
+    #[test]
+    fn test_return_data_is_capped() {
+        let event = trove_opened();
+        let frame_len = event.frame().len();
+        let mut buffer = Vec::new();
+        while event.append_frame(&mut buffer) {}
+
+        assert!(buffer.len() <= MAX_RETURN_DATA);
+        assert!(buffer.len() + frame_len > MAX_RETURN_DATA);
+        let events = ProtocolEvent::decode_return_data(&buffer).unwrap();
+        assert_eq!(events.len(), MAX_RETURN_DATA / frame_len);
+    }
+}
@@ -1,4 +1,5 @@
 use borsh::{BorshDeserialize, BorshSerialize};
+use crate::dependencies::protocol_error::ProtocolError;
 
 pub trait IERC2612 {
     /// Sets `amount` as the allowance of `spender` over `owner`'s tokens,
@@ -22,7 +23,7 @@ pub trait IERC2612 {
         v: u8,
         r: [u8; 32],
         s: [u8; 32]
-    ) -> Result<(), String>;
+    ) -> Result<(), ProtocolError>;
 
     /// Returns the current ERC2612 nonce for `owner`.
     ///
@@ -78,7 +79,7 @@ impl IERC2612 for ERC2612Impl {
         v: u8,
         r: [u8; 32],
         s: [u8; 32]
-    ) -> Result<(), String> {
+    ) -> Result<(), ProtocolError> {
         // Implementation here
         Ok(())
     }
@@ -1,6 +1,6 @@
 use borsh::{BorshDeserialize, BorshSerialize};
 
-#[derive(BorshSerialize, BorshDeserialize)]
+#[derive(Default, BorshSerialize, BorshDeserialize)]
 pub struct RoundData {
     pub round_id: u128,
     pub answer: i128,
@@ -16,9 +16,9 @@ pub trait AggregatorV3Interface {
 
     fn version(&self) -> u128;
 
-    // getRoundData and latestRoundData should both raise "No data present"
-    // if they do not have data to report, instead of returning unset values
-    // which could be misinterpreted as actual reported values.
+    // getRoundData and latestRoundData return a zeroed RoundData if they have
+    // no data to report. Consumers must treat `updated_at == 0` as no data
+    // rather than as an actual reported value.
     
     fn get_round_data(
         &self,
@@ -0,0 +1,23 @@
+use arch_program::pubkey::Pubkey;
+use crate::core::Debt_token::DebtToken;
+
+// keccak256("ERC3156FlashBorrower.onFlashLoan"), returned by a borrower that accepts the loan
+pub const FLASH_LOAN_CALLBACK_SUCCESS: [u8; 32] = [
+    0x43, 0x91, 0x48, 0xf0, 0xbb, 0xc6, 0x82, 0xca, 0x07, 0x9e, 0x46, 0xd6, 0xe2, 0xc2, 0xf0, 0xc1,
+    0xe3, 0xb8, 0x20, 0xf1, 0xa2, 0x91, 0xb0, 0x69, 0xd8, 0x88, 0x2a, 0xbf, 0x8c, 0xf1, 0x8d, 0xd9,
+];
+
+// ERC-3156 style flash loan receiver
+pub trait FlashBorrower {
+    // Receives `amount` debt tokens. Before returning, the borrower must hold at least
+    // `amount + fee` so the token can pull the repayment from its balance.
+    // Must return FLASH_LOAN_CALLBACK_SUCCESS, any other value aborts the loan.
+    fn on_flash_loan(
+        &mut self,
+        token: &mut DebtToken,
+        initiator: Pubkey,
+        amount: u128,
+        fee: u128,
+        data: &[u8],
+    ) -> [u8; 32];
+}
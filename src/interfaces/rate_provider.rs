@@ -0,0 +1,8 @@
+// Exchange rate of a yield-bearing collateral share in its underlying asset,
+// e.g. a liquid-staked BTC token or a DEX LP share
+pub trait RateProvider {
+    // Underlying amount per share, with `rate_decimals()` decimals
+    fn get_rate(&self) -> u128;
+
+    fn rate_decimals(&self) -> u8;
+}
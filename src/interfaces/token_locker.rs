@@ -1,4 +1,5 @@
 use borsh::{BorshDeserialize, BorshSerialize};
+use crate::dependencies::protocol_error::ProtocolError;
 
 #[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
 pub struct LockData {
@@ -15,63 +16,63 @@ pub struct ExtendLockData {
 
 // Define a trait for ITokenLocker
 pub trait ITokenLocker {
-    fn extend_lock(&self, amount: u256, weeks: u256, new_weeks: u256) -> Result<(), String>;
+    fn extend_lock(&self, amount: u256, weeks: u256, new_weeks: u256) -> Result<(), ProtocolError>;
     
-    fn extend_many(&self, new_extend_locks: Vec<ExtendLockData>) -> Result<(), String>;
+    fn extend_many(&self, new_extend_locks: Vec<ExtendLockData>) -> Result<(), ProtocolError>;
     
-    fn freeze(&self) -> Result<(), String>;
+    fn freeze(&self) -> Result<(), ProtocolError>;
     
-    fn get_account_weight_write(&self, account: String) -> Result<u256, String>;
+    fn get_account_weight_write(&self, account: String) -> Result<u256, ProtocolError>;
     
-    fn get_total_weight_write(&self) -> Result<u256, String>;
+    fn get_total_weight_write(&self) -> Result<u256, ProtocolError>;
     
-    fn lock(&self, account: String, amount: u256, weeks: u256) -> Result<(), String>;
+    fn lock(&self, account: String, amount: u256, weeks: u256) -> Result<(), ProtocolError>;
     
-    fn lock_many(&self, account: String, new_locks: Vec<LockData>) -> Result<(), String>;
+    fn lock_many(&self, account: String, new_locks: Vec<LockData>) -> Result<(), ProtocolError>;
     
-    fn set_penalty_withdrawals_enabled(&self, enabled: bool) -> Result<(), String>;
+    fn set_penalty_withdrawals_enabled(&self, enabled: bool) -> Result<(), ProtocolError>;
     
-    fn unfreeze(&self, keep_incentives_vote: bool) -> Result<(), String>;
+    fn unfreeze(&self, keep_incentives_vote: bool) -> Result<(), ProtocolError>;
     
-    fn withdraw_expired_locks(&self, weeks: u256) -> Result<(), String>;
+    fn withdraw_expired_locks(&self, weeks: u256) -> Result<(), ProtocolError>;
     
-    fn withdraw_with_penalty(&self, amount_to_withdraw: u256) -> Result<(u256, u256), String>;
+    fn withdraw_with_penalty(&self, amount_to_withdraw: u256) -> Result<(u256, u256), ProtocolError>;
     
-    fn max_lock_weeks(&self) -> Result<u256, String>;
+    fn max_lock_weeks(&self) -> Result<u256, ProtocolError>;
     
-    fn babel_core(&self) -> Result<String, String>;
+    fn babel_core(&self) -> Result<String, ProtocolError>;
     
-    fn get_account_active_locks(&self, account: String, min_weeks: u256) -> Result<(Vec<LockData>, u256), String>;
+    fn get_account_active_locks(&self, account: String, min_weeks: u256) -> Result<(Vec<LockData>, u256), ProtocolError>;
     
-    fn get_account_balances(&self, account: String) -> Result<(u256, u256), String>;
+    fn get_account_balances(&self, account: String) -> Result<(u256, u256), ProtocolError>;
     
-    fn get_account_weight(&self, account: String) -> Result<u256, String>;
+    fn get_account_weight(&self, account: String) -> Result<u256, ProtocolError>;
     
-    fn get_account_weight_at(&self, account: String, week: u256) -> Result<u256, String>;
+    fn get_account_weight_at(&self, account: String, week: u256) -> Result<u256, ProtocolError>;
     
-    fn get_total_weight(&self) -> Result<u256, String>;
+    fn get_total_weight(&self) -> Result<u256, ProtocolError>;
     
-    fn get_total_weight_at(&self, week: u256) -> Result<u256, String>;
+    fn get_total_weight_at(&self, week: u256) -> Result<u256, ProtocolError>;
     
-    fn get_week(&self) -> Result<u256, String>;
+    fn get_week(&self) -> Result<u256, ProtocolError>;
     
-    fn get_withdraw_with_penalty_amounts(&self, account: String, amount_to_withdraw: u256) -> Result<(u256, u256), String>;
+    fn get_withdraw_with_penalty_amounts(&self, account: String, amount_to_withdraw: u256) -> Result<(u256, u256), ProtocolError>;
     
-    fn guardian(&self) -> Result<String, String>;
+    fn guardian(&self) -> Result<String, ProtocolError>;
     
-    fn incentive_voter(&self) -> Result<String, String>;
+    fn incentive_voter(&self) -> Result<String, ProtocolError>;
     
-    fn lock_to_token_ratio(&self) -> Result<u256, String>;
+    fn lock_to_token_ratio(&self) -> Result<u256, ProtocolError>;
     
-    fn lock_token(&self) -> Result<String, String>;
+    fn lock_token(&self) -> Result<String, ProtocolError>;
     
-    fn owner(&self) -> Result<String, String>;
+    fn owner(&self) -> Result<String, ProtocolError>;
     
-    fn penalty_withdrawals_enabled(&self) -> Result<bool, String>;
+    fn penalty_withdrawals_enabled(&self) -> Result<bool, ProtocolError>;
     
-    fn babel_core(&self) -> Result<String, String>;
+    fn babel_core(&self) -> Result<String, ProtocolError>;
     
-    fn total_decay_rate(&self) -> Result<u256, String>;
+    fn total_decay_rate(&self) -> Result<u256, ProtocolError>;
     
-    fn total_updated_week(&self) -> Result<u256, String>;
+    fn total_updated_week(&self) -> Result<u256, ProtocolError>;
 }
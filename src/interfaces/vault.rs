@@ -1,4 +1,5 @@
 use borsh::{BorshDeserialize, BorshSerialize};
+use crate::dependencies::protocol_error::ProtocolError;
 
 #[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
 pub struct InitialAllowance {
@@ -9,7 +10,7 @@ pub struct InitialAllowance {
 // Define the trait for IBabelVault
 pub trait IBabelVault {
     // Method to allocate new emissions
-    fn allocate_new_emissions(&self, id: u256) -> Result<u256, String>;
+    fn allocate_new_emissions(&self, id: u256) -> Result<u256, ProtocolError>;
     
     // Method to batch claim rewards
     fn batch_claim_rewards(
@@ -18,22 +19,22 @@ pub trait IBabelVault {
         boost_delegate: String,
         reward_contracts: Vec<String>,
         max_fee_pct: u256
-    ) -> Result<bool, String>;
+    ) -> Result<bool, ProtocolError>;
     
     // Method to increase unallocated supply
-    fn increase_unallocated_supply(&self, amount: u256) -> Result<bool, String>;
+    fn increase_unallocated_supply(&self, amount: u256) -> Result<bool, ProtocolError>;
     
     // Method to register a new receiver
-    fn register_receiver(&self, receiver: String, count: u256) -> Result<bool, String>;
+    fn register_receiver(&self, receiver: String, count: u256) -> Result<bool, ProtocolError>;
     
     // Method to set the boost calculator address
-    fn set_boost_calculator(&self, boost_calculator: String) -> Result<bool, String>;
+    fn set_boost_calculator(&self, boost_calculator: String) -> Result<bool, ProtocolError>;
     
     // Method to set boost delegation parameters
-    fn set_boost_delegation_params(&self, is_enabled: bool, fee_pct: u256, callback: String) -> Result<bool, String>;
+    fn set_boost_delegation_params(&self, is_enabled: bool, fee_pct: u256, callback: String) -> Result<bool, ProtocolError>;
     
     // Method to set emission schedule address
-    fn set_emission_schedule(&self, emission_schedule: String) -> Result<bool, String>;
+    fn set_emission_schedule(&self, emission_schedule: String) -> Result<bool, ProtocolError>;
     
     // Method to set initial parameters
     fn set_initial_parameters(
@@ -44,25 +45,25 @@ pub trait IBabelVault {
         initial_lock_weeks: u64,
         fixed_initial_amounts: Vec<u128>,
         initial_allowances: Vec<InitialAllowance>
-    ) -> Result<(), String>;
+    ) -> Result<(), ProtocolError>;
     
     // Method to set receiver's active status
-    fn set_receiver_is_active(&self, id: u256, is_active: bool) -> Result<bool, String>;
+    fn set_receiver_is_active(&self, id: u256, is_active: bool) -> Result<bool, ProtocolError>;
     
     // Method to transfer allocated tokens
-    fn transfer_allocated_tokens(&self, claimant: String, receiver: String, amount: u256) -> Result<bool, String>;
+    fn transfer_allocated_tokens(&self, claimant: String, receiver: String, amount: u256) -> Result<bool, ProtocolError>;
     
     // Method to transfer tokens
-    fn transfer_tokens(&self, token: String, receiver: String, amount: u256) -> Result<bool, String>;
+    fn transfer_tokens(&self, token: String, receiver: String, amount: u256) -> Result<bool, ProtocolError>;
     
     // View methods
-    fn babel_core(&self) -> Result<String, String>;
+    fn babel_core(&self) -> Result<String, ProtocolError>;
     
-    fn allocated(&self, account: String) -> Result<u256, String>;
+    fn allocated(&self, account: String) -> Result<u256, ProtocolError>;
     
-    fn boost_calculator(&self) -> Result<String, String>;
+    fn boost_calculator(&self) -> Result<String, ProtocolError>;
     
-    fn boost_delegation(&self, account: String) -> Result<(bool, u16, String), String>;
+    fn boost_delegation(&self, account: String) -> Result<(bool, u16, String), ProtocolError>;
     
     fn claimable_reward_after_boost(
         &self,
@@ -70,37 +71,37 @@ pub trait IBabelVault {
         receiver: String,
         boost_delegate: String,
         reward_contract: String
-    ) -> Result<(u256, u256), String>;
+    ) -> Result<(u256, u256), ProtocolError>;
   
-    fn emission_schedule(&self) -> Result<String, String>;
+    fn emission_schedule(&self) -> Result<String, ProtocolError>;
     
-    fn get_claimable_with_boost(&self, claimant: String) -> Result<(u256, u256), String>;
+    fn get_claimable_with_boost(&self, claimant: String) -> Result<(u256, u256), ProtocolError>;
     
-    fn get_week(&self) -> Result<u256, String>;
+    fn get_week(&self) -> Result<u256, ProtocolError>;
     
-    fn guardian(&self) -> Result<String, String>;
+    fn guardian(&self) -> Result<String, ProtocolError>;
     
-    fn id_to_receiver(&self, id: u256) -> Result<(String, bool), String>;
+    fn id_to_receiver(&self, id: u256) -> Result<(String, bool), ProtocolError>;
     
-    fn lock_weeks(&self) -> Result<u64, String>;
+    fn lock_weeks(&self) -> Result<u64, ProtocolError>;
     
-    fn locker(&self) -> Result<String, String>;
+    fn locker(&self) -> Result<String, ProtocolError>;
     
-    fn owner(&self) -> Result<String, String>;
+    fn owner(&self) -> Result<String, ProtocolError>;
     
-    fn claimable_boost_delegation_fees(&self, claimant: String) -> Result<u256, String>;
+    fn claimable_boost_delegation_fees(&self, claimant: String) -> Result<u256, ProtocolError>;
     
-    fn babel_token(&self) -> Result<String, String>;
+    fn babel_token(&self) -> Result<String, ProtocolError>;
     
-    fn receiver_updated_week(&self, id: u256) -> Result<u16, String>;
+    fn receiver_updated_week(&self, id: u256) -> Result<u16, ProtocolError>;
     
-    fn total_update_week(&self) -> Result<u64, String>;
+    fn total_update_week(&self) -> Result<u64, ProtocolError>;
     
-    fn unallocated_total(&self) -> Result<u128, String>;
+    fn unallocated_total(&self) -> Result<u128, ProtocolError>;
     
-    fn voter(&self) -> Result<String, String>;
+    fn voter(&self) -> Result<String, ProtocolError>;
     
-    fn weekly_emissions(&self, id: u256) -> Result<u128, String>;
+    fn weekly_emissions(&self, id: u256) -> Result<u128, ProtocolError>;
 }
 
 // Define u256 as an 
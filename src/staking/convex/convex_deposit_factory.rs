@@ -8,13 +8,13 @@ use arch_program::{
     entrypoint,
     instruction::Instruction,
     program::{invoke, next_account_info, set_return_data},
-    program_error::ProgramError,
     pubkey::Pubkey, // Ensure Pubkey is imported
     system_instruction::SystemInstruction,
     transaction_to_sign::TransactionToSign, // Ensure this import is present
     utxo::UtxoMeta,
 };
 use bitcoin::{self, Transaction}; // Import bitcoin crate and Transaction struct
+use crate::dependencies::protocol_error::ProtocolError;
 
 /// Struct representing ConvexDepositToken
 #[derive(BorshSerialize, BorshDeserialize, Clone)]
@@ -44,9 +44,9 @@ impl BabelOwnable {
         Self { babel_core, owner }
     }
 
-    pub fn only_owner(&self, caller: Pubkey) -> Result<(), ProgramError> {
+    pub fn only_owner(&self, caller: Pubkey) -> Result<(), ProtocolError> {
         if caller != self.owner {
-            return Err(ProgramError::Custom(1)); // Custom error for "Only owner"
+            return Err(ProtocolError::Unauthorized);
         }
         Ok(())
     }
@@ -75,13 +75,13 @@ impl ConvexFactory {
         pid: u64,
         caller: Pubkey,
         account_info: &AccountInfo,
-    ) -> Result<Pubkey, ProgramError> {
+    ) -> Result<Pubkey, ProtocolError> {
         // Ensure only the owner can deploy new instances
         self.babel_ownable.only_owner(caller)?;
 
         // Clone the deposit token
         let mut new_token = self.deposit_token_impl.clone();
-        new_token.initialize(pid, caller);
+        new_token.initialize(pid, caller)?;
 
         // Register the new token in the deployed tokens map
         let new_token_address = self.create_deterministic_address(pid)?;
@@ -106,7 +106,7 @@ impl ConvexFactory {
     }
 
     /// Simulate deterministic address creation (can be a hash or some logic to derive an address)
-    fn create_deterministic_address(&self, pid: u64) -> Result<Pubkey, ProgramError> {
+    fn create_deterministic_address(&self, pid: u64) -> Result<Pubkey, ProtocolError> {
         // Here, we simulate generating a new deterministic address for the token
         // In practice, this could involve hashing the pid or using some other
         // method to ensure the same input always gives the same output address.
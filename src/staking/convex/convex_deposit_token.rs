@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::time::{SystemTime, UNIX_EPOCH};
+use crate::dependencies::protocol_error::ProtocolError;
 
 pub struct IERC20 {
     pub balance: HashMap<String, u128>, // To track token balances of users
@@ -122,8 +123,10 @@ impl ConvexDepositToken {
         }
     }
 
-    pub fn initialize(&mut self, pid: u64) {
-        assert_eq!(self.lp_token.balance.len(), 0, "Already initialized");
+    pub fn initialize(&mut self, pid: u64) -> Result<(), ProtocolError> {
+        if !self.lp_token.balance.is_empty() {
+            return Err(ProtocolError::AlreadyInitialized);
+        }
 
         let (lp_token, _, _, crv_rewards, stash, _) = self.booster.pool_info(pid);
 
@@ -132,7 +135,9 @@ impl ConvexDepositToken {
         self.crv_rewards = Box::new(DefaultRewardPool::new());
 
         let (_, rewards) = self.curve_proxy.token_info(&stash);
-        assert_ne!(rewards, "", "Pool has no CVX rewards");
+        if rewards.is_empty() {
+            return Err(ProtocolError::InvalidParameter); // Pool has no CVX rewards
+        }
         self.cvx_rewards = Box::new(DefaultRewardPool::new());
 
         // Setup token approval and metadata
@@ -140,10 +145,13 @@ impl ConvexDepositToken {
         self.name = format!("Babel {} Convex Deposit", lp_token);
 
         self.period_finish = Self::current_timestamp() - 1;
+        Ok(())
     }
 
-    pub fn deposit(&mut self, receiver: &str, amount: u128) -> bool {
-        assert!(amount > 0, "Cannot deposit zero");
+    pub fn deposit(&mut self, receiver: &str, amount: u128) -> Result<(), ProtocolError> {
+        if amount == 0 {
+            return Err(ProtocolError::ZeroAmount);
+        }
         self.lp_token.transfer_from(receiver, &self.symbol, amount);
 
         self.booster.deposit(self.deposit_pid, amount, true);
@@ -154,14 +162,16 @@ impl ConvexDepositToken {
 
         self.update_integrals(receiver, *balance, self.total_supply);
         if Self::current_timestamp() / 604800 >= self.period_finish / 604800 {
-            self.fetch_rewards();
+            self.fetch_rewards()?;
         }
 
-        true
+        Ok(())
     }
 
-    pub fn withdraw(&mut self, receiver: &str, amount: u128) -> bool {
-        assert!(amount > 0, "Cannot withdraw zero");
+    pub fn withdraw(&mut self, receiver: &str, amount: u128) -> Result<(), ProtocolError> {
+        if amount == 0 {
+            return Err(ProtocolError::ZeroAmount);
+        }
         let balance = self.balance_of.entry(receiver.to_string()).or_insert(0);
         *balance -= amount;
         self.total_supply -= amount;
@@ -171,10 +181,10 @@ impl ConvexDepositToken {
 
         self.update_integrals(receiver, *balance, self.total_supply);
         if Self::current_timestamp() / 604800 >= self.period_finish / 604800 {
-            self.fetch_rewards();
+            self.fetch_rewards()?;
         }
 
-        true
+        Ok(())
     }
 
     fn claim_reward(&mut self, claimant: &str, receiver: &str) -> [u128; 3] {
@@ -225,11 +235,14 @@ impl ConvexDepositToken {
         }
     }
 
-    fn fetch_rewards(&mut self) {
-        assert!(Self::current_timestamp() / 604800 >= self.period_finish / 604800, "Can only fetch once per week");
+    fn fetch_rewards(&mut self) -> Result<(), ProtocolError> {
+        if Self::current_timestamp() / 604800 < self.period_finish / 604800 {
+            return Err(ProtocolError::RewardsAlreadyFetched);
+        }
 
         self.update_integrals("", 0, self.total_supply);
         // Fetch rewards logic...
+        Ok(())
     }
 
     fn current_timestamp() -> u64 {
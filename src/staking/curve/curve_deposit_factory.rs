@@ -1,6 +1,7 @@
 use crate::babel_ownable::BabelOwnable;
 use crate::curve_proxy::CurveProxy;
 use crate::curve_deposit_token::CurveDepositToken;
+use crate::dependencies::protocol_error::ProtocolError;
 use arch_program_sdk::ArchClient;
 use std::collections::HashMap;
 
@@ -32,9 +33,9 @@ impl CurveFactory {
     /// Deploy a new instance of the CurveDepositToken with a deterministic address based on gauge
     /// After calling this function, the owner should also call `Vault.register_receiver`
     /// to enable BABEL emissions on the newly deployed CurveDepositToken
-    pub fn deploy_new_instance(&mut self, gauge: String) -> Result<(), &'static str> {
+    pub fn deploy_new_instance(&mut self, gauge: String) -> Result<(), ProtocolError> {
         if !self.babel_core.is_owner() {
-            return Err("Only owner can deploy new instances");
+            return Err(ProtocolError::Unauthorized);
         }
 
         // Generate deterministic address for the new deployment
@@ -42,7 +43,7 @@ impl CurveFactory {
 
         // Initialize the new deposit token with the gauge address
         let mut new_token = self.deposit_token_impl.clone();
-        new_token.initialize(gauge.clone());
+        new_token.initialize(gauge.clone())?;
 
         // Set per-gauge approval in the CurveProxy contract
         self.curve_proxy.set_per_gauge_approval(deposit_token_address.clone(), gauge.clone());
@@ -57,11 +58,11 @@ impl CurveFactory {
     }
 
     /// Predict the deterministic address of a deposit token based on the gauge address
-    pub fn get_deposit_token(&self, gauge: String) -> Result<String, &'static str> {
+    pub fn get_deposit_token(&self, gauge: String) -> Result<String, ProtocolError> {
         if let Some(token_address) = self.deployed_tokens.get(&gauge) {
             Ok(token_address.clone())
         } else {
-            Err("Deposit token not found for given gauge")
+            Err(ProtocolError::InvalidParameter)
         }
     }
 
@@ -3,6 +3,7 @@ use std::collections::HashMap;
 use arch_program::{CurveProxy, BabelVault, LiquidityGauge, EmissionReceiver, Token, ArchUtxo, ArchSdk, TransferResult};
 use chrono::prelude::*;
 use serde::{Serialize, Deserialize};
+use crate::dependencies::protocol_error::ProtocolError;
 
 #[derive(BorshSerialize, BorshDeserialize, Debug)]
 pub struct CurveDepositToken {
@@ -52,9 +53,9 @@ impl CurveDepositToken {
         }
     }
 
-    pub fn initialize(&mut self, gauge: LiquidityGauge) -> Result<(), &'static str> {
+    pub fn initialize(&mut self, gauge: LiquidityGauge) -> Result<(), ProtocolError> {
         if self.gauge.is_some() {
-            return Err("Already initialized");
+            return Err(ProtocolError::AlreadyInitialized);
         }
 
         let lp_token = gauge.lp_token();
@@ -70,20 +71,20 @@ impl CurveDepositToken {
         Ok(())
     }
 
-    pub fn notify_registered_id(&mut self, assigned_ids: Vec<u64>) -> Result<(), &'static str> {
+    pub fn notify_registered_id(&mut self, assigned_ids: Vec<u64>) -> Result<(), ProtocolError> {
         if self.emission_id.is_some() {
-            return Err("Already registered");
+            return Err(ProtocolError::AlreadyInitialized);
         }
         if assigned_ids.len() != 1 {
-            return Err("Incorrect ID count");
+            return Err(ProtocolError::InvalidParameter);
         }
         self.emission_id = Some(assigned_ids[0]);
         Ok(())
     }
 
-    pub fn deposit(&mut self, receiver: String, amount: u128) -> Result<(), &'static str> {
+    pub fn deposit(&mut self, receiver: String, amount: u128) -> Result<(), ProtocolError> {
         if amount == 0 {
-            return Err("Cannot deposit zero");
+            return Err(ProtocolError::ZeroAmount);
         }
 
         let lp_token = self.lp_token.as_ref().unwrap();
@@ -102,14 +103,14 @@ impl CurveDepositToken {
         Ok(())
     }
 
-    pub fn withdraw(&mut self, receiver: String, amount: u128) -> Result<(), &'static str> {
+    pub fn withdraw(&mut self, receiver: String, amount: u128) -> Result<(), ProtocolError> {
         if amount == 0 {
-            return Err("Cannot withdraw zero");
+            return Err(ProtocolError::ZeroAmount);
         }
 
-        let balance = self.balance_of.get_mut(&receiver).ok_or("Insufficient balance")?;
+        let balance = self.balance_of.get_mut(&receiver).ok_or(ProtocolError::InsufficientBalance)?;
         if *balance < amount {
-            return Err("Insufficient balance");
+            return Err(ProtocolError::InsufficientBalance);
         }
 
         let lp_token = self.lp_token.as_ref().unwrap();
@@ -125,7 +126,7 @@ impl CurveDepositToken {
         Ok(())
     }
 
-    fn claim_reward(&mut self, claimant: String, receiver: String) -> Result<[u128; 2], &'static str> {
+    fn claim_reward(&mut self, claimant: String, receiver: String) -> Result<[u128; 2], ProtocolError> {
         self.update_integrals(&claimant, *self.balance_of.get(&claimant).unwrap_or(&0), self.total_supply);
 
         let pending_rewards = self.stored_pending_reward.remove(&claimant).unwrap_or([0; 2]);
@@ -141,7 +142,7 @@ impl CurveDepositToken {
         Ok(pending_rewards)
     }
 
-    pub fn claimable_reward(&self, account: &String) -> Result<(u128, u128), &'static str> {
+    pub fn claimable_reward(&self, account: &String) -> Result<(u128, u128), ProtocolError> {
         let updated = std::cmp::min(self.period_finish, Utc::now().timestamp());
         let duration = updated - self.last_update;
 
@@ -161,7 +162,7 @@ impl CurveDepositToken {
         Ok((amounts[0], amounts[1]))
     }
 
-    fn fetch_rewards_if_needed(&mut self) -> Result<(), &'static str> {
+    fn fetch_rewards_if_needed(&mut self) -> Result<(), ProtocolError> {
         if Utc::now().timestamp() / 1.week() >= self.period_finish / 1.week() {
             self.fetch_rewards();
         }
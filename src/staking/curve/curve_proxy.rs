@@ -1,5 +1,6 @@
 use crate::babel_ownable::BabelOwnable;
 use crate::constants::BIMA_100_PCT;
+use crate::dependencies::protocol_error::ProtocolError;
 use crate::interfaces::{
     ILiquidityGauge, ICurveProxy, IGaugeController, IERC20, IMinter, IFeeDistributor, IVotingEscrow, IAragon,
 };
@@ -89,11 +90,12 @@ impl CurveProxy {
         }
     }
 
-    pub fn set_crv_fee_pct(&mut self, fee_pct: u64) {
+    pub fn set_crv_fee_pct(&mut self, fee_pct: u64) -> Result<(), ProtocolError> {
         if fee_pct > BIMA_100_PCT {
-            panic!("Invalid setting");
+            return Err(ProtocolError::InvalidParameter);
         }
         self.crv_fee_pct = fee_pct;
+        Ok(())
     }
 
     pub fn set_vote_manager(&mut self, vote_manager: String) {
@@ -166,7 +168,7 @@ impl CurveProxy {
         }
     }
 
-    pub fn execute(&mut self, target: &str, data: &[u8]) {
+    pub fn execute(&mut self, target: &str, data: &[u8]) -> Result<(), ProtocolError> {
         let selector = &data[0..4];
         if !self.is_owner_or_vote_manager(self.babel_core.owner()) {
             if !self
@@ -175,10 +177,11 @@ impl CurveProxy {
                 .and_then(|permissions| permissions.get(target))
                 .map_or(false, |selectors| selectors.get(selector).copied().unwrap_or(false))
             {
-                panic!("Not permitted");
+                return Err(ProtocolError::Unauthorized);
             }
         }
         self.call_function(target, data);
+        Ok(())
     }
 
     fn update_lock(&mut self, amount: u64, unlock: u64, max_unlock: u64) {
@@ -58,7 +58,7 @@ mod tests {
         let mut admin = InterimAdmin::new("babel_core".to_string(), "owner".to_string());
         let payload = vec![Action { target: "target".to_string(), data: vec![1, 2, 3] }];
 
-        admin.create_new_proposal("owner", payload); // Implement the logic for this method...
+        admin.create_new_proposal("owner", payload).unwrap(); // Implement the logic for this method...
 
         // Assert the proposal was created successfully...
     }